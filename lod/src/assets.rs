@@ -0,0 +1,13 @@
+//! Where the data files live, as `archive/entry` paths for `LodManager::try_get_bytes`.
+//!
+//! MM6, MM7 and MM8 agree on the names of the files read so far, keeping them in one
+//! place lets a game that moves them (e.g. the localized archives of MM8) be handled here.
+
+/// Tile descriptions used by the outdoor maps, the same in all three games.
+pub const TILE_DATA_ENTRY: &str = "icons/dtile.bin";
+
+/// Archive holding textures and palettes, the same in all three games.
+pub const BITMAPS_ARCHIVE: &str = "bitmaps";
+
+/// Archive holding the sprites of decorations and monsters, the same in all three games.
+pub const SPRITES_ARCHIVE: &str = "sprites";
@@ -12,6 +12,10 @@ use crate::{
     LodManager,
 };
 
+/// Per-instance record of a decoration placed on the map.
+/// The odm has no separate extra-attribute table for decorations: the instance flags
+/// are the `attributes` field below, while runtime state that changes while playing
+/// (e.g. a torch being lit) is stored in the map delta (.ddm) which is not parsed yet.
 #[repr(C)]
 #[derive(Default, Debug)]
 pub struct BillboardData {
@@ -61,6 +65,73 @@ pub struct Billboard {
     pub data: BillboardData,
 }
 
+/// Decoration names the game gives a special behavior, like the wells and
+/// obelisks granting bonuses. The declist has no flag for them so they are matched by name.
+const SPECIAL_OBJECT_NAMES: [&str; 6] =
+    ["well", "fountain", "obelisk", "altar", "shrine", "pedestal"];
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum ObjectKind {
+    /// Invisible placeholder, like the party start
+    Marker,
+    /// Wells, fountains, obelisks, altars and shrines
+    Special,
+    /// Runs an event when clicked or touched
+    Interactive,
+    Decoration,
+}
+
+impl Billboard {
+    /// Whether the decoration has a sprite to draw: invisible instances, like event
+    /// triggers, and declist entries flagged as no-draw or marker are skipped by the game.
+    pub fn is_drawn(&self, declist_item: &DDecListItem) -> bool {
+        !self.data.is_invisible() && !declist_item.is_no_draw() && !declist_item.is_marker()
+    }
+
+    pub fn kind(&self, declist_item: &DDecListItem) -> ObjectKind {
+        if declist_item.is_marker() {
+            ObjectKind::Marker
+        } else if SPECIAL_OBJECT_NAMES
+            .iter()
+            .any(|name| self.declist_name.contains(name))
+        {
+            ObjectKind::Special
+        } else if self.data.event != 0
+            || self.data.is_triggered_by_touch()
+            || self.data.is_triggered_by_monster()
+            || self.data.is_triggered_by_object()
+        {
+            ObjectKind::Interactive
+        } else {
+            ObjectKind::Decoration
+        }
+    }
+}
+
+/// A chest placed on the map, its contents are not stored in the odm
+/// but are looked up by the game through the event attached to it.
+#[derive(Debug)]
+pub struct Chest {
+    pub declist_name: String,
+    pub declist_id: u16,
+    pub position: [i32; 3],
+    pub event: i16,
+    pub event_variable: i16,
+}
+
+impl From<&Billboard> for Chest {
+    fn from(billboard: &Billboard) -> Self {
+        Self {
+            declist_name: billboard.declist_name.clone(),
+            declist_id: billboard.data.declist_id,
+            position: billboard.data.position,
+            event: billboard.data.event,
+            event_variable: billboard.data.event_variable,
+        }
+    }
+}
+
+/// Reads `count` MM6 decoration records followed by their declist names.
 pub(super) fn read_billboards(
     cursor: &mut Cursor<&[u8]>,
     count: usize,
@@ -161,4 +232,57 @@ impl BillboardManager {
             d_sft_frame: sft_frame.clone(),
         })
     }
+
+    pub fn kind(&self, billboard: &Billboard) -> Option<ObjectKind> {
+        let declist_item = self
+            .d_declist
+            .items
+            .get(billboard.data.declist_id as usize)?;
+        Some(billboard.kind(declist_item))
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn billboard(name: &str, event: i16) -> Billboard {
+        Billboard {
+            declist_name: name.into(),
+            data: BillboardData {
+                event,
+                ..Default::default()
+            },
+        }
+    }
+
+    #[test]
+    fn kind_works() {
+        let item = DDecListItem::default();
+        assert_eq!(billboard("well", 12).kind(&item), ObjectKind::Special);
+        assert_eq!(billboard("obelisk", 0).kind(&item), ObjectKind::Special);
+        assert_eq!(billboard("barrel", 3).kind(&item), ObjectKind::Interactive);
+        assert_eq!(billboard("tree01", 0).kind(&item), ObjectKind::Decoration);
+
+        let mut marker = DDecListItem::default();
+        marker.attributes = 0x0020;
+        assert_eq!(
+            billboard("party start", 0).kind(&marker),
+            ObjectKind::Marker
+        );
+    }
+
+    #[test]
+    fn is_drawn_works() {
+        let item = DDecListItem::default();
+        let mut tree = billboard("tree01", 0);
+        assert!(tree.is_drawn(&item));
+
+        let mut no_draw = DDecListItem::default();
+        no_draw.attributes = 0x0002;
+        assert!(!tree.is_drawn(&no_draw));
+
+        tree.data.attributes = 0x0040;
+        assert!(!tree.is_drawn(&item));
+    }
 }
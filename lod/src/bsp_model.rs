@@ -19,6 +19,43 @@ pub struct BSPModel {
     pub indices: Vec<u32>,
 }
 
+impl BSPModel {
+    /// Footprint of the model on the ground, the convex hull of its vertices projected on
+    /// the (x, z) plane of the viewer coordinates, counterclockwise.
+    pub fn outline(&self) -> Polygon {
+        let mut points: Vec<[f32; 2]> = self.vertices.iter().map(|v| [v[0], v[2]]).collect();
+        points.sort_by(|a, b| a[0].total_cmp(&b[0]).then(a[1].total_cmp(&b[1])));
+        points.dedup();
+        if points.len() < 3 {
+            return Polygon { points };
+        }
+
+        let cross = |o: [f32; 2], a: [f32; 2], b: [f32; 2]| {
+            (a[0] - o[0]) * (b[1] - o[1]) - (a[1] - o[1]) * (b[0] - o[0])
+        };
+        let mut hull: Vec<[f32; 2]> = Vec::with_capacity(points.len() + 1);
+        for pass in [points.clone(), points.into_iter().rev().collect()] {
+            let start = hull.len();
+            for p in pass {
+                while hull.len() >= start + 2
+                    && cross(hull[hull.len() - 2], hull[hull.len() - 1], p) <= 0.
+                {
+                    hull.pop();
+                }
+                hull.push(p);
+            }
+            hull.pop();
+        }
+        Polygon { points: hull }
+    }
+}
+
+/// A closed polygon, the last point connects back to the first one.
+#[derive(Debug, Clone, Default, PartialEq)]
+pub struct Polygon {
+    pub points: Vec<[f32; 2]>,
+}
+
 #[allow(dead_code)]
 #[derive(Debug, Default)]
 pub struct BSPModelHeader {
@@ -349,8 +386,31 @@ fn decode_indices(model: &BSPModel) -> Vec<u32> {
 
 #[cfg(test)]
 mod tests {
+    use super::*;
     use crate::{get_lod_path, odm::Odm, LodManager};
 
+    #[test]
+    fn outline_works() {
+        let mut vertices = Vec::new();
+        for [x, z] in [[0., 0.], [4., 0.], [4., 4.], [0., 4.], [2., 2.], [1., 3.]] {
+            vertices.push([x, 0., z]);
+            vertices.push([x, 10., z]);
+        }
+        let model = BSPModel {
+            header: BSPModelHeader::default(),
+            vertices,
+            faces: Vec::new(),
+            unk: Vec::new(),
+            texture_names: Vec::new(),
+            bsp_nodes: Vec::new(),
+            indices: Vec::new(),
+        };
+        assert_eq!(
+            model.outline().points,
+            [[0., 0.], [4., 0.], [4., 4.], [0., 4.]]
+        );
+    }
+
     #[test]
     fn get_map_works() {
         let lod_manager = LodManager::new(get_lod_path()).unwrap();
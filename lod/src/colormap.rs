@@ -0,0 +1,86 @@
+/// Gradients used to color data such as the terrain height.
+/// Colors are returned in sRGB space with alpha set to 1.0.
+#[derive(Debug, Default, Clone, Copy, PartialEq, Eq)]
+pub enum ColorMap {
+    #[default]
+    Grayscale,
+    Terrain,
+    Viridis,
+    Magma,
+}
+
+const GRAYSCALE: &[[f32; 3]] = &[[0.0, 0.0, 0.0], [1.0, 1.0, 1.0]];
+
+const TERRAIN: &[[f32; 3]] = &[
+    [0.13, 0.40, 0.16],
+    [0.55, 0.70, 0.33],
+    [0.60, 0.46, 0.30],
+    [1.0, 1.0, 1.0],
+];
+
+const VIRIDIS: &[[f32; 3]] = &[
+    [0.267, 0.004, 0.329],
+    [0.231, 0.322, 0.545],
+    [0.129, 0.569, 0.549],
+    [0.369, 0.788, 0.384],
+    [0.992, 0.906, 0.145],
+];
+
+const MAGMA: &[[f32; 3]] = &[
+    [0.0, 0.0, 0.016],
+    [0.317, 0.071, 0.486],
+    [0.718, 0.216, 0.475],
+    [0.988, 0.537, 0.380],
+    [0.988, 0.992, 0.749],
+];
+
+impl ColorMap {
+    pub const ALL: [ColorMap; 4] = [
+        ColorMap::Grayscale,
+        ColorMap::Terrain,
+        ColorMap::Viridis,
+        ColorMap::Magma,
+    ];
+
+    fn stops(&self) -> &'static [[f32; 3]] {
+        match self {
+            ColorMap::Grayscale => GRAYSCALE,
+            ColorMap::Terrain => TERRAIN,
+            ColorMap::Viridis => VIRIDIS,
+            ColorMap::Magma => MAGMA,
+        }
+    }
+
+    /// Samples the gradient, `t` is clamped to 0.0..=1.0.
+    pub fn color(&self, t: f32) -> [f32; 4] {
+        let stops = self.stops();
+        let t = if t.is_nan() { 0.0 } else { t.clamp(0.0, 1.0) };
+        let pos = t * (stops.len() - 1) as f32;
+        let i = (pos as usize).min(stops.len() - 2);
+        let f = pos - i as f32;
+        let (a, b) = (stops[i], stops[i + 1]);
+        [
+            a[0] + (b[0] - a[0]) * f,
+            a[1] + (b[1] - a[1]) * f,
+            a[2] + (b[2] - a[2]) * f,
+            1.0,
+        ]
+    }
+
+    /// Maps every value between the minimum and the maximum of `values` onto the gradient.
+    pub fn normalized_colors(&self, values: impl Iterator<Item = f32> + Clone) -> Vec<[f32; 4]> {
+        let (min, max) = values.clone().fold((f32::MAX, f32::MIN), |(min, max), v| {
+            (min.min(v), max.max(v))
+        });
+        let range = max - min;
+        values
+            .map(|v| {
+                if range > 0.0 {
+                    self.color((v - min) / range)
+                } else {
+                    self.color(0.0)
+                }
+            })
+            .collect()
+    }
+}
@@ -0,0 +1,139 @@
+use std::{
+    error::Error,
+    io::{Cursor, Read},
+};
+
+use byteorder::{LittleEndian, ReadBytesExt};
+
+use crate::{lod_data::LodData, utils::try_read_name, LodManager};
+
+/// Monsters come in families of three, one for every difficulty level (A, B and C).
+pub const MONSTER_GROUP_SIZE: usize = 3;
+
+pub struct DMonList {
+    pub items: Vec<DMonListItem>,
+}
+
+#[allow(dead_code)]
+#[repr(C)]
+#[derive(Default, Clone)]
+pub struct DMonListItem {
+    pub height: u16,
+    pub radius: u16,
+    pub movement_speed: u16,
+    pub to_hit_radius: i16,
+    pub sound_ids: [u16; 4],
+    name: [u8; 32],
+    sprite_names: [[u8; 10]; 10],
+}
+
+impl DMonListItem {
+    pub fn name(&self) -> Option<String> {
+        try_read_name(&self.name)
+    }
+
+    pub fn sprite_names(&self) -> Vec<String> {
+        self.sprite_names
+            .iter()
+            .filter_map(|n| try_read_name(n))
+            .filter(|n| !n.is_empty())
+            .collect()
+    }
+}
+
+/// A monster of a group, `index` is its position in the monster list.
+#[derive(Debug, Clone, PartialEq)]
+pub struct MonsterRef {
+    pub index: usize,
+    pub name: String,
+}
+
+impl DMonList {
+    pub fn new(lod_manager: &LodManager) -> Result<Self, Box<dyn Error>> {
+        let data = LodData::try_from(lod_manager.try_get_bytes("icons/dmonlist.bin")?)?;
+        let data = data.data.as_slice();
+
+        let mut cursor = Cursor::new(data);
+        let mut items: Vec<DMonListItem> = Vec::new();
+        let item_count = cursor.read_u32::<LittleEndian>()?;
+        let item_size = std::mem::size_of::<DMonListItem>();
+
+        for _i in 0..item_count {
+            let mut item = DMonListItem::default();
+            cursor.read_exact(unsafe {
+                std::slice::from_raw_parts_mut(&mut item as *mut _ as *mut u8, item_size)
+            })?;
+            items.push(item);
+        }
+
+        Ok(Self { items })
+    }
+
+    /// The monsters of the group `id`, ordered by difficulty.
+    ///
+    /// Spawn points don't store a group id: their [`SpawnPoint::index`] picks one of the
+    /// monsters listed for the map in `mapstats.txt`. The group of that monster is its
+    /// index in this list divided by [`MONSTER_GROUP_SIZE`].
+    ///
+    /// [`SpawnPoint::index`]: crate::odm::SpawnPoint::index
+    pub fn group(&self, id: usize) -> Vec<MonsterRef> {
+        let start = id * MONSTER_GROUP_SIZE;
+        self.items
+            .iter()
+            .enumerate()
+            .skip(start)
+            .take(MONSTER_GROUP_SIZE)
+            .map(|(index, item)| MonsterRef {
+                index,
+                name: item.name().unwrap_or_default(),
+            })
+            .collect()
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use crate::{get_lod_path, LodManager};
+
+    use super::{DMonList, DMonListItem, MONSTER_GROUP_SIZE};
+
+    fn test_item(name: &str) -> DMonListItem {
+        let mut item = DMonListItem::default();
+        item.name[..name.len()].copy_from_slice(name.as_bytes());
+        item
+    }
+
+    #[test]
+    fn group_works() {
+        let names = [
+            "GoblinA", "GoblinB", "GoblinC", "ArcherA", "ArcherB", "ArcherC", "Ghost",
+        ];
+        let d_monlist = DMonList {
+            items: names.iter().map(|name| test_item(name)).collect(),
+        };
+
+        let group = d_monlist.group(1);
+        let names: Vec<&str> = group.iter().map(|m| m.name.as_str()).collect();
+        assert_eq!(names, ["archera", "archerb", "archerc"]);
+        let indices: Vec<usize> = group.iter().map(|m| m.index).collect();
+        assert_eq!(indices, [3, 4, 5]);
+
+        // the last group of the list can be incomplete
+        assert_eq!(d_monlist.group(2).len(), 1);
+        assert!(d_monlist.group(3).is_empty());
+    }
+
+    #[test]
+    fn read_monlist_data_works() {
+        assert_eq!(std::mem::size_of::<DMonListItem>(), 148);
+
+        let lod_manager = LodManager::new(get_lod_path()).unwrap();
+        let d_monlist = DMonList::new(&lod_manager).unwrap();
+        assert!(!d_monlist.items.is_empty());
+
+        let group = d_monlist.group(1);
+        assert_eq!(group.len(), MONSTER_GROUP_SIZE);
+        assert_eq!(group[0].index, MONSTER_GROUP_SIZE);
+        assert!(group.iter().all(|m| !m.name.is_empty()));
+    }
+}
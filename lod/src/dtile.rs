@@ -1,9 +1,19 @@
-use crate::{image::get_atlas, lod_data::LodData, utils::try_read_name, LodManager};
+use crate::{
+    assets,
+    image::{get_atlas, get_atlas_mips, get_atlas_with_border, tile_image, TileBorder, TILE_SIZE},
+    lod_data::LodData,
+    utils::try_read_name,
+    LodManager,
+};
 use byteorder::{LittleEndian, ReadBytesExt};
-use image::DynamicImage;
+use image::{DynamicImage, RgbaImage};
+use serde::{Deserialize, Serialize};
 use std::{
+    collections::BTreeMap,
     error::Error,
     io::{Cursor, Read},
+    ops::BitOr,
+    path::Path,
 };
 
 #[derive(Debug)]
@@ -83,7 +93,7 @@ impl Tile {
 
 impl Dtile {
     pub fn new(lod_manager: &LodManager) -> Result<Self, Box<dyn Error>> {
-        let data = LodData::try_from(lod_manager.try_get_bytes("icons/dtile.bin")?)?;
+        let data = LodData::try_from(lod_manager.try_get_bytes(assets::TILE_DATA_ENTRY)?)?;
         let data = data.data.as_slice();
 
         let mut cursor = Cursor::new(data);
@@ -103,6 +113,7 @@ impl Dtile {
 
     pub fn table(&self, tile_data: [u16; 8]) -> Option<TileTable> {
         let mut names_table: Vec<String> = Vec::with_capacity(256);
+        let mut attributes_table = [0; 256];
         for i in 0_u16..=255_u16 {
             let index = if (90..125).contains(&i) {
                 i - 90 + tile_data[1] // primary
@@ -119,18 +130,57 @@ impl Dtile {
 
             let tile = self.tiles.get(index as usize)?;
             names_table.push(tile.name().unwrap_or("pending".into()));
+            attributes_table[i as usize] = tile.attributes;
         }
 
-        Some(TileTable::new(names_table.try_into().unwrap()))
+        let mut table = TileTable::new(names_table.try_into().unwrap());
+        table.attributes_table = attributes_table;
+        Some(table)
     }
 }
 
-#[derive(Debug)]
+/// Flags of a tile type, shared by every cell using the tile.
+/// Swamps and roads have no flag, they are told apart by their tile set.
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq, Hash)]
+pub struct TileAttributes(pub u16);
+
+impl TileAttributes {
+    /// Hurts the party walking on it, like lava
+    pub const BURN: Self = Self(0x0001);
+    pub const WATER: Self = Self(0x0002);
+    /// Can't be walked on
+    pub const BLOCK: Self = Self(0x0004);
+    pub const REPULSE: Self = Self(0x0010);
+    pub const FLAT: Self = Self(0x0020);
+    pub const WAVE: Self = Self(0x0040);
+    pub const NO_DRAW: Self = Self(0x0080);
+    pub const WATER_TRANSITION: Self = Self(0x0200);
+    pub const TRANSITION: Self = Self(0x0400);
+    pub const SCROLL_DOWN: Self = Self(0x0800);
+    pub const SCROLL_UP: Self = Self(0x1000);
+    pub const SCROLL_LEFT: Self = Self(0x2000);
+    pub const SCROLL_RIGHT: Self = Self(0x4000);
+
+    pub fn contains(self, other: Self) -> bool {
+        self.0 & other.0 == other.0
+    }
+}
+
+impl BitOr for TileAttributes {
+    type Output = Self;
+
+    fn bitor(self, rhs: Self) -> Self {
+        Self(self.0 | rhs.0)
+    }
+}
+
+#[derive(Debug, Clone)]
 pub struct TileTable {
     size: (u8, u8),
     names_table: [String; 256],
     names_set: Vec<String>,
     coordinates_table: [(u8, u8); 256],
+    attributes_table: [u16; 256],
 }
 
 impl TileTable {
@@ -141,6 +191,7 @@ impl TileTable {
             names_set,
             names_table,
             coordinates_table: [(0, 0); 256],
+            attributes_table: [0; 256],
         };
         t.generate_coordinates_table();
         t
@@ -160,10 +211,29 @@ impl TileTable {
         self.size
     }
 
+    /// Width and height in pixels of the atlas of [`TileTable::atlas_image`], `size` cells of
+    /// 128x128 pixels. A custom atlas loaded with [`load_atlas`] is expected to use the same cells.
+    pub fn atlas_dimensions(&self) -> (u32, u32) {
+        (
+            self.size.0 as u32 * TILE_SIZE,
+            self.size.1 as u32 * TILE_SIZE,
+        )
+    }
+
     fn index_to_coordinate(&self, i: u8) -> (u8, u8) {
         (i.rem_euclid(self.size.0), i.div_euclid(self.size.0))
     }
 
+    /// Tiles that hurt the party walking on them, like lava, as opposed to water
+    /// that only blocks the way.
+    pub fn is_hazard(&self, tile_index: u8) -> bool {
+        self.attributes(tile_index).contains(TileAttributes::BURN)
+    }
+
+    pub fn attributes(&self, tile_index: u8) -> TileAttributes {
+        TileAttributes(self.attributes_table[tile_index as usize])
+    }
+
     pub fn name(&self, tile_index: u8) -> &str {
         self.names_table[tile_index as usize].as_str()
     }
@@ -172,6 +242,19 @@ impl TileTable {
         self.coordinates_table[tile_index as usize]
     }
 
+    /// Cell of a tile in the atlas as normalized `[min_u, min_v, max_u, max_v]`, the same
+    /// layout as the rects of [`crate::image::pack_atlas`].
+    pub fn uv_rect(&self, tile_index: u8) -> [f32; 4] {
+        let (x, y) = self.coordinate(tile_index);
+        let (width, height) = (self.size.0 as f32, self.size.1 as f32);
+        [
+            x as f32 / width,
+            y as f32 / height,
+            (x as f32 + 1.0) / width,
+            (y as f32 + 1.0) / height,
+        ]
+    }
+
     fn generate_coordinates_table(&mut self) {
         let set: Vec<(usize, &String)> = self.names_set.iter().enumerate().collect();
         for i in 0..=255 {
@@ -212,11 +295,174 @@ impl TileTable {
         let ts: Vec<&str> = self.names_set.iter().map(|s| s.as_str()).collect();
         get_atlas(lod_manager, ts.as_slice(), self.size.0 as usize)
     }
+
+    /// Mip levels of the atlas built from the mips stored with the tile bitmaps,
+    /// the first one is [`TileTable::atlas_image`].
+    pub fn atlas_mips(
+        &self,
+        lod_manager: &LodManager,
+    ) -> Result<Vec<DynamicImage>, Box<dyn Error>> {
+        let ts: Vec<&str> = self.names_set.iter().map(|s| s.as_str()).collect();
+        get_atlas_mips(lod_manager, ts.as_slice(), self.size.0 as usize)
+    }
+
+    /// Atlas with a border baked around every tile, showing the tile grid on the terrain.
+    pub fn atlas_image_with_border(
+        &self,
+        lod_manager: &LodManager,
+        border: &TileBorder,
+    ) -> Result<DynamicImage, Box<dyn Error>> {
+        let ts: Vec<&str> = self.names_set.iter().map(|s| s.as_str()).collect();
+        get_atlas_with_border(
+            lod_manager,
+            ts.as_slice(),
+            self.size.0 as usize,
+            Some(border),
+        )
+    }
+
+    /// Image of a single tile as it appears in the atlas, for previews.
+    pub fn tile_preview(
+        &self,
+        tile_index: u8,
+        lod_manager: &LodManager,
+    ) -> Result<RgbaImage, Box<dyn Error>> {
+        let image_water = lod_manager.bitmap("wtrtyl").ok_or("image not found")?;
+        let image = tile_image(lod_manager, self.name(tile_index), &image_water, None)?;
+        Ok(image.into_rgba8())
+    }
+}
+
+/// Placement of the tiles inside a terrain atlas, as a grid of `size` cells.
+/// It can be saved next to the atlas image so both can be edited by hand.
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+pub struct AtlasLayout {
+    pub size: (u8, u8),
+    pub tiles: BTreeMap<String, (u8, u8)>,
+}
+
+impl AtlasLayout {
+    pub fn from_json(json: &str) -> Result<Self, Box<dyn Error>> {
+        Ok(serde_json::from_str(json)?)
+    }
+
+    pub fn to_json(&self) -> Result<String, Box<dyn Error>> {
+        Ok(serde_json::to_string_pretty(self)?)
+    }
 }
 
+impl TileTable {
+    /// Layout of the atlas generated by `atlas_image`.
+    pub fn layout(&self) -> AtlasLayout {
+        let tiles = self
+            .names_set
+            .iter()
+            .enumerate()
+            .map(|(i, name)| (name.clone(), self.index_to_coordinate(i as u8)))
+            .collect();
+        AtlasLayout {
+            size: self.size,
+            tiles,
+        }
+    }
+
+    /// The same table with the tiles placed as in `layout`, so that the uvs
+    /// point into a custom atlas. Every tile of the atlas must be in the layout.
+    pub fn with_layout(&self, layout: &AtlasLayout) -> Result<TileTable, Box<dyn Error>> {
+        let mut table = self.clone();
+        table.size = layout.size;
+        for (i, name) in self.names_table.iter().enumerate() {
+            if !self.names_set.contains(name) {
+                continue;
+            }
+            let coordinate = *layout
+                .tiles
+                .get(name)
+                .ok_or_else(|| format!("tile {} is missing from the atlas layout", name))?;
+            if coordinate.0 >= layout.size.0 || coordinate.1 >= layout.size.1 {
+                return Err(format!("tile {} is outside the atlas layout", name).into());
+            }
+            table.coordinates_table[i] = coordinate;
+        }
+        Ok(table)
+    }
+}
+
+/// Loads a pre-baked atlas image and its json layout.
+pub fn load_atlas<P, Q>(
+    image_path: P,
+    layout_path: Q,
+) -> Result<(DynamicImage, AtlasLayout), Box<dyn Error>>
+where
+    P: AsRef<Path>,
+    Q: AsRef<Path>,
+{
+    let image = image::open(image_path)?;
+    let layout = AtlasLayout::from_json(&std::fs::read_to_string(layout_path)?)?;
+    Ok((image, layout))
+}
+
+#[cfg(test)]
 mod tests {
     use crate::{dtile::Dtile, get_lod_path, odm::Odm, LodManager};
 
+    use super::{AtlasLayout, Tile, TileAttributes, TileTable};
+
+    #[test]
+    fn is_hazard_works() {
+        let mut tiles = vec![Tile::default(); 256];
+        tiles[0].attributes = 0x0001;
+        tiles[126].attributes = 0x0002;
+        let dtile = Dtile { tiles };
+
+        let tile_table = dtile.table([0; 8]).unwrap();
+        assert!(tile_table.is_hazard(0));
+        assert!(!tile_table.is_hazard(126));
+        assert!(!tile_table.is_hazard(1));
+    }
+
+    #[test]
+    fn attributes_works() {
+        let mut tiles = vec![Tile::default(); 256];
+        tiles[126].attributes = 0x0002 | 0x0004;
+        let dtile = Dtile { tiles };
+
+        let tile_table = dtile.table([0; 8]).unwrap();
+        let water = tile_table.attributes(126);
+        assert!(water.contains(TileAttributes::WATER));
+        assert!(water.contains(TileAttributes::WATER | TileAttributes::BLOCK));
+        assert!(!water.contains(TileAttributes::BURN));
+        assert_eq!(tile_table.attributes(0), TileAttributes::default());
+    }
+
+    #[test]
+    fn atlas_layout_works() {
+        let mut names: [String; 256] = std::array::from_fn(|_| "grastyl".to_string());
+        names[1] = "dirttyl".to_string();
+        let tile_table = TileTable::new(names);
+
+        let mut layout = tile_table.layout();
+        assert_eq!(layout.size, (2, 1));
+        assert_eq!(tile_table.atlas_dimensions(), (256, 128));
+        assert_eq!(tile_table.uv_rect(1), [0.0, 0.0, 0.5, 1.0]);
+        assert_eq!(tile_table.uv_rect(0), [0.5, 0.0, 1.0, 1.0]);
+        assert_eq!(
+            AtlasLayout::from_json(&layout.to_json().unwrap()).unwrap(),
+            layout
+        );
+
+        layout.size = (1, 2);
+        layout.tiles.insert("grastyl".into(), (0, 1));
+        layout.tiles.insert("dirttyl".into(), (0, 0));
+        let custom = tile_table.with_layout(&layout).unwrap();
+        assert_eq!(custom.size(), (1, 2));
+        assert_eq!(custom.coordinate(0), (0, 1));
+        assert_eq!(custom.coordinate(1), (0, 0));
+
+        layout.tiles.remove("dirttyl");
+        assert!(tile_table.with_layout(&layout).is_err());
+    }
+
     #[test]
     fn read_dtile_data_works() {
         let lod_manager = LodManager::new(get_lod_path()).unwrap();
@@ -237,4 +483,16 @@ mod tests {
             .save("terrain_atlas.png")
             .unwrap();
     }
+
+    #[test]
+    fn tile_preview_works() {
+        let lod_manager = LodManager::new(get_lod_path()).unwrap();
+        let map = Odm::new(&lod_manager, "oute3.odm").unwrap();
+        let tile_table = map.tile_table(&lod_manager).unwrap();
+
+        let preview = tile_table
+            .tile_preview(map.tile_map[0], &lod_manager)
+            .unwrap();
+        assert_eq!(preview.dimensions(), (128, 128));
+    }
 }
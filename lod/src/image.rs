@@ -1,13 +1,14 @@
 use byteorder::{LittleEndian, ReadBytesExt};
 use image::{imageops, DynamicImage, GenericImageView, ImageBuffer, Rgba};
 use std::{
+    collections::HashMap,
     error::Error,
     io::{Cursor, Seek},
     path::Path,
 };
 
 use super::{palette::Palettes, zlib};
-use crate::LodManager;
+use crate::{lod::Lod, utils::try_read_string_block, LodManager};
 
 #[derive(Debug)]
 pub(super) struct Image {
@@ -21,22 +22,82 @@ pub(super) struct Image {
 const PALETTE_SIZE: usize = 256 * 3;
 const BITMAP_HEADER_SIZE: usize = 48;
 const SPRITE_HEADER_SIZE: usize = 32;
+/// Size of a terrain tile in the atlas
+pub(crate) const TILE_SIZE: u32 = 128;
+/// Levels of the atlas mip chain, down to one pixel per tile
+const TILE_MIP_LEVELS: usize = TILE_SIZE.ilog2() as usize + 1;
+
+/// Header of an image stored in bitmaps.lod and icons.lod.
+#[derive(Debug, Clone, PartialEq)]
+pub struct BitmapHeader {
+    pub name: String,
+    pub width: u16,
+    pub height: u16,
+    /// Size of the pixel data, mipmaps included
+    pub data_size: u32,
+    pub compressed_size: u32,
+    pub uncompressed_size: u32,
+    pub palette_id: u16,
+    pub flags: u32,
+}
+
+pub fn parse_bitmap_header(data: &[u8]) -> Result<BitmapHeader, Box<dyn Error>> {
+    if data.len() < BITMAP_HEADER_SIZE {
+        return Err("Not enough data for a bitmap header".into());
+    }
+    let mut cursor = Cursor::new(data);
+    let name = try_read_string_block(&mut cursor, 16)?;
+    let data_size = cursor.read_u32::<LittleEndian>()?;
+    let compressed_size = cursor.read_u32::<LittleEndian>()?;
+    let width = cursor.read_u16::<LittleEndian>()?;
+    let height = cursor.read_u16::<LittleEndian>()?;
+    cursor.seek(std::io::SeekFrom::Current(8))?;
+    let palette_id = cursor.read_u16::<LittleEndian>()?;
+    cursor.seek(std::io::SeekFrom::Current(2))?;
+    let uncompressed_size = cursor.read_u32::<LittleEndian>()?;
+    let flags = cursor.read_u32::<LittleEndian>()?;
+
+    Ok(BitmapHeader {
+        name,
+        width,
+        height,
+        data_size,
+        compressed_size,
+        uncompressed_size,
+        palette_id,
+        flags,
+    })
+}
+
+/// How many bitmaps of the archive reference each palette id.
+/// The palettes themselves (`palNNN`) and entries without a bitmap header are not counted.
+pub fn palette_usage(lod: &Lod) -> HashMap<u16, usize> {
+    tally_palettes(
+        lod.files()
+            .into_iter()
+            .filter(|name| !(name.starts_with("pal") && name.len() == 6))
+            .filter_map(|name| lod.try_get_bytes(name).ok()),
+    )
+}
+
+fn tally_palettes<'a>(entries: impl Iterator<Item = &'a [u8]>) -> HashMap<u16, usize> {
+    let mut usage = HashMap::new();
+    for header in entries.filter_map(|data| parse_bitmap_header(data).ok()) {
+        if header.width > 0 && header.height > 0 {
+            *usage.entry(header.palette_id).or_insert(0) += 1;
+        }
+    }
+    usage
+}
 
 /// This is for bitmap images
 impl TryFrom<&[u8]> for Image {
     type Error = Box<dyn Error>;
 
     fn try_from(data: &[u8]) -> Result<Self, Self::Error> {
-        let mut cursor = Cursor::new(data);
-        cursor.seek(std::io::SeekFrom::Start(16))?;
-        let pixel_size = cursor.read_u32::<LittleEndian>()? as usize;
-        let compressed_size = cursor.read_u32::<LittleEndian>()? as usize;
-        let width = cursor.read_u16::<LittleEndian>()? as usize;
-        let height = cursor.read_u16::<LittleEndian>()? as usize;
-        cursor.seek(std::io::SeekFrom::Current(12))?;
-        let uncompressed_size = cursor.read_u32::<LittleEndian>()? as usize;
+        let header = parse_bitmap_header(data)?;
 
-        if pixel_size == 0 {
+        if header.data_size == 0 {
             return Err("Pixel size is zero, this is not a valid image".into());
         }
         if data.len() <= BITMAP_HEADER_SIZE + PALETTE_SIZE {
@@ -44,15 +105,18 @@ impl TryFrom<&[u8]> for Image {
         }
 
         let compressed_data = &data[BITMAP_HEADER_SIZE..data.len() - PALETTE_SIZE];
-        let uncompressed_data =
-            zlib::decompress(compressed_data, compressed_size, uncompressed_size)?;
+        let uncompressed_data = zlib::decompress(
+            compressed_data,
+            header.compressed_size as usize,
+            header.uncompressed_size as usize,
+        )?;
 
         let palette_slice = &data[data.len() - PALETTE_SIZE..];
         let palette: [u8; PALETTE_SIZE] = palette_slice.try_into()?;
 
         Ok(Self {
-            height,
-            width,
+            height: header.height as usize,
+            width: header.width as usize,
             data: uncompressed_data,
             palette,
             transparency: false,
@@ -137,10 +201,42 @@ fn process_sprite_data(
     Ok(img)
 }
 
+/// Decodes a bitmap into its 8-bit palette indices without expanding them to RGBA.
+/// Returns the index buffer along with the width and height of the image.
+pub fn decode_indices(bytes: &[u8]) -> Result<(Vec<u8>, u16, u16), Box<dyn Error>> {
+    let image = Image::try_from(bytes)?;
+    let mut indices = image.data;
+    indices.truncate(image.width * image.height);
+    Ok((indices, image.width as u16, image.height as u16))
+}
+
 impl Image {
     pub fn to_image_buffer(&self) -> Result<DynamicImage, Box<dyn Error>> {
+        self.level_image(0, self.width, self.height)
+    }
+
+    /// Decodes the image followed by the mip levels stored after it, each one half the
+    /// size of the previous one. Images stored without mips only give the first level.
+    pub fn mip_levels(&self) -> Result<Vec<DynamicImage>, Box<dyn Error>> {
+        let mut levels = Vec::new();
+        let (mut width, mut height, mut offset) = (self.width, self.height, 0);
+        while width > 0 && height > 0 && offset + width * height <= self.data.len() {
+            levels.push(self.level_image(offset, width, height)?);
+            offset += width * height;
+            width /= 2;
+            height /= 2;
+        }
+        Ok(levels)
+    }
+
+    fn level_image(
+        &self,
+        offset: usize,
+        width: usize,
+        height: usize,
+    ) -> Result<DynamicImage, Box<dyn Error>> {
         let image = raw_to_image_buffer(
-            &self.data,
+            &self.data[offset..],
             &self.palette,
             |index, pixel: &[u8; 3]| {
                 if self.transparency && index == self.data[0] {
@@ -149,8 +245,8 @@ impl Image {
                     Rgba([pixel[0], pixel[1], pixel[2], 255])
                 }
             },
-            self.width as u32,
-            self.height as u32,
+            width as u32,
+            height as u32,
         )?;
         Ok(DynamicImage::ImageRgba8(image))
     }
@@ -168,7 +264,7 @@ impl Image {
 
 /// Converts the image into a versatile generic image buffer.
 /// The image contains more pixels than needed with dimensions (h*w) to account for mipmaps,
-/// but we are currently not utilizing those extra pixels.
+/// only the first `width * height` are read, see [`Image::mip_levels`] for the others.
 /// # Panics
 /// if the input accesses outside the bounds of the palette.
 fn raw_to_image_buffer<P>(
@@ -224,10 +320,87 @@ fn join_images_in_grid(
     DynamicImage::ImageRgba8(combined_image)
 }
 
+/// Packs the images row by row into a single atlas no wider than `max_width`,
+/// returns the atlas and the normalized uv rect `[min_u, min_v, max_u, max_v]` of each image.
+pub fn pack_atlas(images: &[DynamicImage], max_width: u32) -> (DynamicImage, Vec<[f32; 4]>) {
+    let mut order: Vec<usize> = (0..images.len()).collect();
+    order.sort_by_key(|&i| std::cmp::Reverse(images[i].height()));
+
+    let mut offsets = vec![(0, 0); images.len()];
+    let (mut x, mut y, mut row_height, mut atlas_width) = (0, 0, 0, 0);
+    for &i in &order {
+        let (width, height) = images[i].dimensions();
+        if x > 0 && x + width > max_width {
+            x = 0;
+            y += row_height;
+            row_height = 0;
+        }
+        offsets[i] = (x, y);
+        x += width;
+        row_height = row_height.max(height);
+        atlas_width = atlas_width.max(x);
+    }
+    let atlas_height = y + row_height;
+
+    let mut atlas = ImageBuffer::new(atlas_width.max(1), atlas_height.max(1));
+    let mut uv_rects = Vec::with_capacity(images.len());
+    for (image, (x, y)) in images.iter().zip(offsets) {
+        imageops::replace(&mut atlas, &image.to_rgba8(), x as i64, y as i64);
+        let (width, height) = image.dimensions();
+        uv_rects.push([
+            x as f32 / atlas.width() as f32,
+            y as f32 / atlas.height() as f32,
+            (x + width) as f32 / atlas.width() as f32,
+            (y + height) as f32 / atlas.height() as f32,
+        ]);
+    }
+    (DynamicImage::ImageRgba8(atlas), uv_rects)
+}
+
+/// Border drawn inside every tile of an atlas to show the tile grid.
+#[derive(Debug, Clone, Copy)]
+pub struct TileBorder {
+    pub color: [u8; 4],
+    pub width: u32,
+}
+
+impl Default for TileBorder {
+    fn default() -> Self {
+        Self {
+            color: [16, 16, 16, 255],
+            width: 1,
+        }
+    }
+}
+
+impl TileBorder {
+    fn draw(&self, image: &mut ImageBuffer<Rgba<u8>, Vec<u8>>) {
+        let (width, height) = image.dimensions();
+        let border = self.width.min(width / 2).min(height / 2);
+        for y in 0..height {
+            for x in 0..width {
+                if x < border || y < border || x >= width - border || y >= height - border {
+                    image.put_pixel(x, y, Rgba(self.color));
+                }
+            }
+        }
+    }
+}
+
 pub fn get_atlas(
     lod_manager: &LodManager,
     names: &[&str],
     row_size: usize,
+) -> Result<DynamicImage, Box<dyn Error>> {
+    get_atlas_with_border(lod_manager, names, row_size, None)
+}
+
+/// Same as [`get_atlas`] but optionally bakes a border around every tile.
+pub fn get_atlas_with_border(
+    lod_manager: &LodManager,
+    names: &[&str],
+    row_size: usize,
+    border: Option<&TileBorder>,
 ) -> Result<DynamicImage, Box<dyn Error>> {
     let mut images: Vec<DynamicImage> = Vec::with_capacity(names.len());
 
@@ -235,36 +408,307 @@ pub fn get_atlas(
     let image_water = lod_manager.bitmap("wtrtyl").ok_or("image not found")?;
 
     for name in names {
-        let mut image = lod_manager.bitmap(name).ok_or("image not found")?;
-        if image.dimensions() != (128, 128) {
-            image = DynamicImage::ImageRgba8(imageops::resize(
-                &image,
-                128,
-                128,
-                imageops::FilterType::Triangle,
-            ));
+        images.push(tile_image(lod_manager, name, &image_water, border)?);
+    }
+    Ok(join_images_in_grid(&images, row_size, TILE_SIZE, TILE_SIZE))
+}
+
+/// Mip chain of the atlas built by [`get_atlas`], down to one pixel per tile.
+/// Every level uses the mips stored with the tile bitmaps, the levels they don't provide
+/// are downsampled from the previous one.
+pub fn get_atlas_mips(
+    lod_manager: &LodManager,
+    names: &[&str],
+    row_size: usize,
+) -> Result<Vec<DynamicImage>, Box<dyn Error>> {
+    let water = tile_mip_chain(&lod_manager.bitmap_mips("wtrtyl").ok_or("image not found")?)?;
+    let mut tiles = Vec::with_capacity(names.len());
+    for name in names {
+        let mut chain = tile_mip_chain(&lod_manager.bitmap_mips(name).ok_or("image not found")?)?;
+        for (level, water) in chain.iter_mut().zip(&water) {
+            compose_water(level, water)?;
         }
+        tiles.push(chain);
+    }
 
-        let image_buffer = image.as_mut_rgba8().ok_or("wrong image format")?;
-        for y in 0..128 {
-            for x in 0..128 {
-                let rgb: [u8; 4] = image_buffer.get_pixel(x, y).0;
-                if rgb[0] == 0 && rgb[1] >= 252 && rgb[2] >= 252 {
-                    image_buffer.put_pixel(x, y, image_water.get_pixel(x, y));
-                }
+    Ok((0..TILE_MIP_LEVELS)
+        .map(|i| {
+            let size = TILE_SIZE >> i;
+            let level: Vec<DynamicImage> = tiles.iter().map(|chain| chain[i].clone()).collect();
+            join_images_in_grid(&level, row_size, size, size)
+        })
+        .collect())
+}
+
+/// The `TILE_MIP_LEVELS` levels of a tile from its stored ones, a stored level is kept
+/// when it has the expected size, otherwise it is downsampled from the level above.
+fn tile_mip_chain(stored: &[DynamicImage]) -> Result<Vec<DynamicImage>, Box<dyn Error>> {
+    let mut chain: Vec<DynamicImage> = Vec::with_capacity(TILE_MIP_LEVELS);
+    for i in 0..TILE_MIP_LEVELS {
+        let size = TILE_SIZE >> i;
+        let level = match stored.get(i) {
+            Some(image) if image.dimensions() == (size, size) => image.clone(),
+            _ => {
+                let source = chain.last().or(stored.first()).ok_or("image has no data")?;
+                DynamicImage::ImageRgba8(imageops::resize(
+                    source,
+                    size,
+                    size,
+                    imageops::FilterType::Triangle,
+                ))
             }
-        }
+        };
+        chain.push(level);
+    }
+    Ok(chain)
+}
 
-        images.push(image);
+/// Decodes a single terrain tile at the 128x128 size used by the atlas,
+/// filling its water pixels from `image_water`.
+pub(crate) fn tile_image(
+    lod_manager: &LodManager,
+    name: &str,
+    image_water: &DynamicImage,
+    border: Option<&TileBorder>,
+) -> Result<DynamicImage, Box<dyn Error>> {
+    let mut image = lod_manager.bitmap(name).ok_or("image not found")?;
+    if image.dimensions() != (128, 128) {
+        image = DynamicImage::ImageRgba8(imageops::resize(
+            &image,
+            128,
+            128,
+            imageops::FilterType::Triangle,
+        ));
+    }
+
+    compose_water(&mut image, image_water)?;
+    if let Some(border) = border {
+        border.draw(image.as_mut_rgba8().ok_or("wrong image format")?);
     }
-    Ok(join_images_in_grid(&images, row_size, 128, 128))
+    Ok(image)
+}
+
+/// Replaces the cyan pixels of a tile with the ones of the water tile of the same size.
+fn compose_water(
+    image: &mut DynamicImage,
+    image_water: &DynamicImage,
+) -> Result<(), Box<dyn Error>> {
+    let image_buffer = image.as_mut_rgba8().ok_or("wrong image format")?;
+    let (width, height) = image_buffer.dimensions();
+    for y in 0..height {
+        for x in 0..width {
+            let rgb: [u8; 4] = image_buffer.get_pixel(x, y).0;
+            if rgb[0] == 0 && rgb[1] >= 252 && rgb[2] >= 252 {
+                image_buffer.put_pixel(x, y, image_water.get_pixel(x, y));
+            }
+        }
+    }
+    Ok(())
 }
 
 #[cfg(test)]
 mod test {
-    use super::get_atlas;
+    use super::{
+        decode_indices, get_atlas, get_atlas_mips, get_atlas_with_border, pack_atlas,
+        palette_usage, parse_bitmap_header, tally_palettes, tile_mip_chain, BitmapHeader, Image,
+        TileBorder, TILE_MIP_LEVELS, TILE_SIZE,
+    };
+    use crate::lod::Lod;
     use crate::{get_lod_path, LodManager};
-    use image::GenericImageView;
+    use image::{DynamicImage, GenericImageView, ImageBuffer, Rgba};
+
+    #[test]
+    fn tile_border_works() {
+        let mut image = ImageBuffer::from_pixel(8, 8, Rgba([200, 200, 200, 255]));
+        let border = TileBorder {
+            color: [1, 2, 3, 255],
+            width: 2,
+        };
+        border.draw(&mut image);
+        assert_eq!(image.get_pixel(0, 0).0, border.color);
+        assert_eq!(image.get_pixel(1, 4).0, border.color);
+        assert_eq!(image.get_pixel(7, 3).0, border.color);
+        assert_eq!(image.get_pixel(4, 6).0, border.color);
+        assert_eq!(image.get_pixel(2, 2).0, [200, 200, 200, 255]);
+        assert_eq!(image.get_pixel(5, 5).0, [200, 200, 200, 255]);
+    }
+
+    #[test]
+    fn atlas_with_border_works() {
+        let lod_manager = LodManager::new(get_lod_path()).unwrap();
+        let border = TileBorder::default();
+        let atlas_image =
+            get_atlas_with_border(&lod_manager, &["grastyl", "dirttyl"], 2, Some(&border)).unwrap();
+        for (x, y) in [(0, 0), (127, 64), (128, 0), (200, 127)] {
+            assert_eq!(atlas_image.get_pixel(x, y).0, border.color);
+        }
+    }
+
+    #[test]
+    fn parse_bitmap_header_works() {
+        let mut data = b"grastyl\0\0\0\0\0\0\0\0\0".to_vec();
+        for v in [21840u32, 9000] {
+            data.extend(v.to_le_bytes());
+        }
+        for v in [128u16, 128, 7, 7, 127, 127, 3, 0] {
+            data.extend(v.to_le_bytes());
+        }
+        for v in [21840u32, 0x10] {
+            data.extend(v.to_le_bytes());
+        }
+
+        let header = parse_bitmap_header(&data).unwrap();
+        assert_eq!(
+            header,
+            BitmapHeader {
+                name: "grastyl".into(),
+                width: 128,
+                height: 128,
+                data_size: 21840,
+                compressed_size: 9000,
+                uncompressed_size: 21840,
+                palette_id: 3,
+                flags: 0x10,
+            }
+        );
+        assert!(parse_bitmap_header(&data[..40]).is_err());
+    }
+
+    #[test]
+    fn tally_palettes_works() {
+        let header = |width: u16, palette_id: u16| {
+            let mut data = vec![0u8; 48];
+            data[24..26].copy_from_slice(&width.to_le_bytes());
+            data[26..28].copy_from_slice(&16u16.to_le_bytes());
+            data[36..38].copy_from_slice(&palette_id.to_le_bytes());
+            data
+        };
+        let entries = [
+            header(16, 3),
+            header(16, 3),
+            header(16, 7),
+            header(0, 7),
+            vec![0; 8],
+        ];
+        let usage = tally_palettes(entries.iter().map(|e| e.as_slice()));
+        assert_eq!(usage.len(), 2);
+        assert_eq!(usage[&3], 2);
+        assert_eq!(usage[&7], 1);
+    }
+
+    #[test]
+    fn palette_usage_works() {
+        let lod = Lod::open(std::path::Path::new(&get_lod_path()).join("bitmaps.lod")).unwrap();
+        let usage = palette_usage(&lod);
+        let bitmaps = lod
+            .files()
+            .into_iter()
+            .filter(|name| !(name.starts_with("pal") && name.len() == 6))
+            .filter_map(|name| parse_bitmap_header(lod.try_get_bytes(name).ok()?).ok())
+            .filter(|header| header.width > 0 && header.height > 0)
+            .count();
+        assert!(bitmaps > 0);
+        assert_eq!(usage.values().sum::<usize>(), bitmaps);
+    }
+
+    #[test]
+    fn decode_indices_works() {
+        let lod_manager = LodManager::new(get_lod_path()).unwrap();
+        let bytes = lod_manager.try_get_bytes("bitmaps/grastyl").unwrap();
+        let (indices, width, height) = decode_indices(bytes).unwrap();
+        assert_eq!(indices.len(), width as usize * height as usize);
+
+        let palette = Image::try_from(bytes).unwrap().palette;
+        let bitmap = lod_manager.bitmap("grastyl").unwrap();
+        for (i, index) in indices.iter().enumerate() {
+            let (x, y) = (i as u32 % width as u32, i as u32 / width as u32);
+            let color = &palette[*index as usize * 3..*index as usize * 3 + 3];
+            assert_eq!(&bitmap.get_pixel(x, y).0[..3], color);
+        }
+    }
+
+    #[test]
+    fn pack_atlas_works() {
+        let images = [
+            DynamicImage::new_rgba8(64, 32),
+            DynamicImage::new_rgba8(100, 80),
+            DynamicImage::new_rgba8(50, 50),
+        ];
+        let (atlas, uv_rects) = pack_atlas(&images, 128);
+        assert_eq!(atlas.dimensions(), (114, 130));
+        assert_eq!(uv_rects.len(), 3);
+        for (image, rect) in images.iter().zip(&uv_rects) {
+            let width = (rect[2] - rect[0]) * atlas.width() as f32;
+            let height = (rect[3] - rect[1]) * atlas.height() as f32;
+            assert_eq!(
+                (width.round() as u32, height.round() as u32),
+                image.dimensions()
+            );
+        }
+    }
+
+    #[test]
+    fn mip_levels_works() {
+        let mut data = vec![1u8; 4 * 4];
+        data.extend([2u8; 2 * 2]);
+        data.extend([3u8; 1]);
+        let mut palette = [0u8; 768];
+        palette[3..12].copy_from_slice(&[10, 10, 10, 20, 20, 20, 30, 30, 30]);
+        let image = Image {
+            height: 4,
+            width: 4,
+            data,
+            palette,
+            transparency: false,
+        };
+
+        let levels = image.mip_levels().unwrap();
+        let sizes: Vec<(u32, u32)> = levels.iter().map(|l| l.dimensions()).collect();
+        assert_eq!(sizes, [(4, 4), (2, 2), (1, 1)]);
+        assert_eq!(levels[1].get_pixel(1, 1).0, [20, 20, 20, 255]);
+        assert_eq!(levels[2].get_pixel(0, 0).0, [30, 30, 30, 255]);
+        assert_eq!(
+            levels[0].to_rgba8(),
+            image.to_image_buffer().unwrap().to_rgba8()
+        );
+    }
+
+    #[test]
+    fn tile_mip_chain_works() {
+        let stored = [
+            DynamicImage::ImageRgba8(ImageBuffer::from_pixel(128, 128, Rgba([1, 1, 1, 255]))),
+            DynamicImage::ImageRgba8(ImageBuffer::from_pixel(64, 64, Rgba([2, 2, 2, 255]))),
+            DynamicImage::ImageRgba8(ImageBuffer::from_pixel(32, 32, Rgba([3, 3, 3, 255]))),
+        ];
+        let chain = tile_mip_chain(&stored).unwrap();
+        assert_eq!(chain.len(), TILE_MIP_LEVELS);
+        for (i, level) in chain.iter().enumerate() {
+            assert_eq!(level.dimensions(), (TILE_SIZE >> i, TILE_SIZE >> i));
+        }
+        // stored levels are kept as they are, the missing ones come from the last stored
+        assert_eq!(chain[1].get_pixel(5, 5).0, [2, 2, 2, 255]);
+        assert_eq!(chain[2].get_pixel(5, 5).0, [3, 3, 3, 255]);
+        assert_eq!(chain[7].get_pixel(0, 0).0, [3, 3, 3, 255]);
+
+        let oversized = [DynamicImage::ImageRgba8(ImageBuffer::from_pixel(
+            256,
+            256,
+            Rgba([4, 4, 4, 255]),
+        ))];
+        let chain = tile_mip_chain(&oversized).unwrap();
+        assert_eq!(chain[0].dimensions(), (128, 128));
+        assert!(tile_mip_chain(&[]).is_err());
+    }
+
+    #[test]
+    fn atlas_mips_works() {
+        let lod_manager = LodManager::new(get_lod_path()).unwrap();
+        let mips = get_atlas_mips(&lod_manager, &["grastyl", "dirttyl"], 2).unwrap();
+        assert_eq!(mips.len(), TILE_MIP_LEVELS);
+        assert_eq!(mips[1].dimensions(), (128, 64));
+        let stored = lod_manager.bitmap_mips("dirttyl").unwrap();
+        assert_eq!(mips[1].get_pixel(64 + 10, 10), stored[1].get_pixel(10, 10));
+    }
 
     #[test]
     fn join_images() {
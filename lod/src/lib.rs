@@ -5,19 +5,22 @@ use std::fs::{self};
 use std::path::{Path, PathBuf};
 
 use ::image::DynamicImage;
-use lod::Lod;
+use lod::{Lod, LodError, LodVersion};
 use palette::Palettes;
 
+pub mod assets;
 pub mod bsp_model;
 pub mod dtile;
 pub mod odm;
 
 pub mod billboard;
+pub mod colormap;
 pub mod ddeclist;
+pub mod dmonlist;
 pub mod dsft;
 pub mod image;
 
-mod lod;
+pub mod lod;
 pub mod lod_data;
 pub mod palette;
 mod utils;
@@ -65,6 +68,9 @@ impl LodManager {
         let mut lod_file_map: HashMap<String, Lod> = HashMap::new();
 
         for path in lod_files.iter() {
+            #[cfg(feature = "mmap")]
+            let lod = Lod::open_mmap(path)?;
+            #[cfg(not(feature = "mmap"))]
             let lod = Lod::open(path)?;
             let key = path
                 .file_stem()
@@ -77,52 +83,86 @@ impl LodManager {
         Ok(lod_file_map)
     }
 
-    pub fn try_get_bytes<P: AsRef<Path>>(&self, path: P) -> Result<&[u8], Box<dyn Error>> {
+    /// Game of the loaded archives, the first one with a known version wins,
+    /// the archives are looked at in alphabetical order.
+    pub fn version(&self) -> LodVersion {
+        let mut archives: Vec<(&String, &Lod)> = self.lods.iter().collect();
+        archives.sort_unstable_by_key(|(name, _)| name.as_str());
+        archives
+            .into_iter()
+            .map(|(_, lod)| lod.version())
+            .find(|v| *v != LodVersion::Unknown)
+            .unwrap_or(LodVersion::Unknown)
+    }
+
+    /// Name of the archive holding `entry`, `preferred` is searched first and the
+    /// others in alphabetical order, so entries shipped by mods in their own archives are found too.
+    pub fn find_archive(&self, entry: &str, preferred: &str) -> Option<&str> {
+        let mut archives: Vec<&String> = self.lods.keys().collect();
+        archives.sort_unstable_by_key(|name| (name.as_str() != preferred, name.as_str()));
+        archives
+            .into_iter()
+            .find(|name| self.lods[*name].try_get_bytes(entry).is_ok())
+            .map(|name| name.as_str())
+    }
+
+    /// Bytes of `archive/entry` matched ignoring case, `LodError::EntryNotFound` when the
+    /// archive or the entry is missing.
+    pub fn try_get_bytes<P: AsRef<Path>>(&self, path: P) -> Result<&[u8], LodError> {
+        let not_found = || LodError::EntryNotFound(path.as_ref().to_string_lossy().to_string());
         let lod_archive: String = path
             .as_ref()
             .parent()
-            .ok_or("invalid path")?
+            .ok_or_else(not_found)?
             .to_string_lossy()
-            .to_string();
-        let lod = self
-            .lods
-            .get(&lod_archive)
-            .ok_or(format!("lod file not found in {lod_archive} "))?;
+            .to_lowercase();
+        let lod = self.lods.get(&lod_archive).ok_or_else(not_found)?;
         let lod_entry: String = path
             .as_ref()
             .file_name()
-            .ok_or("invalid lod entry")?
+            .ok_or_else(not_found)?
             .to_string_lossy()
             .to_string();
-        let lod_data = lod.try_get_bytes(&lod_entry).ok_or(format!(
-            "unable to open lod entry {:?}",
-            path.as_ref().to_str()
-        ))?;
-        Ok(lod_data)
+        lod.try_get_bytes(&lod_entry).map_err(|_| not_found())
     }
 
     fn palettes(&self) -> Result<Palettes, Box<dyn Error>> {
         // TODO cache palettes
         let bitmaps_lod = self
             .lods
-            .get("bitmaps")
+            .get(assets::BITMAPS_ARCHIVE)
             .ok_or("expected to have bitmaps.lod")?;
         let palettes = palette::Palettes::try_from(bitmaps_lod)?;
         Ok(palettes)
     }
 
     pub fn sprite(&self, name: &str) -> Option<DynamicImage> {
-        let sprite = self.try_get_bytes(format!("sprites/{}", name)).ok()?;
+        let sprite = self
+            .try_get_bytes(format!("{}/{}", assets::SPRITES_ARCHIVE, name))
+            .ok()?;
         let palettes = self.palettes().ok()?;
         let sprite = crate::image::Image::try_from((sprite, &palettes)).ok()?;
         sprite.to_image_buffer().ok()
     }
 
     pub fn bitmap(&self, name: &str) -> Option<DynamicImage> {
-        let bitmap = self.try_get_bytes(format!("bitmaps/{}", name)).ok()?;
+        let bitmap = self
+            .try_get_bytes(format!("{}/{}", assets::BITMAPS_ARCHIVE, name))
+            .ok()?;
         let bitmap = crate::image::Image::try_from(bitmap).ok()?;
         bitmap.to_image_buffer().ok()
     }
+
+    /// The bitmap followed by the mip levels stored with it.
+    pub fn bitmap_mips(&self, name: &str) -> Option<Vec<DynamicImage>> {
+        let bitmap = self
+            .try_get_bytes(format!("{}/{}", assets::BITMAPS_ARCHIVE, name))
+            .ok()?;
+        crate::image::Image::try_from(bitmap)
+            .ok()?
+            .mip_levels()
+            .ok()
+    }
 }
 
 pub fn get_data_path() -> String {
@@ -145,6 +185,51 @@ mod tests {
         assert_eq!(17676, grastyl.unwrap().len());
     }
 
+    #[test]
+    fn find_archive_works() {
+        let lod_manager = LodManager::new(get_lod_path()).unwrap();
+        assert_eq!(
+            lod_manager.find_archive("oute3.odm", "games"),
+            Some("games")
+        );
+        assert_eq!(
+            lod_manager.find_archive("oute3.odm", "bitmaps"),
+            Some("games")
+        );
+        assert_eq!(lod_manager.find_archive("missing.odm", "games"), None);
+    }
+
+    fn test_lod(signature: &str) -> Lod {
+        let mut data = format!("LOD\0{}\0", signature).into_bytes();
+        data.resize(256, 0);
+        let mut folder = [0u8; 32];
+        folder[..5].copy_from_slice(b"icons");
+        folder[16..20].copy_from_slice(&256i32.to_le_bytes());
+        data.extend(folder);
+        Lod::from_reader_lenient(std::io::Cursor::new(data)).unwrap()
+    }
+
+    #[test]
+    fn version_works() {
+        let lods = [
+            ("icons", test_lod("Unknown")),
+            ("games", test_lod("MMVII")),
+            ("bitmaps", test_lod("GameMMVI")),
+        ];
+        let lod_manager = LodManager {
+            lods: lods
+                .into_iter()
+                .map(|(name, lod)| (name.to_string(), lod))
+                .collect(),
+        };
+        assert_eq!(lod_manager.version(), LodVersion::MM6);
+
+        let lod_manager = LodManager {
+            lods: HashMap::from([("icons".to_string(), test_lod("Unknown"))]),
+        };
+        assert_eq!(lod_manager.version(), LodVersion::Unknown);
+    }
+
     #[test]
     fn sprite_works() {
         let lod_path = get_lod_path();
@@ -2,51 +2,463 @@ use std::{
     collections::HashMap,
     error::Error,
     fs::{self, File},
-    io::{BufReader, Cursor, Read, Seek, SeekFrom},
+    io::{self, BufReader, Cursor, Read, Seek, SeekFrom},
+    ops::Range,
     path::Path,
 };
 
 use byteorder::{LittleEndian, ReadBytesExt};
+use thiserror::Error;
 
-use crate::{lod_data::LodData, palette, utils::try_read_string};
+use crate::{
+    lod_data::{LodData, LodDataError, DEFAULT_LEVEL},
+    palette,
+    utils::try_read_string,
+};
+
+/// Errors of the lod archives, callers can match on `EntryNotFound` to look elsewhere.
+#[derive(Debug, Error)]
+pub enum LodError {
+    #[error("i/o error: {0}")]
+    Io(#[from] std::io::Error),
+    #[error("not a lod archive")]
+    BadMagic,
+    #[error("unknown lod version {0:?}")]
+    UnknownVersion(String),
+    #[error("lod entry not found: {0}")]
+    EntryNotFound(String),
+    #[error("lod directory is truncated")]
+    TruncatedDirectory,
+    #[error("invalid lod entry name")]
+    InvalidEntryName,
+    #[error("failed to decompress lod entry {0}")]
+    Decompress(String),
+    #[error("invalid lod entry data: {0}")]
+    Data(#[from] LodDataError),
+}
 
 #[allow(dead_code)]
-pub(super) struct Lod {
-    version: Version,
-    files: HashMap<String, Vec<u8>>,
+pub struct Lod {
+    version: LodVersion,
+    header: Vec<u8>,
+    directory: Vec<FileHeader>,
+    files: HashMap<String, EntryData>,
+    /// Whole archive the `EntryData::Mapped` entries are sliced from
+    backing: Option<Box<dyn AsRef<[u8]> + Send + Sync>>,
+    entries: Vec<LodEntry>,
+    skipped: usize,
+    /// Granularity of the entry offsets, see [`Lod::entry_alignment`]
+    alignment: usize,
+}
+
+/// Bytes of an entry, sliced from the backing buffer of the archive or owned once inserted.
+enum EntryData {
+    Owned(Vec<u8>),
+    Mapped(Range<usize>),
+}
+
+/// A record of the archive directory.
+#[derive(Debug, Clone, PartialEq)]
+pub struct LodEntry {
+    pub name: String,
+    pub offset: u64,
+    /// Size of the entry in the archive, see [`LodEntry::stored_size`].
+    pub size: usize,
+    uncompressed_size: usize,
+}
+
+impl LodEntry {
+    /// The uncompressed size is read from the header of compressed entries, the data is not inflated.
+    fn new(fh: &FileHeader, data: Option<&[u8]>) -> Self {
+        let uncompressed_size = data
+            .and_then(LodData::header)
+            .map(|header| header.uncompressed_size)
+            .unwrap_or(fh.size);
+        Self {
+            name: fh.name.to_lowercase(),
+            offset: fh.offset as u64,
+            size: fh.size,
+            uncompressed_size,
+        }
+    }
+
+    /// Size of the entry once decompressed, the stored size for entries stored raw.
+    pub fn uncompressed_size(&self) -> usize {
+        self.uncompressed_size
+    }
+
+    /// Size of the entry in the archive, compression header included.
+    pub fn stored_size(&self) -> usize {
+        self.size
+    }
 }
 
 impl Lod {
-    pub(super) fn open<P: AsRef<Path>>(path: P) -> Result<Lod, Box<dyn std::error::Error>> {
-        let file: File = File::open(path)?;
-        let mut buf_reader = BufReader::new(file);
+    pub fn open<P: AsRef<Path>>(path: P) -> Result<Lod, LodError> {
+        Self::from_reader(File::open(path)?)
+    }
+
+    /// Parses an archive already in memory, e.g. embedded with `include_bytes!`.
+    /// The entries are slices of `data`, nothing is copied.
+    pub fn from_bytes(data: Vec<u8>) -> Result<Lod, LodError> {
+        Self::from_backing(Box::new(data), false)
+    }
+
+    /// Maps the archive in memory instead of reading it, the entries are sliced from the
+    /// mapping so only the pages of the entries that are used get read from disk.
+    /// The `Lod` is used the same way as one from [`Lod::open`].
+    ///
+    /// The file must not be modified or truncated while the `Lod` is alive: the entries would
+    /// change underneath us, or reading them would crash the process on a truncated file.
+    /// The archives of an installed game are not written while they are being read.
+    #[cfg(feature = "mmap")]
+    pub fn open_mmap<P: AsRef<Path>>(path: P) -> Result<Lod, LodError> {
+        let file = File::open(path)?;
+        // SAFETY: the file is not mutated while mapped, see above.
+        let map = unsafe { memmap2::Mmap::map(&file)? };
+        Self::from_backing(Box::new(map), false)
+    }
+
+    fn from_backing(
+        backing: Box<dyn AsRef<[u8]> + Send + Sync>,
+        lenient: bool,
+    ) -> Result<Lod, LodError> {
+        let data = (*backing).as_ref();
+        let Directory {
+            version,
+            header,
+            file_headers,
+            skipped,
+        } = parse_directory(data, lenient)?;
+        let alignment = entry_alignment(&file_headers);
+        let mut files = HashMap::new();
+        let mut entries = Vec::with_capacity(file_headers.len());
+        for fh in &file_headers {
+            let out_of_bounds = || io::Error::from(io::ErrorKind::UnexpectedEof);
+            let start = fh.offset as usize;
+            let range = start..start.checked_add(fh.size).ok_or_else(out_of_bounds)?;
+            let entry = data.get(range.clone()).ok_or_else(out_of_bounds)?;
+            entries.push(LodEntry::new(fh, Some(entry)));
+            files.insert(fh.name.to_lowercase(), EntryData::Mapped(range));
+        }
+
+        Ok(Lod {
+            version,
+            header,
+            directory: file_headers,
+            files,
+            backing: Some(backing),
+            entries,
+            skipped,
+            alignment,
+        })
+    }
+
+    pub fn from_reader<R: Read + Seek>(reader: R) -> Result<Lod, LodError> {
+        Self::from_backing(Box::new(read_all(reader)?), false)
+    }
+
+    /// Opens archives of unknown versions too, keeping the directory records that
+    /// can be parsed and skipping the malformed ones.
+    pub fn open_lenient<P: AsRef<Path>>(path: P) -> Result<Lod, LodError> {
+        Self::from_reader_lenient(File::open(path)?)
+    }
+
+    /// Same as [`Lod::open_lenient`] for archives that are not files.
+    pub fn from_reader_lenient<R: Read + Seek>(reader: R) -> Result<Lod, LodError> {
+        Self::from_backing(Box::new(read_all(reader)?), true)
+    }
+
+    pub fn version(&self) -> LodVersion {
+        self.version
+    }
+
+    /// The directory records that were parsed and how many malformed ones were skipped.
+    pub fn files_lenient(&self) -> (Vec<LodEntry>, usize) {
+        (self.entries.clone(), self.skipped)
+    }
+
+    /// Directory records in the order they are stored in the archive,
+    /// starting with the record of the archive folder itself.
+    pub fn entries(&self) -> impl Iterator<Item = &LodEntry> {
+        self.entries.iter()
+    }
+
+    /// Size of an entry once decompressed, the stored size for uncompressed entries.
+    /// Read from the header of compressed entries like [`LodEntry::uncompressed_size`].
+    pub fn uncompressed_size(&self, name: &str) -> Option<usize> {
+        let data = self.get(name)?;
+        Some(
+            LodData::header(data)
+                .map(|header| header.uncompressed_size)
+                .unwrap_or(data.len()),
+        )
+    }
+
+    /// Names of the entries, sorted alphabetically.
+    pub fn files(&self) -> Vec<&str> {
+        let mut files: Vec<&str> = self.files.keys().map(|f| f.as_str()).collect();
+        files.sort_unstable();
+        files
+    }
+
+    /// The `n` biggest entries by uncompressed size, in descending order.
+    /// The sizes come from the directory and the compression headers, nothing is inflated.
+    /// The record of the archive folder is not an entry and is left out.
+    pub fn largest_entries(&self, n: usize) -> Vec<(&str, usize)> {
+        let mut entries: Vec<(&str, usize)> = self
+            .entries
+            .iter()
+            .skip(1)
+            .map(|entry| (entry.name.as_str(), entry.uncompressed_size()))
+            .collect();
+        entries.sort_unstable_by(|a, b| b.1.cmp(&a.1).then_with(|| a.0.cmp(b.0)));
+        entries.truncate(n);
+        entries
+    }
+
+    /// Finds the stored name of an entry the way the game looks it up:
+    /// the exact name first, then the other names of the same kind in
+    /// `FALLBACK_EXTENSIONS`, so `d01.dlv` can resolve to `d01.blv` but never to
+    /// an outdoor `d01.odm`. A name without extension tries every kind.
+    pub fn resolve_name(&self, base: &str) -> Option<&str> {
+        let base = base.to_lowercase();
+        let (stem, ext) = match base.rsplit_once('.') {
+            Some((stem, ext)) => (stem, Some(ext)),
+            None => (base.as_str(), None),
+        };
+        let fallbacks = FALLBACK_EXTENSIONS
+            .iter()
+            .filter(|kind| ext.is_none_or(|ext| kind.contains(&ext)))
+            .flat_map(|kind| kind.iter())
+            .map(|ext| match ext {
+                &"" => stem.to_string(),
+                ext => format!("{}.{}", stem, ext),
+            });
+        std::iter::once(base.clone())
+            .chain(fallbacks)
+            .find_map(|name| self.files.get_key_value(&name).map(|(k, _)| k.as_str()))
+    }
+
+    /// Hash of the decompressed content of an entry, to find which files differ between
+    /// two installs. It is a 64 bit FNV-1a so it stays the same across builds and platforms.
+    pub fn entry_hash(&self, name: &str) -> Option<u64> {
+        let data = self.get(name)?;
+        let hash = match LodData::try_from(data) {
+            Ok(lod_data) => fnv1a(&lod_data.data),
+            Err(_) => fnv1a(data),
+        };
+        Some(hash)
+    }
+
+    /// Entry names are matched ignoring case, the games don't agree on it
+    /// (`dtile.bin` and `DTILE.BIN`, `oute3.odm` and `Oute3.Odm`).
+    pub fn try_get_bytes<'a>(&'a self, name: &str) -> Result<&'a [u8], LodError> {
+        self.get(name)
+            .ok_or_else(|| LodError::EntryNotFound(name.to_string()))
+    }
+
+    /// The entries are stored under their lowercase name.
+    fn get(&self, name: &str) -> Option<&[u8]> {
+        self.files
+            .get(name)
+            .or_else(|| self.files.get(&name.to_lowercase()))
+            .map(|data| self.bytes(data))
+    }
 
-        let magic = try_read_string(&mut buf_reader)?;
-        if magic != "LOD" {
-            return Err("Invalid file format".into());
+    fn bytes<'a>(&'a self, data: &'a EntryData) -> &'a [u8] {
+        match data {
+            EntryData::Owned(data) => data,
+            EntryData::Mapped(range) => {
+                let backing = self
+                    .backing
+                    .as_ref()
+                    .expect("mapped entries have a backing");
+                &(**backing).as_ref()[range.clone()]
+            }
         }
+    }
 
-        let version = Version::try_from(try_read_string(&mut buf_reader)?.as_str())?;
+    /// Content of an entry, decompressed when it is stored compressed.
+    /// Entries with a compression header that fail to inflate are a `LodError::Decompress`.
+    pub fn decompressed(&self, name: &str) -> Result<Vec<u8>, LodError> {
+        let data = self.try_get_bytes(name)?;
+        match LodData::header(data) {
+            Some(_) => {
+                LodData::decompress(data).map_err(|_| LodError::Decompress(name.to_string()))
+            }
+            None => Ok(data.to_vec()),
+        }
+    }
 
-        let file_headers = read_file_headers(&mut buf_reader)?;
-        let files = read_files(file_headers, buf_reader)?;
+    /// Names and decompressed content of the entries in alphabetical order, each entry is
+    /// decompressed when the iterator reaches it and a failing one doesn't stop the others.
+    pub fn iter_decompressed(&self) -> impl Iterator<Item = (&str, Result<Vec<u8>, LodError>)> {
+        let folder = self.directory.first().map(|fh| fh.name.to_lowercase());
+        self.files()
+            .into_iter()
+            .filter(move |name| Some(*name) != folder.as_deref())
+            .map(|name| (name, self.decompressed(name)))
+    }
 
-        Ok(Lod { version, files })
+    /// Every occurrence of `needle` in the decompressed entries, as the entry name and the
+    /// offset in its decompressed content. Overlapping occurrences are all reported and
+    /// entries failing to decompress are skipped.
+    pub fn search_bytes(&self, needle: &[u8]) -> Vec<(String, usize)> {
+        if needle.is_empty() {
+            return Vec::new();
+        }
+        let mut hits = Vec::new();
+        for (name, data) in self.iter_decompressed() {
+            let Ok(data) = data else {
+                continue;
+            };
+            for (offset, window) in data.windows(needle.len()).enumerate() {
+                if window == needle {
+                    hits.push((name.to_string(), offset));
+                }
+            }
+        }
+        hits
     }
 
-    pub(super) fn files(&self) -> Vec<&str> {
-        self.files.keys().map(|f| f.as_str()).collect()
+    /// Granularity the entries are stored at, the largest power of two up to
+    /// [`MAX_ENTRY_ALIGNMENT`] all the offsets from the folder record are multiples of.
+    /// It is detected when the archive is opened, the stock archives pack their entries
+    /// so it is usually 1. [`LodBuilder`] and [`Lod::insert`] pad the entries to keep it.
+    pub fn entry_alignment(&self) -> usize {
+        self.alignment
     }
 
-    pub(super) fn try_get_bytes<'a>(&'a self, name: &str) -> Option<&'a [u8]> {
-        self.files.get(name).map(|v| v.as_slice())
+    /// Adds an entry stored raw, or overwrites the one with the same name.
+    /// New entries keep the directory sorted by name, the game looks entries up with a binary search.
+    pub fn insert(&mut self, name: &str, data: &[u8]) -> Result<(), LodError> {
+        self.insert_with(name, data, false)
+    }
+
+    /// Same as [`Lod::insert`], `compressed` stores the data deflated behind the 8 bytes
+    /// header (compressed size, uncompressed size) instead of raw.
+    /// Names must be ASCII and fit the 16 bytes of the directory record.
+    pub fn insert_with(
+        &mut self,
+        name: &str,
+        data: &[u8],
+        compressed: bool,
+    ) -> Result<(), LodError> {
+        if name.is_empty() || name.len() >= 16 || !name.is_ascii() {
+            return Err(LodError::InvalidEntryName);
+        }
+        if self.directory.is_empty() {
+            return Err(LodError::TruncatedDirectory);
+        }
+        let stored = if compressed {
+            LodData::compress(data, DEFAULT_LEVEL)?.to_bytes()?
+        } else {
+            data.to_vec()
+        };
+
+        let key = name.to_lowercase();
+        let records = &self.directory[1..];
+        if !records.iter().any(|fh| fh.name.to_lowercase() == key) {
+            let index = records
+                .binary_search_by(|fh| fh.name.to_lowercase().cmp(&key))
+                .unwrap_or_else(|i| i);
+            let mut raw_name = [0; 16];
+            raw_name[..name.len()].copy_from_slice(name.as_bytes());
+            let file_header = FileHeader {
+                name: name.to_string(),
+                raw_name,
+                offset: 0,
+                size: 0,
+                unknown: 0,
+                count: 0,
+            };
+            self.directory.insert(index + 1, file_header);
+        }
+        self.files.insert(key, EntryData::Owned(stored));
+        self.update_directory();
+        Ok(())
+    }
+
+    /// Removes an entry, returning the bytes it was stored with.
+    pub fn remove(&mut self, name: &str) -> Option<Vec<u8>> {
+        let key = name.to_lowercase();
+        let index = self
+            .directory
+            .iter()
+            .skip(1)
+            .position(|fh| fh.name.to_lowercase() == key)?;
+        self.directory.remove(index + 1);
+        let data = self
+            .files
+            .remove(&key)
+            .map(|data| self.bytes(&data).to_vec());
+        self.update_directory();
+        data
+    }
+
+    /// Recomputes the directory offsets and sizes the way [`LodBuilder`] packs the archive.
+    fn update_directory(&mut self) {
+        let sizes: Vec<usize> = self
+            .directory
+            .iter()
+            .map(|fh| self.get(&fh.name).map_or(0, <[u8]>::len))
+            .collect();
+        let Some((folder, records)) = self.directory.split_first_mut() else {
+            return;
+        };
+        let mut offset = self.header.len() + FILE_HEADER_SIZE * (records.len() + 1);
+        let start = folder.offset as usize;
+        for (fh, size) in records.iter_mut().zip(&sizes[1..]) {
+            offset = align_from(offset, start, self.alignment);
+            fh.size = *size;
+            fh.offset = offset as i32;
+            offset += fh.size;
+        }
+        folder.count = records.len() as i32;
+        folder.size = offset - folder.offset as usize;
+        self.entries = self
+            .directory
+            .iter()
+            .map(|fh| LodEntry::new(fh, self.get(&fh.name)))
+            .collect();
+    }
+
+    /// Writes the archive back in the lod layout, see [`LodBuilder`] to change entries first.
+    pub fn save<P: AsRef<Path>>(&self, path: P) -> Result<(), LodError> {
+        self.builder().save(path)
+    }
+
+    pub fn builder(&self) -> LodBuilder<'_> {
+        LodBuilder::new(self)
+    }
+
+    /// Writes the decompressed content of every entry to `out_dir/<name>`, creating the
+    /// directory if needed, and returns how many entries were written.
+    /// Names are reduced to a single file name so an entry can't be written outside `out_dir`.
+    pub fn extract_all(&self, out_dir: &Path) -> Result<usize, LodError> {
+        fs::create_dir_all(out_dir)?;
+        let folder = self.directory.first().map(|fh| fh.name.to_lowercase());
+        let mut count = 0;
+        for name in self.files() {
+            if Some(name) == folder.as_deref() {
+                continue;
+            }
+            let data = self.try_get_bytes(name)?;
+            let data = LodData::try_from(data)
+                .map(|lod_data| lod_data.data)
+                .unwrap_or_else(|_| data.to_vec());
+            fs::write(out_dir.join(sanitize_file_name(name)), data)?;
+            count += 1;
+        }
+        Ok(count)
     }
 
     fn save_all(&self, path: &Path, palettes: &palette::Palettes) -> Result<(), Box<dyn Error>> {
         fs::create_dir_all(path)?;
         for file in &self.files {
             let file_name = file.0;
-            let data = file.1.as_slice();
+            let data = self.bytes(file.1);
             if let Ok(image) = crate::image::Image::try_from(data) {
                 if let Err(e) = image.save(path.join(format!("{}.png", file_name))) {
                     println!("Error saving image {} : {}", file_name, e);
@@ -65,94 +477,365 @@ impl Lod {
     }
 }
 
-fn read_file_headers(buf_reader: &mut BufReader<File>) -> Result<Vec<FileHeader>, Box<dyn Error>> {
+/// Serializes an archive with the header and directory order it was opened with.
+/// Offsets are recomputed and the entry data is packed right after the directory,
+/// zero padded to [`Lod::entry_alignment`], so an unmodified archive round-trips to the same bytes.
+pub struct LodBuilder<'a> {
+    lod: &'a Lod,
+    replaced: HashMap<String, Vec<u8>>,
+}
+
+impl<'a> LodBuilder<'a> {
+    pub fn new(lod: &'a Lod) -> Self {
+        Self {
+            lod,
+            replaced: HashMap::new(),
+        }
+    }
+
+    /// Replaces the decompressed content of an existing entry. It is compressed again
+    /// with the same kind of header when the original entry was stored compressed.
+    pub fn replace(mut self, name: &str, data: &[u8]) -> Result<Self, LodError> {
+        let name = name.to_lowercase();
+        let original = self.lod.try_get_bytes(&name)?;
+        let stored = match LodData::try_from(original)
+            .ok()
+            .filter(|d| d.header.is_some())
+        {
+            Some(mut lod_data) => {
+                lod_data.data = data.to_vec();
+                lod_data.to_bytes()?
+            }
+            None => data.to_vec(),
+        };
+        self.replaced.insert(name, stored);
+        Ok(self)
+    }
+
+    pub fn to_bytes(&self) -> Vec<u8> {
+        let mut out = self.lod.header.clone();
+        let Some((folder, records)) = self.lod.directory.split_first() else {
+            return out;
+        };
+        let stored: Vec<&[u8]> = records
+            .iter()
+            .map(|fh| {
+                let name = fh.name.to_lowercase();
+                self.replaced
+                    .get(&name)
+                    .map(|data| data.as_slice())
+                    .or_else(|| self.lod.get(&name))
+                    .unwrap_or_default()
+            })
+            .collect();
+
+        let data_start = out.len() + FILE_HEADER_SIZE * self.lod.directory.len();
+        let start = folder.offset as usize;
+        let mut offsets = Vec::with_capacity(stored.len());
+        let mut end = data_start;
+        for data in &stored {
+            end = align_from(end, start, self.lod.alignment);
+            offsets.push(end);
+            end += data.len();
+        }
+        let folder_size = end as i32 - folder.offset;
+        write_file_header(&mut out, folder, folder.offset, folder_size, records.len());
+
+        for ((fh, data), offset) in records.iter().zip(&stored).zip(&offsets) {
+            write_file_header(
+                &mut out,
+                fh,
+                *offset as i32 - folder.offset,
+                data.len() as i32,
+                fh.count.max(0) as usize,
+            );
+        }
+        for (data, offset) in stored.iter().zip(offsets) {
+            out.resize(offset, 0);
+            out.extend_from_slice(data);
+        }
+        out
+    }
+
+    pub fn save<P: AsRef<Path>>(&self, path: P) -> Result<(), LodError> {
+        fs::write(path, self.to_bytes())?;
+        Ok(())
+    }
+}
+
+/// Largest alignment [`Lod::entry_alignment`] reports.
+pub const MAX_ENTRY_ALIGNMENT: usize = 16;
+
+/// Largest power of two up to [`MAX_ENTRY_ALIGNMENT`] dividing the offsets of all the
+/// records from the folder record, 1 when there are no records.
+fn entry_alignment(directory: &[FileHeader]) -> usize {
+    let Some((folder, records)) = directory.split_first() else {
+        return 1;
+    };
+    records
+        .iter()
+        .map(|fh| {
+            let offset = fh.offset.wrapping_sub(folder.offset) as u32;
+            1usize
+                << offset
+                    .trailing_zeros()
+                    .min(MAX_ENTRY_ALIGNMENT.trailing_zeros())
+        })
+        .min()
+        .unwrap_or(1)
+}
+
+/// Rounds `offset` up to the next multiple of the power of two `alignment` from `start`.
+fn align_from(offset: usize, start: usize, alignment: usize) -> usize {
+    offset + (start.wrapping_sub(offset) & (alignment - 1))
+}
+
+/// Writes a directory record.
+fn write_file_header(out: &mut Vec<u8>, fh: &FileHeader, offset: i32, size: i32, count: usize) {
+    out.extend_from_slice(&fh.raw_name);
+    for v in [offset, size, fh.unknown, count as i32] {
+        out.extend_from_slice(&v.to_le_bytes());
+    }
+}
+
+/// Replaces the characters that are not safe in a file name, `..` stays inside the folder
+/// once the separators are gone.
+fn sanitize_file_name(name: &str) -> String {
+    let name: String = name
+        .chars()
+        .map(|c| {
+            if c.is_ascii_alphanumeric() || matches!(c, '.' | '_' | '-' | ' ') {
+                c
+            } else {
+                '_'
+            }
+        })
+        .collect();
+    match name.as_str() {
+        "" | "." | ".." => "_".repeat(name.len().max(1)),
+        _ => name,
+    }
+}
+
+fn fnv1a(data: &[u8]) -> u64 {
+    data.iter().fold(0xcbf29ce484222325, |hash, &b| {
+        (hash ^ b as u64).wrapping_mul(0x100000001b3)
+    })
+}
+
+/// Header block and directory of an archive, see [`parse_directory`].
+struct Directory {
+    version: LodVersion,
+    header: Vec<u8>,
+    file_headers: Vec<FileHeader>,
+    skipped: usize,
+}
+
+/// Reads the magic, version, header block and directory records of an archive.
+/// When `lenient` an unknown version is accepted, and the records that are malformed
+/// or point outside of `bytes` are skipped instead of failing.
+fn parse_directory(bytes: &[u8], lenient: bool) -> Result<Directory, LodError> {
+    let mut buf_reader = BufReader::new(Cursor::new(bytes));
+    read_magic(&mut buf_reader)?;
+
+    let version = try_read_string(&mut buf_reader);
+    let version = if lenient {
+        version
+            .ok()
+            .and_then(|v| LodVersion::try_from(v.as_str()).ok())
+            .unwrap_or(LodVersion::Unknown)
+    } else {
+        let version = version.map_err(|_| LodError::BadMagic)?;
+        LodVersion::try_from(version.as_str()).map_err(|_| LodError::UnknownVersion(version))?
+    };
+
+    let (file_headers, skipped) = if lenient {
+        read_file_headers_lenient(&mut buf_reader, bytes.len() as u64)?
+    } else {
+        (read_file_headers(&mut buf_reader, bytes.len() as u64)?, 0)
+    };
+    let header = read_header(&mut buf_reader)?;
+    Ok(Directory {
+        version,
+        header,
+        file_headers,
+        skipped,
+    })
+}
+
+/// The whole archive, from its start whatever the position of `reader`.
+fn read_all<R: Read + Seek>(mut reader: R) -> Result<Vec<u8>, LodError> {
+    reader.rewind()?;
+    let mut data = Vec::new();
+    reader.read_to_end(&mut data)?;
+    Ok(data)
+}
+
+/// Raw header block, kept to write the archive back with the same magic, version and description.
+fn read_header<R: Read + Seek>(buf_reader: &mut BufReader<R>) -> Result<Vec<u8>, LodError> {
+    buf_reader.rewind()?;
+    let mut header = vec![0; FILE_INDEX_OFFSET as usize];
+    buf_reader.read_exact(&mut header)?;
+    Ok(header)
+}
+
+fn read_magic<R: Read + Seek>(buf_reader: &mut BufReader<R>) -> Result<(), LodError> {
+    match try_read_string(buf_reader) {
+        Ok(magic) if magic == "LOD" => Ok(()),
+        _ => Err(LodError::BadMagic),
+    }
+}
+
+/// The folder record and the records it counts, a count that is negative or doesn't fit
+/// in the `file_size` bytes of the archive is a truncated directory.
+fn read_file_headers<R: Read + Seek>(
+    buf_reader: &mut BufReader<R>,
+    file_size: u64,
+) -> Result<Vec<FileHeader>, LodError> {
     buf_reader.seek(SeekFrom::Start(FILE_INDEX_OFFSET))?;
     let initial_file_header: FileHeader = read_file_header(buf_reader)?;
     let initial_offset = initial_file_header.offset;
-    let num_files = initial_file_header.count as usize;
-    let mut file_headers = Vec::with_capacity(num_files);
+    let max_files = file_size.saturating_sub(FILE_INDEX_OFFSET) as usize / FILE_HEADER_SIZE;
+    let num_files = usize::try_from(initial_file_header.count)
+        .ok()
+        .filter(|&count| count < max_files)
+        .ok_or(LodError::TruncatedDirectory)?;
+    let mut file_headers = Vec::with_capacity(num_files + 1);
     file_headers.push(initial_file_header);
     for _ in 0..num_files {
         let mut file_header = read_file_header(buf_reader)?;
-        file_header.offset += initial_offset;
+        file_header.offset = file_header
+            .offset
+            .checked_add(initial_offset)
+            .ok_or(LodError::TruncatedDirectory)?;
         file_headers.push(file_header);
     }
     Ok(file_headers)
 }
 
-fn read_file_header(buf_reader: &mut BufReader<File>) -> Result<FileHeader, Box<dyn Error>> {
-    let mut buf: [u8; FILE_HEADER_SIZE] = [0; FILE_HEADER_SIZE];
-    buf_reader.read_exact(&mut buf)?;
-    let file_header = FileHeader::try_from(&buf)?;
-    Ok(file_header)
-}
-
-fn read_files(
-    file_headers: Vec<FileHeader>,
-    mut buf_reader: BufReader<File>,
-) -> Result<HashMap<String, Vec<u8>>, Box<dyn Error>> {
-    let mut files: HashMap<String, Vec<u8>> = HashMap::new();
-    for fh in file_headers {
-        let buf = read_file(&mut buf_reader, &fh)?;
-        files.insert(fh.name.to_lowercase(), buf);
+fn read_file_headers_lenient<R: Read + Seek>(
+    buf_reader: &mut BufReader<R>,
+    file_size: u64,
+) -> Result<(Vec<FileHeader>, usize), LodError> {
+    buf_reader.seek(SeekFrom::Start(FILE_INDEX_OFFSET))?;
+    let initial_file_header: FileHeader = read_file_header(buf_reader)?;
+    let initial_offset = initial_file_header.offset;
+    let num_files = initial_file_header.count.max(0) as usize;
+    let mut file_headers = Vec::new();
+    let mut skipped = 0;
+    if initial_file_header.is_within(file_size) {
+        file_headers.push(initial_file_header);
+    } else {
+        skipped += 1;
+    }
+    for i in 0..num_files {
+        let mut buf = vec![0; FILE_HEADER_SIZE];
+        if buf_reader.read_exact(&mut buf).is_err() {
+            skipped += num_files - i;
+            break;
+        }
+        match FileHeader::try_from(buf.as_slice()) {
+            Ok(mut file_header) => {
+                file_header.offset = file_header.offset.wrapping_add(initial_offset);
+                if file_header.is_within(file_size) {
+                    file_headers.push(file_header);
+                } else {
+                    skipped += 1;
+                }
+            }
+            Err(_) => skipped += 1,
+        }
     }
-    Ok(files)
+    Ok((file_headers, skipped))
 }
 
-fn read_file(buf_reader: &mut BufReader<File>, fh: &FileHeader) -> Result<Vec<u8>, Box<dyn Error>> {
-    buf_reader.seek(SeekFrom::Start(fh.offset as u64))?;
-    let mut buf = vec![0; fh.size];
-    buf_reader.read_exact(&mut buf)?;
-    Ok(buf.to_vec())
+fn read_file_header<R: Read + Seek>(buf_reader: &mut BufReader<R>) -> Result<FileHeader, LodError> {
+    let mut buf = vec![0; FILE_HEADER_SIZE];
+    buf_reader
+        .read_exact(&mut buf)
+        .map_err(|_| LodError::TruncatedDirectory)?;
+    FileHeader::try_from(buf.as_slice())
 }
 
 #[derive(Debug)]
 struct FileHeader {
     name: String,
+    raw_name: [u8; 16],
     offset: i32,
     size: usize,
+    unknown: i32,
     count: i32,
 }
 
+impl FileHeader {
+    fn is_within(&self, file_size: u64) -> bool {
+        self.offset >= 0 && self.offset as u64 + self.size as u64 <= file_size
+    }
+}
+
+/// Size of a directory record. The 32 bytes records of MM6 and MM7 are the only layout
+/// supported, MM8 archives are read assuming the same records.
 const FILE_HEADER_SIZE: usize = 32;
+
+/// Extensions tried, in order, when an entry is not found by name, grouped by kind:
+/// bitmaps (stored bare, without extension), outdoor maps and indoor maps.
+const FALLBACK_EXTENSIONS: [&[&str]; 3] = [&["", "bmp", "pcx"], &["odm", "ddm"], &["blv", "dlv"]];
+/// Size of the header block, the directory starts right after it.
+/// Every version is read with the 256 bytes header of MM6 and MM7, there is nothing to
+/// detect per version.
 const FILE_INDEX_OFFSET: u64 = 256;
 
-impl TryFrom<&[u8; FILE_HEADER_SIZE]> for FileHeader {
-    type Error = Box<dyn Error>;
+impl TryFrom<&[u8]> for FileHeader {
+    type Error = LodError;
 
-    fn try_from(data: &[u8; FILE_HEADER_SIZE]) -> Result<Self, Self::Error> {
+    fn try_from(data: &[u8]) -> Result<Self, Self::Error> {
+        let data = data
+            .get(..FILE_HEADER_SIZE)
+            .ok_or(LodError::TruncatedDirectory)?;
         let first_zero_idx = data.iter().position(|&x| x == 0).unwrap_or(data.len());
-        let name: &str = std::str::from_utf8(&data[0..first_zero_idx])?;
+        let name: &str = std::str::from_utf8(&data[0..first_zero_idx])
+            .map_err(|_| LodError::InvalidEntryName)?;
 
         let mut cursor = Cursor::new(&data[16..]);
         let offset = cursor.read_i32::<LittleEndian>()?;
         let size = cursor.read_i32::<LittleEndian>()?;
-        let _ = cursor.read_i32::<LittleEndian>()?;
+        let unknown = cursor.read_i32::<LittleEndian>()?;
         let count = cursor.read_i32::<LittleEndian>()?;
+        // a record pointing before the folder or with a negative size can't be sliced
+        if offset < 0 || size < 0 {
+            return Err(LodError::TruncatedDirectory);
+        }
+        let mut raw_name = [0; 16];
+        raw_name.copy_from_slice(&data[..16]);
         Ok(FileHeader {
             name: name.to_string(),
+            raw_name,
             offset,
             size: size as usize,
+            unknown,
             count,
         })
     }
 }
 
-// Enum to represent different versions of the games
-enum Version {
+/// Game an archive belongs to, read from the lod header.
+/// It is only reported: the directory of every version is parsed with the same layout.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum LodVersion {
     MM6,
     MM7,
     MM8,
+    Unknown,
 }
 
-impl TryFrom<&str> for Version {
+impl TryFrom<&str> for LodVersion {
     type Error = &'static str;
 
     fn try_from(data: &str) -> Result<Self, Self::Error> {
         match data {
-            "GameMMVI" | "MMVI" => Ok(Version::MM6),
-            "GameMMVII" | "MMVII" => Ok(Version::MM7),
-            "GameMMVIII" | "MMVIII" => Ok(Version::MM8),
+            "GameMMVI" | "MMVI" => Ok(LodVersion::MM6),
+            "GameMMVII" | "MMVII" => Ok(LodVersion::MM7),
+            "GameMMVIII" | "MMVIII" => Ok(LodVersion::MM8),
             _ => Err("Invalid game version"),
         }
     }
@@ -165,6 +848,16 @@ mod tests {
     use super::*;
     use std::path::Path;
 
+    /// Appends a directory record to a fake archive.
+    fn push_header(data: &mut Vec<u8>, name: &[u8], offset: i32, size: i32, count: i32) {
+        let mut header = [0u8; FILE_HEADER_SIZE];
+        header[..name.len()].copy_from_slice(name);
+        for (i, v) in [offset, size, 0, count].iter().enumerate() {
+            header[16 + i * 4..20 + i * 4].copy_from_slice(&v.to_le_bytes());
+        }
+        data.extend(header);
+    }
+
     #[test]
     fn save_works() {
         let lod_path = get_lod_path();
@@ -208,6 +901,519 @@ mod tests {
         assert_eq!(goblin_image.height(), 289);
     }
 
+    #[test]
+    fn largest_entries_works() {
+        let lod_path = get_lod_path();
+        let lod_path = Path::new(&lod_path);
+
+        let games_lod = Lod::open(lod_path.join("games.lod")).unwrap();
+        let largest = games_lod.largest_entries(3);
+        assert_eq!(largest.len(), 3);
+        assert!(largest[0].1 >= largest[1].1);
+        assert!(largest[1].1 >= largest[2].1);
+
+        let max = games_lod
+            .iter_decompressed()
+            .map(|(_, data)| data.unwrap().len())
+            .max()
+            .unwrap();
+        assert_eq!(largest[0].1, max);
+    }
+
+    #[test]
+    fn files_lenient_works() {
+        let mut data = b"LOD\0Unknown\0".to_vec();
+        data.resize(FILE_INDEX_OFFSET as usize, 0);
+        let directory_offset = FILE_INDEX_OFFSET as i32;
+        push_header(
+            &mut data,
+            b"dir",
+            directory_offset,
+            4 * FILE_HEADER_SIZE as i32,
+            3,
+        );
+        push_header(&mut data, b"first", 4 * FILE_HEADER_SIZE as i32, 4, 0);
+        push_header(&mut data, b"\xff\xfe", 0, 4, 0);
+        push_header(&mut data, b"second", 4 * FILE_HEADER_SIZE as i32 + 4, 4, 0);
+        data.extend(b"abcdefgh");
+
+        let path = std::env::temp_dir().join("files_lenient_works.lod");
+        fs::write(&path, &data).unwrap();
+        assert!(Lod::open(&path).is_err());
+
+        let lod = Lod::open_lenient(&path).unwrap();
+        let (entries, skipped) = lod.files_lenient();
+        fs::remove_file(&path).unwrap();
+
+        assert_eq!(skipped, 1);
+        let names: Vec<&str> = entries.iter().map(|e| e.name.as_str()).collect();
+        assert_eq!(names, ["dir", "first", "second"]);
+        assert_eq!(lod.try_get_bytes("first").ok(), Some(b"abcd".as_slice()));
+        assert_eq!(lod.try_get_bytes("second").ok(), Some(b"efgh".as_slice()));
+    }
+
+    #[test]
+    fn open_per_version_works() {
+        for (signature, version) in [
+            ("GameMMVI", LodVersion::MM6),
+            ("MMVII", LodVersion::MM7),
+            ("MMVIII", LodVersion::MM8),
+        ] {
+            let mut data = format!("LOD\0{}\0", signature).into_bytes();
+            data.resize(FILE_INDEX_OFFSET as usize, 0);
+            push_header(
+                &mut data,
+                b"icons",
+                FILE_INDEX_OFFSET as i32,
+                2 * FILE_HEADER_SIZE as i32,
+                1,
+            );
+            push_header(&mut data, b"dtile.bin", 2 * FILE_HEADER_SIZE as i32, 4, 0);
+            data.extend(b"abcd");
+
+            let path = std::env::temp_dir().join(format!("open_per_version_{}.lod", signature));
+            fs::write(&path, &data).unwrap();
+            let lod = Lod::open(&path);
+            fs::remove_file(&path).unwrap();
+
+            let lod = lod.unwrap();
+            assert_eq!(lod.version(), version);
+            assert_eq!(lod.entries[0].name, "icons");
+            assert_eq!(lod.entries[1].name, "dtile.bin");
+            assert_eq!(
+                lod.try_get_bytes("dtile.bin").ok(),
+                Some(b"abcd".as_slice())
+            );
+        }
+        assert!(matches!(
+            FileHeader::try_from(&[0u8; 8][..]),
+            Err(LodError::TruncatedDirectory)
+        ));
+    }
+
+    #[test]
+    fn entries_works() {
+        let mut data = b"LOD\0MMVII\0".to_vec();
+        data.resize(FILE_INDEX_OFFSET as usize, 0);
+        let directory_size = 3 * FILE_HEADER_SIZE as i32;
+        push_header(
+            &mut data,
+            b"games",
+            FILE_INDEX_OFFSET as i32,
+            directory_size,
+            2,
+        );
+        push_header(&mut data, b"zeta", directory_size, 4, 0);
+        push_header(&mut data, b"alpha", directory_size + 4, 2, 0);
+        data.extend(b"abcdef");
+
+        let lod = Lod::from_bytes(data).unwrap();
+
+        let entries: Vec<(&str, usize)> =
+            lod.entries().map(|e| (e.name.as_str(), e.size)).collect();
+        assert_eq!(entries, [("games", 96), ("zeta", 4), ("alpha", 2)]);
+        assert_eq!(lod.uncompressed_size("alpha"), Some(2));
+        assert_eq!(lod.uncompressed_size("missing"), None);
+    }
+
+    #[test]
+    fn from_bytes_works() {
+        let mut data = b"LOD\0GameMMVI\0".to_vec();
+        data.resize(FILE_INDEX_OFFSET as usize, 0);
+        for (name, offset, size, count) in [
+            (b"icons".as_slice(), 256i32, 64, 1),
+            (b"dsft.bin", 64, 3, 0),
+        ] {
+            push_header(&mut data, name, offset, size, count);
+        }
+        data.extend(b"xyz");
+
+        let lod = Lod::from_bytes(data.clone()).unwrap();
+        assert_eq!(lod.version(), LodVersion::MM6);
+        assert_eq!(lod.try_get_bytes("dsft.bin").unwrap(), b"xyz");
+
+        let lenient = Lod::from_reader_lenient(Cursor::new(data)).unwrap();
+        assert_eq!(lenient.files_lenient().1, 0);
+
+        // negative count, size and offset
+        for (count, offset, size) in [(-1, 64, 3), (1, 64, -1), (1, -300, 3)] {
+            let mut data = b"LOD\0GameMMVI\0".to_vec();
+            data.resize(FILE_INDEX_OFFSET as usize, 0);
+            push_header(&mut data, b"icons", 256, 64, count);
+            push_header(&mut data, b"dsft.bin", offset, size, 0);
+            data.extend(b"xyz");
+            assert!(matches!(
+                Lod::from_bytes(data),
+                Err(LodError::TruncatedDirectory)
+            ));
+        }
+        assert!(matches!(
+            Lod::from_bytes(b"LOD".to_vec()),
+            Err(LodError::BadMagic)
+        ));
+    }
+
+    /// An MM6 archive with a raw `Raw.bin` and an 8 bytes header compressed `packed.bin`.
+    fn packed_archive() -> Vec<u8> {
+        let compressed =
+            crate::zlib::compress(b"hello hello hello", crate::lod_data::DEFAULT_LEVEL).unwrap();
+        let mut packed = (compressed.len() as u32).to_le_bytes().to_vec();
+        packed.extend(17u32.to_le_bytes());
+        packed.extend(&compressed);
+
+        let mut data = b"LOD\0GameMMVI\0".to_vec();
+        data.resize(FILE_INDEX_OFFSET as usize, 0);
+        let directory_offset = FILE_INDEX_OFFSET as i32 + FILE_HEADER_SIZE as i32;
+        let entries_size = 2 * FILE_HEADER_SIZE as i32;
+        for (name, offset, size, count) in [
+            (
+                b"icons".as_slice(),
+                directory_offset,
+                entries_size + 3 + packed.len() as i32,
+                2,
+            ),
+            (b"Raw.bin", entries_size, 3, 0),
+            (b"packed.bin", entries_size + 3, packed.len() as i32, 0),
+        ] {
+            push_header(&mut data, name, offset, size, count);
+        }
+        data.extend(b"xyz");
+        data.extend(&packed);
+        data
+    }
+
+    #[test]
+    fn builder_works() {
+        let data = packed_archive();
+        let lod = Lod::from_bytes(data.clone()).unwrap();
+        assert_eq!(lod.builder().to_bytes(), data);
+
+        let edited = lod
+            .builder()
+            .replace("packed.bin", b"changed")
+            .unwrap()
+            .replace("raw.bin", b"abcd")
+            .unwrap()
+            .to_bytes();
+        let edited = Lod::from_bytes(edited).unwrap();
+        assert_eq!(edited.version(), LodVersion::MM6);
+        assert_eq!(edited.try_get_bytes("raw.bin").unwrap(), b"abcd");
+        assert_eq!(edited.decompressed("packed.bin").unwrap(), b"changed");
+        assert_ne!(edited.try_get_bytes("packed.bin").unwrap(), b"changed");
+        assert!(matches!(
+            lod.builder().replace("missing", b""),
+            Err(LodError::EntryNotFound(_))
+        ));
+
+        let path = std::env::temp_dir().join("builder_works.lod");
+        lod.save(&path).unwrap();
+        assert_eq!(fs::read(&path).unwrap(), data);
+        fs::remove_file(&path).unwrap();
+    }
+
+    /// An MM6 archive with two raw entries stored at offsets multiple of 16.
+    fn aligned_archive() -> Vec<u8> {
+        let mut data = b"LOD\0GameMMVI\0".to_vec();
+        data.resize(FILE_INDEX_OFFSET as usize, 0);
+        let directory_offset = FILE_INDEX_OFFSET as i32 + FILE_HEADER_SIZE as i32;
+        let entries_size = 2 * FILE_HEADER_SIZE as i32;
+        for (name, offset, size, count) in [
+            (b"icons".as_slice(), directory_offset, entries_size + 20, 2),
+            (b"a.bin", entries_size, 3, 0),
+            (b"b.bin", entries_size + 16, 4, 0),
+        ] {
+            push_header(&mut data, name, offset, size, count);
+        }
+        data.extend(b"xyz");
+        data.resize(data.len() + 13, 0);
+        data.extend(b"abcd");
+        data
+    }
+
+    #[test]
+    fn entry_alignment_works() {
+        let packed = Lod::from_bytes(packed_archive()).unwrap();
+        assert_eq!(packed.entry_alignment(), 1);
+
+        let data = aligned_archive();
+        let mut lod = Lod::from_bytes(data.clone()).unwrap();
+        assert_eq!(lod.entry_alignment(), 16);
+        assert_eq!(lod.builder().to_bytes(), data);
+
+        let edited = lod.builder().replace("a.bin", b"12345").unwrap().to_bytes();
+        let edited = Lod::from_bytes(edited).unwrap();
+        assert_eq!(edited.entry_alignment(), 16);
+        assert_eq!(edited.try_get_bytes("a.bin").unwrap(), b"12345");
+        assert_eq!(edited.try_get_bytes("b.bin").unwrap(), b"abcd");
+
+        lod.insert("ab.bin", b"new").unwrap();
+        let inserted = Lod::from_bytes(lod.builder().to_bytes()).unwrap();
+        assert_eq!(inserted.entry_alignment(), 16);
+        assert_eq!(inserted.try_get_bytes("ab.bin").unwrap(), b"new");
+        assert_eq!(inserted.try_get_bytes("b.bin").unwrap(), b"abcd");
+    }
+
+    #[test]
+    fn stock_entry_alignment_works() {
+        let lod_path = get_lod_path();
+        let lod_path = Path::new(&lod_path);
+        for name in ["BITMAPS.LOD", "games.lod", "SPRITES.LOD", "icons.lod"] {
+            let lod = Lod::open(lod_path.join(name)).unwrap();
+            let alignment = lod.entry_alignment() as u64;
+            let mut entries = lod.entries();
+            let folder = entries.next().unwrap().offset;
+            assert!(entries.all(|e| (e.offset - folder).is_multiple_of(alignment)));
+        }
+    }
+
+    #[test]
+    fn insert_remove_works() {
+        let mut lod = Lod::from_bytes(packed_archive()).unwrap();
+        lod.insert("zz.bin", b"last").unwrap();
+        lod.insert_with("Aa.bin", b"first first", true).unwrap();
+        lod.insert("RAW.BIN", b"new").unwrap();
+        assert!(matches!(
+            lod.insert("a_name_too_long.bin", b""),
+            Err(LodError::InvalidEntryName)
+        ));
+        assert!(lod.remove("packed.bin").is_some());
+        assert_eq!(lod.remove("packed.bin"), None);
+
+        let names: Vec<&str> = lod.entries().map(|e| e.name.as_str()).collect();
+        assert_eq!(names, ["icons", "aa.bin", "raw.bin", "zz.bin"]);
+
+        let lod = Lod::from_bytes(lod.builder().to_bytes()).unwrap();
+        let names: Vec<&str> = lod.entries().map(|e| e.name.as_str()).collect();
+        assert_eq!(names, ["icons", "aa.bin", "raw.bin", "zz.bin"]);
+        assert_eq!(lod.decompressed("aa.bin").unwrap(), b"first first");
+        assert_ne!(lod.try_get_bytes("aa.bin").unwrap(), b"first first");
+        assert_eq!(lod.try_get_bytes("raw.bin").unwrap(), b"new");
+        assert_eq!(lod.try_get_bytes("zz.bin").unwrap(), b"last");
+    }
+
+    #[test]
+    fn extract_all_works() {
+        let mut lod = Lod::from_bytes(packed_archive()).unwrap();
+        lod.insert("../evil.bin", b"evil").unwrap();
+        lod.insert("..", b"dots").unwrap();
+
+        let out_dir = std::env::temp_dir().join("extract_all_works");
+        let _ = fs::remove_dir_all(&out_dir);
+        assert_eq!(lod.extract_all(&out_dir).unwrap(), 4);
+        assert_eq!(fs::read(out_dir.join("raw.bin")).unwrap(), b"xyz");
+        assert_eq!(
+            fs::read(out_dir.join("packed.bin")).unwrap(),
+            b"hello hello hello"
+        );
+        assert_eq!(fs::read(out_dir.join(".._evil.bin")).unwrap(), b"evil");
+        assert_eq!(fs::read(out_dir.join("__")).unwrap(), b"dots");
+        assert!(!out_dir.join("icons").exists());
+        fs::remove_dir_all(&out_dir).unwrap();
+    }
+
+    #[test]
+    fn entry_sizes_works() {
+        let lod = Lod::from_bytes(packed_archive()).unwrap();
+        let sizes: Vec<(&str, usize, usize)> = lod
+            .entries()
+            .skip(1)
+            .map(|e| (e.name.as_str(), e.stored_size(), e.uncompressed_size()))
+            .collect();
+        let packed_size = lod.try_get_bytes("packed.bin").unwrap().len();
+        assert_eq!(sizes, [("raw.bin", 3, 3), ("packed.bin", packed_size, 17)]);
+
+        assert_eq!(lod.largest_entries(3), [("packed.bin", 17), ("raw.bin", 3)]);
+        assert_eq!(lod.largest_entries(1), [("packed.bin", 17)]);
+    }
+
+    #[test]
+    fn iter_decompressed_works() {
+        let mut lod = Lod::from_bytes(packed_archive()).unwrap();
+        let mut corrupted = 4u32.to_le_bytes().to_vec();
+        corrupted.extend(10u32.to_le_bytes());
+        corrupted.extend(b"junk");
+        lod.insert("corrupted.bin", &corrupted).unwrap();
+
+        let results: Vec<(&str, Result<Vec<u8>, LodError>)> = lod.iter_decompressed().collect();
+        let names: Vec<&str> = results.iter().map(|(name, _)| *name).collect();
+        assert_eq!(names, ["corrupted.bin", "packed.bin", "raw.bin"]);
+        assert_eq!(results.iter().filter(|(_, r)| r.is_ok()).count(), 2);
+        assert!(matches!(&results[0].1, Err(LodError::Decompress(n)) if n == "corrupted.bin"));
+        assert_eq!(results[1].1.as_ref().unwrap(), b"hello hello hello");
+        assert_eq!(results[2].1.as_ref().unwrap(), b"xyz");
+    }
+
+    #[test]
+    fn search_bytes_works() {
+        let lod = Lod::from_bytes(packed_archive()).unwrap();
+        assert_eq!(
+            lod.search_bytes(b"llo"),
+            [
+                ("packed.bin".to_string(), 2),
+                ("packed.bin".to_string(), 8),
+                ("packed.bin".to_string(), 14)
+            ]
+        );
+        assert_eq!(lod.search_bytes(b"yz"), [("raw.bin".to_string(), 1)]);
+        assert!(lod.search_bytes(b"missing").is_empty());
+        assert!(lod.search_bytes(b"").is_empty());
+    }
+
+    #[cfg(feature = "mmap")]
+    #[test]
+    fn open_mmap_works() {
+        let path = std::env::temp_dir().join("open_mmap_works.lod");
+        fs::write(&path, packed_archive()).unwrap();
+        let lod = Lod::open_mmap(&path).unwrap();
+        assert_eq!(lod.try_get_bytes("raw.bin").unwrap(), b"xyz");
+        assert_eq!(
+            lod.decompressed("packed.bin").unwrap(),
+            b"hello hello hello"
+        );
+        assert_eq!(lod.builder().to_bytes(), packed_archive());
+        drop(lod);
+        fs::remove_file(&path).unwrap();
+    }
+
+    #[test]
+    fn ignore_case_works() {
+        let lod = Lod::from_bytes(packed_archive()).unwrap();
+        assert_eq!(lod.try_get_bytes("RAW.BIN").unwrap(), b"xyz");
+        assert_eq!(lod.try_get_bytes("Raw.bin").unwrap(), b"xyz");
+        assert_eq!(
+            lod.decompressed("Packed.Bin").unwrap(),
+            b"hello hello hello"
+        );
+        assert_eq!(lod.uncompressed_size("PACKED.BIN"), Some(17));
+        assert_eq!(lod.entry_hash("PACKED.bin"), lod.entry_hash("packed.bin"));
+        assert!(matches!(
+            lod.try_get_bytes("MISSING.BIN"),
+            Err(LodError::EntryNotFound(n)) if n == "MISSING.BIN"
+        ));
+    }
+
+    #[test]
+    fn lod_error_works() {
+        let path = std::env::temp_dir().join("lod_error_works.lod");
+        fs::write(&path, b"NOT\0").unwrap();
+        assert!(matches!(Lod::open(&path), Err(LodError::BadMagic)));
+
+        let mut data = b"LOD\0Unknown\0".to_vec();
+        fs::write(&path, &data).unwrap();
+        assert!(matches!(Lod::open(&path), Err(LodError::UnknownVersion(v)) if v == "Unknown"));
+
+        data = b"LOD\0MMVII\0".to_vec();
+        data.resize(FILE_INDEX_OFFSET as usize + 8, 0);
+        fs::write(&path, &data).unwrap();
+        assert!(matches!(
+            Lod::open(&path),
+            Err(LodError::TruncatedDirectory)
+        ));
+        fs::remove_file(&path).unwrap();
+
+        // a negative count or one larger than the archive, and an offset overflowing
+        // once added to the folder offset
+        for (count, offset) in [(-1, 0), (i32::MAX, 0), (1, i32::MAX)] {
+            let mut data = b"LOD\0MMVII\0".to_vec();
+            data.resize(FILE_INDEX_OFFSET as usize, 0);
+            push_header(&mut data, b"games", FILE_INDEX_OFFSET as i32, 64, count);
+            push_header(&mut data, b"entry", offset, 0, 0);
+            assert!(matches!(
+                Lod::from_bytes(data),
+                Err(LodError::TruncatedDirectory)
+            ));
+        }
+
+        assert!(matches!(Lod::open(&path), Err(LodError::Io(_))));
+        let lod = Lod {
+            version: LodVersion::MM6,
+            header: Vec::new(),
+            directory: Vec::new(),
+            files: HashMap::new(),
+            backing: None,
+            entries: Vec::new(),
+            skipped: 0,
+            alignment: 1,
+        };
+        assert!(
+            matches!(lod.try_get_bytes("missing"), Err(LodError::EntryNotFound(n)) if n == "missing")
+        );
+    }
+
+    #[test]
+    fn resolve_name_works() {
+        let files = ["grastyl", "oute3.odm", "d01.blv", "d01.dlv"]
+            .iter()
+            .map(|name| (name.to_string(), EntryData::Owned(Vec::new())))
+            .collect();
+        let lod = Lod {
+            version: LodVersion::MM6,
+            header: Vec::new(),
+            directory: Vec::new(),
+            files,
+            backing: None,
+            entries: Vec::new(),
+            skipped: 0,
+            alignment: 1,
+        };
+
+        assert_eq!(lod.resolve_name("grastyl"), Some("grastyl"));
+        assert_eq!(lod.resolve_name("GRASTYL.BMP"), Some("grastyl"));
+        assert_eq!(lod.resolve_name("oute3"), Some("oute3.odm"));
+        assert_eq!(lod.resolve_name("d01"), Some("d01.blv"));
+        assert_eq!(lod.resolve_name("d01.dlv"), Some("d01.dlv"));
+        assert_eq!(lod.resolve_name("oute4"), None);
+        assert_eq!(lod.resolve_name("oute3.blv"), None);
+    }
+
+    #[test]
+    fn resolve_name_same_kind_works() {
+        let files = ["t1.odm", "t1.dlv"]
+            .iter()
+            .map(|name| (name.to_string(), EntryData::Owned(Vec::new())))
+            .collect();
+        let lod = Lod {
+            version: LodVersion::MM6,
+            header: Vec::new(),
+            directory: Vec::new(),
+            files,
+            backing: None,
+            entries: Vec::new(),
+            skipped: 0,
+            alignment: 1,
+        };
+
+        assert_eq!(lod.resolve_name("t1"), Some("t1.odm"));
+        assert_eq!(lod.resolve_name("t1.ddm"), Some("t1.odm"));
+        assert_eq!(lod.resolve_name("t1.blv"), Some("t1.dlv"));
+        assert_eq!(lod.resolve_name("t1.bmp"), None);
+    }
+
+    #[test]
+    fn entry_hash_works() {
+        let lod_with = |entries: &[(&str, &[u8])]| Lod {
+            version: LodVersion::MM6,
+            header: Vec::new(),
+            directory: Vec::new(),
+            files: entries
+                .iter()
+                .map(|(name, data)| (name.to_string(), EntryData::Owned(data.to_vec())))
+                .collect(),
+            backing: None,
+            entries: Vec::new(),
+            skipped: 0,
+            alignment: 1,
+        };
+        let original = lod_with(&[("a", b"same"), ("b", b"original")]);
+        let modded = lod_with(&[("a", b"same"), ("b", b"modded")]);
+
+        assert_eq!(original.entry_hash("a"), modded.entry_hash("a"));
+        assert_ne!(original.entry_hash("b"), modded.entry_hash("b"));
+        assert_eq!(original.entry_hash("c"), None);
+        assert_eq!(fnv1a(b""), 0xcbf29ce484222325);
+        assert_eq!(fnv1a(b"a"), 0xaf63dc4c8601ec8c);
+    }
+
     #[test]
     fn get_sprite() {
         let lod_path = get_lod_path();
@@ -215,6 +1421,6 @@ mod tests {
 
         let sprites_lod = Lod::open(lod_path.join("SPRITES.LOD")).unwrap();
         let rock01 = sprites_lod.try_get_bytes("rock01");
-        assert!(rock01.is_some());
+        assert!(rock01.is_ok());
     }
 }
@@ -1,57 +1,152 @@
-use std::{
-    error::Error,
-    io::{Cursor, Seek},
-    path::Path,
-};
+use std::{borrow::Cow, error::Error, path::Path};
 
-use byteorder::{LittleEndian, ReadBytesExt};
+use thiserror::Error;
 
 #[allow(dead_code)]
 #[derive(Debug)]
 pub struct LodData<'a> {
-    pub header: Option<&'a [u8]>,
+    pub header: Option<Cow<'a, [u8]>>,
     pub data: Vec<u8>,
+    /// zlib level used by `to_bytes`, from 0 to 9
+    level: u32,
+}
+
+/// zlib level of the entries compressed by the game tools.
+pub const DEFAULT_LEVEL: u32 = 6;
+
+/// Sizes declared by the header of a compressed entry.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct LodDataHeader {
+    /// Length of the header itself, 8 bytes for most entries and 48 bytes for bitmaps
+    pub size: usize,
+    pub compressed_size: usize,
+    pub uncompressed_size: usize,
+}
+
+impl LodDataHeader {
+    /// Entries whose sizes are the same hold their bytes uncompressed after the header.
+    pub fn is_stored(&self) -> bool {
+        self.compressed_size == self.uncompressed_size
+    }
+}
+
+/// Errors inflating a compressed entry.
+#[derive(Debug, Error)]
+pub enum LodDataError {
+    #[error("entry has no compression header")]
+    NotCompressed,
+    #[error("zlib error: {0}")]
+    Zlib(#[from] std::io::Error),
+    #[error("declared uncompressed size is {declared}B but {actual}B were inflated")]
+    SizeMismatch { declared: usize, actual: usize },
 }
 
 impl<'a> TryFrom<&'a [u8]> for LodData<'a> {
     type Error = Box<dyn Error>;
 
+    /// Inflates the entry when it has a compression header, otherwise keeps the data as is.
     fn try_from(data: &'a [u8]) -> Result<Self, Self::Error> {
-        if let Ok(lod_data) = decompress_with_8_bytes_header(data) {
-            Ok(lod_data)
-        } else if let Ok(lod_data) = decompress_with_48_bytes_header(data) {
-            Ok(lod_data)
+        if let (Some(header), Ok(inflated)) = (LodData::header(data), LodData::decompress(data)) {
+            Ok(Self {
+                header: Some(Cow::Borrowed(&data[..header.size])),
+                data: inflated,
+                level: DEFAULT_LEVEL,
+            })
         } else {
             Ok(Self {
                 header: None,
                 data: data.to_vec(),
+                level: DEFAULT_LEVEL,
             })
         }
     }
 }
 
-fn decompress_with_48_bytes_header(data: &[u8]) -> Result<LodData, Box<dyn Error>> {
-    let mut cursor = Cursor::new(data);
-    cursor.seek(std::io::SeekFrom::Start(20))?;
-    let compressed_size = cursor.read_u32::<LittleEndian>()? as usize;
-    cursor.seek(std::io::SeekFrom::Current(16))?;
-    let uncompressed_size = cursor.read_u32::<LittleEndian>()? as usize;
-    Ok(LodData {
-        header: Some(&data[..48]),
-        data: super::zlib::decompress(&data[48..], compressed_size, uncompressed_size)?.to_vec(),
-    })
-}
+impl LodData<'_> {
+    /// Reads the compression header of an entry without inflating it, `None` when the
+    /// entry is stored raw. A header is only recognized when the declared compressed size
+    /// matches the data following it.
+    pub fn header(data: &[u8]) -> Option<LodDataHeader> {
+        let read_u32 = |at: usize| {
+            data.get(at..at + 4)
+                .map(|b| u32::from_le_bytes(b.try_into().unwrap()) as usize)
+        };
+        // (header size, offset of the compressed size, offset of the uncompressed size)
+        [(8, 0, 4), (48, 20, 40)]
+            .into_iter()
+            .find_map(|(size, compressed_at, uncompressed_at)| {
+                let compressed_size = read_u32(compressed_at)?;
+                (compressed_size > 0 && compressed_size + size == data.len()).then(|| {
+                    LodDataHeader {
+                        size,
+                        compressed_size,
+                        uncompressed_size: read_u32(uncompressed_at).unwrap_or_default(),
+                    }
+                })
+            })
+    }
 
-fn decompress_with_8_bytes_header(data: &[u8]) -> Result<LodData, Box<dyn Error>> {
-    let compressed_size = u32::from_le_bytes(data[0..=3].try_into()?) as usize;
-    let decompressed_size = u32::from_le_bytes(data[4..=7].try_into()?) as usize;
-    Ok(LodData {
-        header: Some(&data[..8]),
-        data: super::zlib::decompress(&data[8..], compressed_size, decompressed_size)?.to_vec(),
-    })
-}
+    /// Inflates a compressed entry, checking the result against the declared size.
+    /// Stored entries are returned without their header, a deflate stream can happen to be
+    /// as long as its output so inflating is tried first.
+    pub fn decompress(data: &[u8]) -> Result<Vec<u8>, LodDataError> {
+        let header = LodData::header(data).ok_or(LodDataError::NotCompressed)?;
+        let payload = &data[header.size..];
+        let inflated = super::zlib::inflate(payload, header.uncompressed_size);
+        match inflated {
+            Ok(inflated) if inflated.len() == header.uncompressed_size => Ok(inflated),
+            _ if header.is_stored() => Ok(payload.to_vec()),
+            Ok(inflated) => Err(LodDataError::SizeMismatch {
+                declared: header.uncompressed_size,
+                actual: inflated.len(),
+            }),
+            Err(e) => Err(e.into()),
+        }
+    }
+
+    /// Deflates `data` behind the 8 bytes header of the MM6 archives, the compressed size
+    /// followed by the uncompressed size. There is no magic in this header.
+    pub fn compress(data: &[u8], level: u32) -> Result<LodData<'static>, LodDataError> {
+        let deflated = super::zlib::compress(data, level)?;
+        let mut header = (deflated.len() as u32).to_le_bytes().to_vec();
+        header.extend((data.len() as u32).to_le_bytes());
+        Ok(LodData {
+            header: Some(Cow::Owned(header)),
+            data: data.to_vec(),
+            level,
+        })
+    }
+
+    /// The entry as stored in the archive. Entries with a header keep its kind and its other
+    /// fields with the sizes updated for `data`, stored entries stay uncompressed.
+    /// Entries without a header are `data` as is.
+    pub fn to_bytes(&self) -> Result<Vec<u8>, LodDataError> {
+        let Some(header) = &self.header else {
+            return Ok(self.data.clone());
+        };
+        let (compressed_at, uncompressed_at) = if header.len() == 8 { (0, 4) } else { (20, 40) };
+        let mut bytes = header.to_vec();
+        let size_at = |at: usize| bytes.get(at..at + 4);
+        let stored =
+            size_at(compressed_at).is_some() && size_at(compressed_at) == size_at(uncompressed_at);
+        let payload = if stored {
+            self.data.clone()
+        } else {
+            super::zlib::compress(&self.data, self.level)?
+        };
+        for (at, size) in [
+            (compressed_at, payload.len()),
+            (uncompressed_at, self.data.len()),
+        ] {
+            bytes
+                .get_mut(at..at + 4)
+                .ok_or(LodDataError::NotCompressed)?
+                .copy_from_slice(&(size as u32).to_le_bytes());
+        }
+        bytes.extend(payload);
+        Ok(bytes)
+    }
 
-impl LodData<'_> {
     pub fn dump<Q>(&self, path: Q) -> Result<(), Box<dyn Error>>
     where
         Q: AsRef<Path>,
@@ -61,3 +156,89 @@ impl LodData<'_> {
         Ok(())
     }
 }
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn compressed(data: &[u8], declared: u32) -> Vec<u8> {
+        let deflated = crate::zlib::compress(data, DEFAULT_LEVEL).unwrap();
+        let mut packed = (deflated.len() as u32).to_le_bytes().to_vec();
+        packed.extend(declared.to_le_bytes());
+        packed.extend(deflated);
+        packed
+    }
+
+    #[test]
+    fn decompress_works() {
+        let packed = compressed(b"terrain", 7);
+        assert_eq!(
+            LodData::header(&packed),
+            Some(LodDataHeader {
+                size: 8,
+                compressed_size: packed.len() - 8,
+                uncompressed_size: 7
+            })
+        );
+        assert_eq!(LodData::decompress(&packed).unwrap(), b"terrain");
+        assert_eq!(
+            LodData::try_from(packed.as_slice()).unwrap().data,
+            b"terrain"
+        );
+
+        let wrong_size = compressed(b"terrain", 9);
+        assert!(matches!(
+            LodData::decompress(&wrong_size),
+            Err(LodDataError::SizeMismatch {
+                declared: 9,
+                actual: 7
+            })
+        ));
+        let raw = LodData::try_from(wrong_size.as_slice()).unwrap();
+        assert!(raw.header.is_none());
+
+        assert_eq!(LodData::header(b"raw"), None);
+        assert_eq!(LodData::try_from(&b"raw"[..]).unwrap().data, b"raw");
+        assert!(matches!(
+            LodData::decompress(b"raw"),
+            Err(LodDataError::NotCompressed)
+        ));
+    }
+
+    #[test]
+    fn stored_works() {
+        let table = b"Item\tValue\r\nsword\t10\r\n";
+        let mut stored = (table.len() as u32).to_le_bytes().to_vec();
+        stored.extend((table.len() as u32).to_le_bytes());
+        stored.extend(table);
+
+        let header = LodData::header(&stored).unwrap();
+        assert!(header.is_stored());
+        assert_eq!(LodData::decompress(&stored).unwrap(), table);
+        let lod_data = LodData::try_from(stored.as_slice()).unwrap();
+        assert_eq!(lod_data.header.as_deref(), Some(&stored[..8]));
+        assert_eq!(lod_data.data, table);
+        assert_eq!(lod_data.to_bytes().unwrap(), stored);
+    }
+
+    #[test]
+    fn compress_works() {
+        let dtile = vec![7u8; 1000];
+        let lod_data = LodData::compress(&dtile, 9).unwrap();
+        let bytes = lod_data.to_bytes().unwrap();
+        let header = LodData::header(&bytes).unwrap();
+        assert_eq!(header.size, 8);
+        assert_eq!(header.uncompressed_size, 1000);
+        assert!(header.compressed_size < 1000);
+        assert_eq!(lod_data.header.as_deref(), Some(&bytes[..8]));
+        assert_eq!(LodData::decompress(&bytes).unwrap(), dtile);
+
+        let mut edited = LodData::try_from(bytes.as_slice()).unwrap();
+        edited.data.truncate(10);
+        let edited = edited.to_bytes().unwrap();
+        assert_eq!(LodData::decompress(&edited).unwrap(), [7u8; 10]);
+
+        let raw = LodData::try_from(&b"raw"[..]).unwrap();
+        assert_eq!(raw.to_bytes().unwrap(), b"raw");
+    }
+}
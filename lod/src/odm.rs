@@ -1,16 +1,19 @@
 use std::{
+    collections::BTreeSet,
     error::Error,
     io::{Cursor, Read, Seek},
+    ops::Range,
 };
 
-use byteorder::{LittleEndian, ReadBytesExt};
+use byteorder::{LittleEndian, ReadBytesExt, WriteBytesExt};
 
 use crate::{
-    billboard::{read_billboards, Billboard},
-    bsp_model::{read_bsp_models, BSPModel},
-    dtile::{Dtile, TileTable},
+    billboard::{read_billboards, Billboard, Chest},
+    bsp_model::{read_bsp_models, BSPModel, Polygon},
+    colormap::ColorMap,
+    dtile::{Dtile, TileAttributes, TileTable},
     lod_data::LodData,
-    utils::try_read_string_block,
+    utils::{try_read_string, try_read_string_block},
     LodManager,
 };
 
@@ -22,35 +25,82 @@ pub const ODM_TILE_SCALE: f32 = 512.;
 pub const ODM_HEIGHT_SCALE: f32 = 32.;
 
 const HEIGHT_MAP_OFFSET: u64 = 176;
-const HEIGHT_MAP_SIZE: usize = ODM_AREA;
-
-const TILE_MAP_OFFSET: u64 = HEIGHT_MAP_OFFSET + HEIGHT_MAP_SIZE as u64;
-const TILEMAP_SIZE: usize = ODM_AREA;
-
-const ATTRIBUTE_MAP_OFFSET: u64 = TILE_MAP_OFFSET + ATTRIBUTE_MAP_SIZE as u64;
-const ATTRIBUTE_MAP_SIZE: usize = ODM_AREA;
+/// Decorations are loaded by the engine in a fixed size array, the odm itself
+/// only stores how many are used.
+pub const MAX_DECORATIONS: usize = 3000;
 
+/// An outdoor map.
+/// The header has no timing fields: how often the monsters respawn is set per map
+/// in `mapstats.txt`, not in the .odm.
+/// There are no travel nodes either, town portal and travel destinations come from
+/// `2DEvents.txt` and the map transition tables.
 #[allow(dead_code)]
 #[derive(Debug)]
 pub struct Odm {
+    /// Map name stored in the header
     pub name: String,
+    /// Name of the lod entry the map was loaded from, empty for maps parsed from bytes
+    pub file_name: String,
     pub odm_version: String,
     pub sky_texture: String,
     pub ground_texture: String,
     pub tile_data: [u16; 8],
-    pub height_map: [u8; HEIGHT_MAP_SIZE],
-    pub tile_map: [u8; TILEMAP_SIZE],
-    pub attribute_map: [u8; ATTRIBUTE_MAP_SIZE],
+    pub height_map: Vec<u8>,
+    /// Tile index of every grid cell. The .odm embeds no pre-rendered minimap,
+    /// a minimap has to be drawn from this grid.
+    pub tile_map: Vec<u8>,
+    /// Per-vertex terrain flags. The odm has no per-vertex tint or lightmap palette:
+    /// terrain lighting is computed at runtime from the sun, so there are no baked
+    /// colors to feed to the mesh.
+    /// Cells carry no event ids either, events triggered by walking somewhere come
+    /// from decorations with a trigger radius (see `BillboardData::is_triggered_by_touch`).
+    pub attribute_map: Vec<u8>,
     pub bsp_models: Vec<BSPModel>,
+    /// Decorations of the map, read with the MM6 record layout.
+    /// The parser follows the MM6 odm layout from the header on, so MM7 and MM8 maps are
+    /// not supported yet and their object records are not distinguished by version.
     pub billboards: Vec<Billboard>,
+    pub spawn_points: Vec<SpawnPoint>,
+    size: (usize, usize),
+    /// Bytes of the spawn point section in `data`, `None` for maps built in memory
+    spawn_points_range: Option<Range<usize>>,
+    data: Vec<u8>,
 }
 
 impl Odm {
+    /// Loads the map from `games.lod` or from any other opened archive containing it.
     pub fn new(lod_manager: &LodManager, name: &str) -> Result<Self, Box<dyn Error>> {
-        let data = LodData::try_from(lod_manager.try_get_bytes(&format!("games/{}", name))?)?;
-        let data = data.data.as_slice();
+        let archive = lod_manager
+            .find_archive(name, "games")
+            .ok_or(format!("map {} not found in the lod files", name))?;
+        let data = LodData::try_from(lod_manager.try_get_bytes(format!("{}/{}", archive, name))?)?;
+        let mut map = Self::try_from(data.data.as_slice())?;
+        map.file_name = name.to_string();
+        Ok(map)
+    }
+}
+
+impl TryFrom<&[u8]> for Odm {
+    type Error = Box<dyn Error>;
+
+    fn try_from(data: &[u8]) -> Result<Self, Self::Error> {
+        Self::try_from_with_size(data, (ODM_SIZE, ODM_SIZE))
+    }
+}
+
+impl Odm {
+    /// Parses a map with a `(width, depth)` terrain grid.
+    /// The odm header has no field for the grid size, all the maps of the games are
+    /// `ODM_SIZE` x `ODM_SIZE` but modded maps can be read by passing their size here.
+    pub fn try_from_with_size(data: &[u8], size: (usize, usize)) -> Result<Self, Box<dyn Error>> {
+        if size.0 < 2 || size.1 < 2 {
+            return Err("the map grid should be at least 2x2".into());
+        }
+        let area = size.0 * size.1;
 
         let mut cursor = Cursor::new(data);
+        let name = try_read_string_block(&mut cursor, 32)?;
+        // followed by the file name, `file_name` is taken from the lod entry instead
         cursor.seek(std::io::SeekFrom::Start(2 * 32))?;
         let odm_version = try_read_string_block(&mut cursor, 32)?;
         let sky_texture = try_read_string_block(&mut cursor, 32)?;
@@ -67,15 +117,13 @@ impl Odm {
         ];
 
         cursor.seek(std::io::SeekFrom::Start(HEIGHT_MAP_OFFSET))?;
-        let mut height_map: [u8; HEIGHT_MAP_SIZE] = [0; HEIGHT_MAP_SIZE];
+        let mut height_map = vec![0; area];
         cursor.read_exact(&mut height_map)?;
 
-        cursor.seek(std::io::SeekFrom::Start(TILE_MAP_OFFSET))?;
-        let mut tile_map: [u8; TILEMAP_SIZE] = [0; TILEMAP_SIZE];
+        let mut tile_map = vec![0; area];
         cursor.read_exact(&mut tile_map)?;
 
-        cursor.seek(std::io::SeekFrom::Start(ATTRIBUTE_MAP_OFFSET))?;
-        let mut attribute_map: [u8; ATTRIBUTE_MAP_SIZE] = [0; ATTRIBUTE_MAP_SIZE];
+        let mut attribute_map = vec![0; area];
         cursor.read_exact(&mut attribute_map)?;
 
         let bsp_model_count = cursor.read_u32::<LittleEndian>()? as usize;
@@ -84,8 +132,20 @@ impl Odm {
         let billboard_count = cursor.read_u32::<LittleEndian>()? as usize;
         let billboards: Vec<Billboard> = read_billboards(&mut cursor, billboard_count)?;
 
+        // decoration face ids (u16) and the grid of decorations per cell (u32), not used yet
+        let face_id_count = cursor.read_u32::<LittleEndian>()? as i64;
+        cursor.seek(std::io::SeekFrom::Current(
+            face_id_count * 2 + area as i64 * 4,
+        ))?;
+
+        let spawn_points_start = cursor.position() as usize;
+        let spawn_point_count = cursor.read_u32::<LittleEndian>()? as usize;
+        let spawn_points = read_spawn_points(&mut cursor, spawn_point_count)?;
+        let spawn_points_range = spawn_points_start..cursor.position() as usize;
+
         Ok(Self {
-            name: "test".into(),
+            name,
+            file_name: String::new(),
             odm_version,
             sky_texture,
             ground_texture,
@@ -95,13 +155,26 @@ impl Odm {
             attribute_map,
             bsp_models,
             billboards,
+            spawn_points,
+            size,
+            spawn_points_range: Some(spawn_points_range),
+            data: data.to_vec(),
         })
     }
 }
 
 impl Odm {
     pub fn size(&self) -> (usize, usize) {
-        (ODM_SIZE, ODM_SIZE)
+        self.size
+    }
+
+    /// Side of the playable area, the border of the map can't be reached.
+    pub fn play_size(&self) -> (usize, usize) {
+        let border = ODM_SIZE - ODM_PLAY_SIZE;
+        (
+            self.size.0.saturating_sub(border).max(1),
+            self.size.1.saturating_sub(border).max(1),
+        )
     }
 
     pub fn tile_table(&self, lod_manager: &LodManager) -> Result<TileTable, Box<dyn Error>> {
@@ -109,37 +182,618 @@ impl Odm {
             .table(self.tile_data)
             .ok_or("could not get the tile table".into())
     }
+
+    pub fn set_tile(&mut self, col: usize, row: usize, tile: u8) -> Result<(), Box<dyn Error>> {
+        let i = self.index(col, row)?;
+        self.tile_map[i] = tile;
+        Ok(())
+    }
+
+    pub fn set_height(&mut self, col: usize, row: usize, height: u8) -> Result<(), Box<dyn Error>> {
+        let i = self.index(col, row)?;
+        self.height_map[i] = height;
+        Ok(())
+    }
+
+    /// Tile index of a grid cell, `None` outside the map.
+    /// This is the byte as stored in the grid, before it is resolved through the tile table.
+    /// The grid origin is the north-west corner of the map: `col` grows eastward along
+    /// the world x axis and `row` grows southward, toward +z in the viewer (-y in the game).
+    pub fn tile(&self, col: usize, row: usize) -> Option<u8> {
+        self.index(col, row).ok().map(|i| self.tile_map[i])
+    }
+
+    /// Flags of the tile of every grid cell, in the order of [`Odm::tiles`].
+    /// The odm only stores tile indices, the flags come from the tile types of `tile_table`.
+    pub fn tile_attributes(&self, tile_table: &TileTable) -> Vec<TileAttributes> {
+        self.tile_map
+            .iter()
+            .map(|&tile| tile_table.attributes(tile))
+            .collect()
+    }
+
+    /// Tile indices of the whole grid, row by row from the north-west corner,
+    /// `size().0` values per row. See [`Odm::tile`] for the orientation.
+    pub fn tiles(&self) -> &[u8] {
+        &self.tile_map
+    }
+
+    /// Tile indices referenced by the grid, the entries of the `TileTable` this map needs.
+    pub fn used_tile_ids(&self) -> BTreeSet<u8> {
+        self.tile_map.iter().copied().collect()
+    }
+
+    pub fn height(&self, col: usize, row: usize) -> Option<u8> {
+        self.index(col, row).ok().map(|i| self.height_map[i])
+    }
+
+    /// Per-vertex terrain heights, row by row, `size().0` values per row.
+    /// Use [`Odm::height`] for a single vertex and [`Odm::height_at`] for world coordinates.
+    pub fn heightmap(&self) -> &[u8] {
+        &self.height_map
+    }
+
+    fn index(&self, col: usize, row: usize) -> Result<usize, Box<dyn Error>> {
+        let (width, depth) = self.size;
+        if col >= width || row >= depth {
+            return Err(format!("coordinates ({}, {}) are outside the map", col, row).into());
+        }
+        Ok(row * width + col)
+    }
+
+    /// Serializes the map in the odm format, the header, terrain maps and spawn points are
+    /// written from the current values while the other sections are kept as they were read.
+    /// An unmodified map gives back the bytes it was read from.
+    pub fn to_bytes(&self) -> Vec<u8> {
+        let area = self.size.0 * self.size.1;
+        let height_map_offset = HEIGHT_MAP_OFFSET as usize;
+        let tile_map_offset = height_map_offset + area;
+        let attribute_map_offset = tile_map_offset + area;
+        let maps_end = attribute_map_offset + area;
+        let mut data = self.data.clone();
+        let spawn_points_range = match &self.spawn_points_range {
+            Some(range) => range.clone(),
+            None => {
+                // no bsp models, no billboards, no decoration face ids and an empty
+                // decoration grid
+                data.resize(maps_end + 12 + area * 4, 0);
+                data.len()..data.len()
+            }
+        };
+
+        write_string_block(&mut data[0..32], &self.name);
+        write_string_block(&mut data[2 * 32..3 * 32], &self.odm_version);
+        write_string_block(&mut data[3 * 32..4 * 32], &self.sky_texture);
+        write_string_block(&mut data[4 * 32..5 * 32], &self.ground_texture);
+        let mut tile_data = &mut data[5 * 32..HEIGHT_MAP_OFFSET as usize];
+        for t in self.tile_data {
+            // the slice is sized for exactly 8 values
+            tile_data.write_u16::<LittleEndian>(t).unwrap();
+        }
+
+        data[height_map_offset..tile_map_offset].copy_from_slice(&self.height_map);
+        data[tile_map_offset..attribute_map_offset].copy_from_slice(&self.tile_map);
+        data[attribute_map_offset..maps_end].copy_from_slice(&self.attribute_map);
+
+        let mut spawn_points = Vec::with_capacity(4 + self.spawn_points.len() * 20);
+        spawn_points.extend((self.spawn_points.len() as u32).to_le_bytes());
+        for spawn_point in &self.spawn_points {
+            write_spawn_point(&mut spawn_points, spawn_point);
+        }
+        data.splice(spawn_points_range, spawn_points);
+        data
+    }
+
+    /// Terrain height at grid coordinates, interpolated between the tile corners.
+    fn grid_height(&self, w: f32, d: f32) -> f32 {
+        let (width, depth) = self.size;
+        let (w, d) = (
+            w.clamp(0.0, (width - 1) as f32),
+            d.clamp(0.0, (depth - 1) as f32),
+        );
+        let (w0, d0) = (w.floor() as usize, d.floor() as usize);
+        let (w1, d1) = ((w0 + 1).min(width - 1), (d0 + 1).min(depth - 1));
+        let (tw, td) = (w - w0 as f32, d - d0 as f32);
+        let height = |w: usize, d: usize| self.height_map[d * width + w] as f32;
+
+        let top = height(w0, d0) * (1.0 - tw) + height(w1, d0) * tw;
+        let bottom = height(w0, d1) * (1.0 - tw) + height(w1, d1) * tw;
+        top * (1.0 - td) + bottom * td
+    }
+
+    /// Steepest slope of a tile in radians, measured along its edges and diagonals.
+    /// Coordinates outside the map are clamped to the border, on a map one vertex wide
+    /// or deep the tile collapses to a line and only its length is measured.
+    pub fn tile_slope(&self, col: usize, row: usize) -> f32 {
+        let (width, depth) = self.size;
+        if width == 0 || depth == 0 {
+            return 0.;
+        }
+        let (c0, r0) = (
+            col.min(width.saturating_sub(2)),
+            row.min(depth.saturating_sub(2)),
+        );
+        let (c1, r1) = ((c0 + 1).min(width - 1), (r0 + 1).min(depth - 1));
+        let height = |c: usize, r: usize| self.height_map[r * width + c] as f32 * ODM_HEIGHT_SCALE;
+        let (x1, z1) = ((c1 - c0) as f32, (r1 - r0) as f32);
+        let corners: [(f32, f32, f32); 4] = [
+            (height(c0, r0), 0., 0.),
+            (height(c1, r0), x1, 0.),
+            (height(c0, r1), 0., z1),
+            (height(c1, r1), x1, z1),
+        ];
+
+        let mut slope: f32 = 0.;
+        for (i, (h0, x0, z0)) in corners.iter().enumerate() {
+            for (h1, x1, z1) in &corners[i + 1..] {
+                let distance = (x1 - x0).hypot(z1 - z0) * ODM_TILE_SCALE;
+                slope = slope.max((h1 - h0).abs().atan2(distance));
+            }
+        }
+        slope
+    }
+
+    /// Terrain height at the world coordinates used by the generated mesh, bilinearly
+    /// interpolated between the four surrounding tile corners. Coordinates outside the map
+    /// are clamped to its border.
+    pub fn height_at(&self, x: f32, z: f32) -> f32 {
+        let (width, depth) = self.size;
+        let w = x / ODM_TILE_SCALE + width as f32 / 2.;
+        let d = z / ODM_TILE_SCALE + depth as f32 / 2.;
+        self.grid_height(w, d) * ODM_HEIGHT_SCALE
+    }
+
+    /// Grid cell containing the world coordinates, `None` outside the map.
+    pub fn tile_at(&self, x: f32, z: f32) -> Option<(usize, usize)> {
+        let (width, depth) = self.size;
+        let w = x / ODM_TILE_SCALE + width as f32 / 2.;
+        let d = z / ODM_TILE_SCALE + depth as f32 / 2.;
+        if w < 0. || d < 0. || w >= (width - 1) as f32 || d >= (depth - 1) as f32 {
+            return None;
+        }
+        Some((w as usize, d as usize))
+    }
+
+    /// First point where a ray hits the terrain within `max_distance` of its origin,
+    /// in the world coordinates of the generated mesh. The ray is marched a quarter
+    /// of a tile at a time and the hit refined by bisection.
+    pub fn raycast(
+        &self,
+        origin: [f32; 3],
+        direction: [f32; 3],
+        max_distance: f32,
+    ) -> Option<[f32; 3]> {
+        let length = direction.iter().map(|v| v * v).sum::<f32>().sqrt();
+        if length == 0. {
+            return None;
+        }
+        let point = |t: f32| {
+            [
+                origin[0] + direction[0] / length * t,
+                origin[1] + direction[1] / length * t,
+                origin[2] + direction[2] / length * t,
+            ]
+        };
+        let above = |p: [f32; 3]| p[1] > self.height_at(p[0], p[2]);
+        if !above(origin) {
+            return None;
+        }
+
+        let step = ODM_TILE_SCALE / 4.;
+        let mut t0 = 0.;
+        while t0 < max_distance {
+            let t1 = (t0 + step).min(max_distance);
+            if !above(point(t1)) {
+                let (mut lo, mut hi) = (t0, t1);
+                for _ in 0..16 {
+                    let mid = (lo + hi) / 2.;
+                    if above(point(mid)) {
+                        lo = mid;
+                    } else {
+                        hi = mid;
+                    }
+                }
+                let hit = point(hi);
+                return self.tile_at(hit[0], hit[2]).map(|_| hit);
+            }
+            t0 = t1;
+        }
+        None
+    }
+
+    /// How many decorations the engine can hold for a map.
+    pub fn object_capacity(&self) -> usize {
+        MAX_DECORATIONS
+    }
+
+    /// Static 3D models of the map (buildings, bridges, fences...), in the order they are
+    /// stored. `texture_names` holds the texture of each face and the header the model
+    /// position and bounding box, vertices are already in the viewer coordinates.
+    pub fn bmodels(&self) -> &[BSPModel] {
+        &self.bsp_models
+    }
+
+    /// Decorations placed on the map, in the order they are stored, with their declist id,
+    /// position, direction and flags. See [`Billboard::is_drawn`] to filter out the invisible ones.
+    pub fn objects(&self) -> &[Billboard] {
+        &self.billboards
+    }
+
+    /// How many decorations are placed on the map.
+    pub fn object_count(&self) -> usize {
+        self.billboards.len()
+    }
+
+    /// Sky and ground rendering parameters of the map.
+    pub fn environment(&self) -> OdmEnvironment<'_> {
+        OdmEnvironment {
+            sky_texture: &self.sky_texture,
+            ground_texture: &self.ground_texture,
+        }
+    }
+
+    /// Ground footprint of every building, in the order of `bsp_models`.
+    /// MM6 stores no precomputed outlines in the .odm, the game draws buildings on the
+    /// automap from their faces, so these are the convex hulls of the models' vertices.
+    pub fn building_outlines(&self) -> Vec<Polygon> {
+        self.bsp_models.iter().map(BSPModel::outline).collect()
+    }
+
+    /// Spots where monsters and treasure are generated, in the order they are stored.
+    pub fn spawn_points(&self) -> &[SpawnPoint] {
+        &self.spawn_points
+    }
+
+    /// The marker decoration placing the party when entering the map.
+    pub fn party_start(&self) -> Option<&Billboard> {
+        self.billboards
+            .iter()
+            .find(|b| b.declist_name == "party start")
+    }
+
+    /// Initial facing of the party in radians, counterclockwise from the x axis.
+    pub fn party_view_direction(&self) -> Option<f32> {
+        self.party_start()
+            .map(|b| (b.data.direction as f32).to_radians())
+    }
+
+    pub fn chests(&self) -> Vec<Chest> {
+        self.billboards
+            .iter()
+            .filter(|b| b.data.is_chest())
+            .map(Chest::from)
+            .collect()
+    }
+
+    /// Human readable summary of the map, one `[section]` per topic.
+    pub fn report(&self) -> String {
+        let (width, depth) = self.size;
+        let (play_width, play_depth) = self.play_size();
+        let min_height = self.height_map.iter().min().copied().unwrap_or(0);
+        let max_height = self.height_map.iter().max().copied().unwrap_or(0);
+        let environment = self.environment();
+        let spawns_of =
+            |kind: SpawnKind| self.spawn_points.iter().filter(|s| s.kind == kind).count();
+
+        format!(
+            "[map]\n\
+             name: {name}\n\
+             version: {version}\n\
+             size: {width} x {depth}\n\
+             play area: {play_width} x {play_depth}\n\
+             \n\
+             [terrain]\n\
+             tilesets: {tilesets:?}\n\
+             used tiles: {used_tiles}\n\
+             height range: {min_height} - {max_height} ({min_world} - {max_world} world units)\n\
+             \n\
+             [environment]\n\
+             sky: {sky}\n\
+             ground: {ground}\n\
+             \n\
+             [objects]\n\
+             models: {models}\n\
+             decorations: {decorations}\n\
+             chests: {chests}\n\
+             spawn points: {spawn_points} ({monsters} monsters, {treasures} treasures)\n",
+            name = self.name,
+            version = self.odm_version,
+            tilesets = self.tile_data,
+            used_tiles = self.used_tile_ids().len(),
+            min_world = min_height as f32 * ODM_HEIGHT_SCALE,
+            max_world = max_height as f32 * ODM_HEIGHT_SCALE,
+            sky = environment.sky_texture,
+            ground = environment.ground_texture,
+            models = self.bsp_models.len(),
+            decorations = self.object_count(),
+            chests = self.chests().len(),
+            spawn_points = self.spawn_points.len(),
+            monsters = spawns_of(SpawnKind::Monster),
+            treasures = spawns_of(SpawnKind::Treasure),
+        )
+    }
+}
+
+fn write_string_block(block: &mut [u8], s: &str) {
+    // keep the original bytes (and any garbage after the terminator) if unchanged
+    if try_read_string(&mut Cursor::new(&block[..])).is_ok_and(|b| b == s) {
+        return;
+    }
+    block.fill(0);
+    let len = s.len().min(block.len() - 1);
+    block[..len].copy_from_slice(&s.as_bytes()[..len]);
+}
+
+/// Sky and ground bitmaps of an outdoor map.
+/// The header only names these two bitmaps: there are no horizon or zenith colors,
+/// a sky without bitmap is drawn with the viewer's own default gradient.
+/// Nor is there an ambient sound loop, no header field names one.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct OdmEnvironment<'a> {
+    pub sky_texture: &'a str,
+    pub ground_texture: &'a str,
+}
+
+/// A spot generating monsters or treasure, the raw MM6 record.
+/// The record has no count, how many monsters appear is decided by the game
+/// from the map's monster settings.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct SpawnPoint {
+    /// Game coordinates, z up
+    pub position: [i32; 3],
+    pub radius: u16,
+    pub kind: SpawnKind,
+    /// Monster group for monsters (1 to 3 for the map's a, b and c monsters, higher values
+    /// mix them), treasure level for items
+    pub index: u16,
+    pub attributes: u16,
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum SpawnKind {
+    Treasure,
+    Monster,
+    Unknown(u16),
+}
+
+impl From<u16> for SpawnKind {
+    fn from(value: u16) -> Self {
+        match value {
+            2 => SpawnKind::Treasure,
+            3 => SpawnKind::Monster,
+            v => SpawnKind::Unknown(v),
+        }
+    }
+}
+
+impl From<SpawnKind> for u16 {
+    fn from(kind: SpawnKind) -> Self {
+        match kind {
+            SpawnKind::Treasure => 2,
+            SpawnKind::Monster => 3,
+            SpawnKind::Unknown(v) => v,
+        }
+    }
+}
+
+fn write_spawn_point(out: &mut Vec<u8>, spawn_point: &SpawnPoint) {
+    for v in spawn_point.position {
+        out.extend(v.to_le_bytes());
+    }
+    for v in [
+        spawn_point.radius,
+        spawn_point.kind.into(),
+        spawn_point.index,
+        spawn_point.attributes,
+    ] {
+        out.extend(v.to_le_bytes());
+    }
+}
+
+/// Reads `count` MM6 spawn point records, 20 bytes each.
+fn read_spawn_points(
+    cursor: &mut Cursor<&[u8]>,
+    count: usize,
+) -> Result<Vec<SpawnPoint>, Box<dyn Error>> {
+    let mut spawn_points = Vec::with_capacity(count.min(1024));
+    for _i in 0..count {
+        spawn_points.push(SpawnPoint {
+            position: [
+                cursor.read_i32::<LittleEndian>()?,
+                cursor.read_i32::<LittleEndian>()?,
+                cursor.read_i32::<LittleEndian>()?,
+            ],
+            radius: cursor.read_u16::<LittleEndian>()?,
+            kind: cursor.read_u16::<LittleEndian>()?.into(),
+            index: cursor.read_u16::<LittleEndian>()?,
+            attributes: cursor.read_u16::<LittleEndian>()?,
+        });
+    }
+    Ok(spawn_points)
+}
+
+/// A change to a terrain cell, remembering the value it replaced.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum OdmEdit {
+    Tile {
+        col: usize,
+        row: usize,
+        old: u8,
+        new: u8,
+    },
+    Height {
+        col: usize,
+        row: usize,
+        old: u8,
+        new: u8,
+    },
+}
+
+/// Optional editing layer over an `Odm` that records every `set_tile`/`set_height`
+/// so they can be undone and redone. A new edit clears the redo history.
+pub struct OdmEditor {
+    odm: Odm,
+    undo_stack: Vec<OdmEdit>,
+    redo_stack: Vec<OdmEdit>,
+}
+
+impl OdmEditor {
+    pub fn new(odm: Odm) -> Self {
+        Self {
+            odm,
+            undo_stack: Vec::new(),
+            redo_stack: Vec::new(),
+        }
+    }
+
+    pub fn odm(&self) -> &Odm {
+        &self.odm
+    }
+
+    pub fn into_odm(self) -> Odm {
+        self.odm
+    }
+
+    pub fn set_tile(&mut self, col: usize, row: usize, tile: u8) -> Result<(), Box<dyn Error>> {
+        let old = self.odm.tile(col, row).ok_or(format!(
+            "coordinates ({}, {}) are outside the map",
+            col, row
+        ))?;
+        self.apply(OdmEdit::Tile {
+            col,
+            row,
+            old,
+            new: tile,
+        })
+    }
+
+    pub fn set_height(&mut self, col: usize, row: usize, height: u8) -> Result<(), Box<dyn Error>> {
+        let old = self.odm.height(col, row).ok_or(format!(
+            "coordinates ({}, {}) are outside the map",
+            col, row
+        ))?;
+        self.apply(OdmEdit::Height {
+            col,
+            row,
+            old,
+            new: height,
+        })
+    }
+
+    fn apply(&mut self, edit: OdmEdit) -> Result<(), Box<dyn Error>> {
+        self.write(edit, false)?;
+        self.undo_stack.push(edit);
+        self.redo_stack.clear();
+        Ok(())
+    }
+
+    /// Reverts the last edit, returns false when there is nothing to undo.
+    pub fn undo(&mut self) -> bool {
+        let Some(edit) = self.undo_stack.pop() else {
+            return false;
+        };
+        // the coordinates were validated when the edit was applied
+        _ = self.write(edit, true);
+        self.redo_stack.push(edit);
+        true
+    }
+
+    /// Reapplies the last undone edit, returns false when there is nothing to redo.
+    pub fn redo(&mut self) -> bool {
+        let Some(edit) = self.redo_stack.pop() else {
+            return false;
+        };
+        _ = self.write(edit, false);
+        self.undo_stack.push(edit);
+        true
+    }
+
+    pub fn can_undo(&self) -> bool {
+        !self.undo_stack.is_empty()
+    }
+
+    pub fn can_redo(&self) -> bool {
+        !self.redo_stack.is_empty()
+    }
+
+    fn write(&mut self, edit: OdmEdit, revert: bool) -> Result<(), Box<dyn Error>> {
+        match edit {
+            OdmEdit::Tile { col, row, old, new } => {
+                self.odm.set_tile(col, row, if revert { old } else { new })
+            }
+            OdmEdit::Height { col, row, old, new } => {
+                self.odm
+                    .set_height(col, row, if revert { old } else { new })
+            }
+        }
+    }
 }
 
 pub struct OdmData {
     pub positions: Vec<[f32; 3]>,
     pub indices: Vec<u32>,
     pub uvs: Vec<[f32; 2]>,
+    /// Minimum and maximum corners of the box enclosing `positions`, in world space
+    pub aabb: ([f32; 3], [f32; 3]),
+    /// Number of vertices of the grid along x and z, the quads are `indices` 6 at a time
+    /// in rows from the north-west corner
+    pub grid_size: (usize, usize),
+    /// Quads along each side of a tile
+    pub tessellation: usize,
 }
 
 impl OdmData {
     pub fn new(odm: &Odm, tile_table: &TileTable) -> Self {
+        Self::with_tessellation(odm, tile_table, 1)
+    }
+
+    /// Generates the terrain splitting every tile in a `tessellation` x `tessellation` grid,
+    /// the heights of the new vertices are bilinearly interpolated from the tile corners.
+    pub fn with_tessellation(odm: &Odm, tile_table: &TileTable, tessellation: usize) -> Self {
+        let tessellation = tessellation.max(1);
         let (width, depth) = odm.size();
-        let width_u32 = width as u32;
         let (width_half, depth_half) = (width as f32 / 2., depth as f32 / 2.);
+        let (grid_width, grid_depth) = (
+            (width - 1) * tessellation + 1,
+            (depth - 1) * tessellation + 1,
+        );
+        let grid_width_u32 = grid_width as u32;
+        let step = 1.0 / tessellation as f32;
 
-        let vertices_count: usize = width * depth;
+        let vertices_count: usize = grid_width * grid_depth;
         let mut positions: Vec<[f32; 3]> = Vec::with_capacity(vertices_count);
-        let indices_count: usize = (width - 1) * (depth - 1) * 6;
+        let indices_count: usize = (grid_width - 1) * (grid_depth - 1) * 6;
         let mut indices: Vec<u32> = Vec::with_capacity(indices_count);
         let mut uvs: Vec<[f32; 2]> = Vec::with_capacity(indices_count); // vertices will be duplicated so we have as much as the indices
+        let mut aabb = ([f32::MAX; 3], [f32::MIN; 3]);
 
-        for d in 0..depth {
-            for w in 0..width {
-                let i = d * width + w;
-                positions.push([
-                    (w as f32 - width_half) * ODM_TILE_SCALE,
-                    odm.height_map[i] as f32 * ODM_HEIGHT_SCALE,
-                    (d as f32 - depth_half) * ODM_TILE_SCALE,
-                ]);
-                if w < (depth - 1) && d < (depth - 1) {
-                    Self::push_uvs(&mut uvs, tile_table, odm.tile_map[i]);
-                    Self::push_triangle_indices(&mut indices, i as u32, width_u32);
+        for gd in 0..grid_depth {
+            for gw in 0..grid_width {
+                let (w, d) = (gw as f32 * step, gd as f32 * step);
+                let position = [
+                    (w - width_half) * ODM_TILE_SCALE,
+                    odm.grid_height(w, d) * ODM_HEIGHT_SCALE,
+                    (d - depth_half) * ODM_TILE_SCALE,
+                ];
+                for (axis, &value) in position.iter().enumerate() {
+                    aabb.0[axis] = aabb.0[axis].min(value);
+                    aabb.1[axis] = aabb.1[axis].max(value);
+                }
+                positions.push(position);
+                if gw < (grid_width - 1) && gd < (grid_depth - 1) {
+                    let i = (gd / tessellation) * width + gw / tessellation;
+                    Self::push_uvs(
+                        &mut uvs,
+                        tile_table,
+                        odm.tile_map[i],
+                        (gw % tessellation, gd % tessellation),
+                        tessellation,
+                    );
+                    Self::push_triangle_indices(
+                        &mut indices,
+                        (gd * grid_width + gw) as u32,
+                        grid_width_u32,
+                    );
                 }
             }
         }
@@ -148,21 +802,41 @@ impl OdmData {
             positions,
             indices,
             uvs,
+            aabb,
+            grid_size: (grid_width, grid_depth),
+            tessellation,
         }
     }
 
-    fn push_uvs(uvs: &mut Vec<[f32; 2]>, tile_table: &TileTable, tile_index: u8) {
-        let (tile_x, tile_y) = tile_table.coordinate(tile_index);
-        let (tile_x, tile_y) = (tile_x as f32, tile_y as f32);
-        let (tile_table_size_x, tile_table_size_y) = tile_table.size();
-        let (tile_table_size_x, tile_table_size_y) =
-            (tile_table_size_x as f32, tile_table_size_y as f32);
+    /// One color per vertex of `positions`, from the lowest to the highest point of the terrain.
+    pub fn height_colors(&self, color_map: ColorMap) -> Vec<[f32; 4]> {
+        color_map.normalized_colors(self.positions.iter().map(|p| p[1]))
+    }
+
+    /// The uvs of a tile are inset by half a texel from the edges of its atlas cell, so the
+    /// bilinear filtering doesn't blend in the texels of the neighbouring tiles.
+    fn push_uvs(
+        uvs: &mut Vec<[f32; 2]>,
+        tile_table: &TileTable,
+        tile_index: u8,
+        sub_tile: (usize, usize),
+        tessellation: usize,
+    ) {
+        let [min_u, min_v, max_u, max_v] = tile_table.uv_rect(tile_index);
+        let (atlas_width, atlas_height) = tile_table.atlas_dimensions();
+        let (inset_x, inset_y) = (0.5 / atlas_width as f32, 0.5 / atlas_height as f32);
+
+        // `t` goes from 0 to 1 across the tile
+        let u = |t: f32| min_u + inset_x + t * (max_u - min_u - 2.0 * inset_x);
+        let v = |t: f32| min_v + inset_y + t * (max_v - min_v - 2.0 * inset_y);
 
-        let uv_scale: f32 = 1.0;
-        let w_start = (tile_x / tile_table_size_x) / uv_scale;
-        let w_end = ((tile_x + 1.0) / tile_table_size_x) / uv_scale;
-        let h_start = (tile_y / tile_table_size_y) / uv_scale;
-        let h_end = ((tile_y + 1.0) / tile_table_size_y) / uv_scale;
+        let step = 1.0 / tessellation as f32;
+        let (sub_x, sub_y) = (sub_tile.0 as f32 * step, sub_tile.1 as f32 * step);
+
+        let w_start = u(sub_x);
+        let w_end = u(sub_x + step);
+        let h_start = v(sub_y);
+        let h_end = v(sub_y + step);
 
         uvs.push([w_start, h_start]);
         uvs.push([w_start, h_end]);
@@ -187,12 +861,514 @@ impl OdmData {
 #[cfg(test)]
 mod tests {
     use super::*;
-    use crate::{get_lod_path, LodManager};
+    use crate::{billboard::BillboardData, get_lod_path, LodManager};
 
     #[test]
     fn get_map_works() {
         let lod_manager = LodManager::new(get_lod_path()).unwrap();
         let map = Odm::new(&lod_manager, "oute3.odm").unwrap();
-        assert_eq!(map.bsp_models.len(), 85)
+        assert_eq!(map.file_name, "oute3.odm");
+        assert_eq!(map.bsp_models.len(), 85);
+        assert_eq!(map.bmodels().len(), 85);
+        assert_eq!(map.building_outlines().len(), map.bsp_models.len());
+        assert!(!map.spawn_points().is_empty());
+        assert!(map.report().contains("decorations: "));
+    }
+
+    #[test]
+    fn report_works() {
+        let mut odm = test_odm();
+        odm.billboards.push(Billboard::default());
+        odm.billboards.push(Billboard::default());
+        odm.set_height(1, 1, 255).unwrap();
+        odm.spawn_points.push(SpawnPoint {
+            position: [0, 0, 0],
+            radius: 256,
+            kind: SpawnKind::Monster,
+            index: 1,
+            attributes: 0,
+        });
+
+        let report = odm.report();
+        for section in ["[map]", "[terrain]", "[environment]", "[objects]"] {
+            assert!(report.contains(section), "missing {}", section);
+        }
+        assert!(report.contains("decorations: 2\n"));
+        assert!(report.contains("size: 128 x 128\n"));
+        assert!(report.contains("height range: 0 - 255"));
+        assert!(report.contains("spawn points: 1 (1 monsters, 0 treasures)"));
+    }
+
+    #[test]
+    fn spawn_points_works() {
+        let mut bytes = test_odm().to_bytes();
+        assert!(Odm::try_from(bytes.as_slice())
+            .unwrap()
+            .spawn_points()
+            .is_empty());
+
+        let count_at = bytes.len() - 4;
+        bytes[count_at..].copy_from_slice(&1u32.to_le_bytes());
+        for v in [100i32, -200, 30] {
+            bytes.extend(v.to_le_bytes());
+        }
+        for v in [512u16, 3, 2, 0] {
+            bytes.extend(v.to_le_bytes());
+        }
+        let odm = Odm::try_from(bytes.as_slice()).unwrap();
+        assert_eq!(
+            odm.spawn_points(),
+            [SpawnPoint {
+                position: [100, -200, 30],
+                radius: 512,
+                kind: SpawnKind::Monster,
+                index: 2,
+                attributes: 0,
+            }]
+        );
+
+        assert_eq!(odm.to_bytes(), bytes);
+
+        let mut edited = odm;
+        edited.spawn_points[0].kind = SpawnKind::Treasure;
+        edited.spawn_points.push(edited.spawn_points[0]);
+        let reparsed = Odm::try_from(edited.to_bytes().as_slice()).unwrap();
+        assert_eq!(reparsed.spawn_points(), edited.spawn_points());
+        assert_eq!(reparsed.spawn_points()[0].kind, SpawnKind::Treasure);
+
+        bytes.truncate(bytes.len() - 1);
+        assert!(Odm::try_from(bytes.as_slice()).is_err());
+    }
+
+    #[test]
+    fn uvs_are_inset_works() {
+        let mut names: [String; 256] = std::array::from_fn(|_| "grastyl".to_string());
+        names[1] = "dirttyl".to_string();
+        let tile_table = TileTable::new(names);
+        let mut odm = test_odm();
+        odm.set_tile(0, 0, 1).unwrap();
+        let (atlas_width, _) = tile_table.atlas_dimensions();
+        let half_texel = 0.5 / atlas_width as f32;
+
+        for tessellation in [1, 2] {
+            let odm_data = OdmData::with_tessellation(&odm, &tile_table, tessellation);
+            let (min, max) = odm_data
+                .uvs
+                .iter()
+                .fold((1.0f32, 0.0f32), |(min, max), uv| {
+                    (min.min(uv[0]), max.max(uv[0]))
+                });
+            assert!((min - half_texel).abs() < 1e-6);
+            assert!((max - (1.0 - half_texel)).abs() < 1e-6);
+            // the first tile ends half a texel before the cell of the second one
+            let first_tile_end = odm_data.uvs[..6 * tessellation]
+                .iter()
+                .map(|uv| uv[0])
+                .fold(0.0, f32::max);
+            assert!((first_tile_end - (0.5 - half_texel)).abs() < 1e-6);
+        }
+    }
+
+    #[test]
+    fn height_colors_works() {
+        let odm_data = OdmData {
+            positions: vec![[0.0, 10.0, 0.0], [0.0, 30.0, 0.0], [0.0, 20.0, 0.0]],
+            indices: vec![0, 1, 2],
+            uvs: vec![],
+            aabb: ([0.0, 10.0, 0.0], [0.0, 30.0, 0.0]),
+            grid_size: (3, 1),
+            tessellation: 1,
+        };
+        for color_map in ColorMap::ALL {
+            let colors = odm_data.height_colors(color_map);
+            assert_eq!(colors.len(), 3);
+            assert_eq!(colors[0], color_map.color(0.0));
+            assert_eq!(colors[1], color_map.color(1.0));
+            assert_eq!(colors[2], color_map.color(0.5));
+        }
+        assert_eq!(ColorMap::Grayscale.color(0.0), [0.0, 0.0, 0.0, 1.0]);
+        assert_eq!(ColorMap::Grayscale.color(1.0), [1.0, 1.0, 1.0, 1.0]);
+        assert_eq!(
+            ColorMap::Grayscale.color(-1.0),
+            ColorMap::Grayscale.color(0.0)
+        );
+    }
+
+    fn test_odm_with_size(size: (usize, usize)) -> Odm {
+        let area = size.0 * size.1;
+        Odm {
+            name: "test".into(),
+            file_name: String::new(),
+            odm_version: String::new(),
+            sky_texture: String::new(),
+            ground_texture: String::new(),
+            tile_data: [0; 8],
+            height_map: (0..area).map(|i| (i % 7 * 10) as u8).collect(),
+            tile_map: vec![0; area],
+            attribute_map: vec![0; area],
+            bsp_models: Vec::new(),
+            billboards: Vec::new(),
+            spawn_points: Vec::new(),
+            size,
+            spawn_points_range: None,
+            data: Vec::new(),
+        }
+    }
+
+    fn test_odm() -> Odm {
+        test_odm_with_size((ODM_SIZE, ODM_SIZE))
+    }
+
+    fn test_tile_table() -> TileTable {
+        TileTable::new(std::array::from_fn(|_| "grastyl".to_string()))
+    }
+
+    #[test]
+    fn tessellation_works() {
+        let odm = test_odm();
+        let tile_table = test_tile_table();
+        let base = OdmData::new(&odm, &tile_table);
+        let tessellated = OdmData::with_tessellation(&odm, &tile_table, 2);
+
+        assert_eq!(base.positions.len(), ODM_AREA);
+        assert_eq!(tessellated.positions.len(), 255 * 255);
+        assert_eq!(tessellated.indices.len(), base.indices.len() * 4);
+        assert_eq!(tessellated.uvs.len(), tessellated.indices.len());
+        assert_eq!(base.grid_size, (ODM_SIZE, ODM_SIZE));
+        assert_eq!(tessellated.grid_size, (255, 255));
+        assert_eq!(tessellated.tessellation, 2);
+
+        // the vertex between two corners lies between their heights
+        let grid_width = 255;
+        for i in 0..(ODM_SIZE - 1) {
+            let a = tessellated.positions[2 * i][1];
+            let mid = tessellated.positions[2 * i + 1][1];
+            let b = tessellated.positions[2 * i + 2][1];
+            assert!(mid >= a.min(b) && mid <= a.max(b));
+            assert_eq!(mid, (a + b) / 2.0);
+        }
+        assert_eq!(
+            tessellated.positions[2 * grid_width + 2],
+            base.positions[ODM_SIZE + 1]
+        );
+    }
+
+    #[test]
+    fn aabb_works() {
+        let odm = test_odm_with_size((8, 4));
+        let odm_data = OdmData::with_tessellation(&odm, &test_tile_table(), 2);
+        let (min, max) = odm_data.aabb;
+        for p in &odm_data.positions {
+            for (axis, &value) in p.iter().enumerate() {
+                assert!(min[axis] <= value && value <= max[axis]);
+            }
+        }
+        assert_eq!(min[0], -4. * ODM_TILE_SCALE);
+        assert_eq!(max[0], 3. * ODM_TILE_SCALE);
+        assert_eq!(min[1], 0.);
+        assert_eq!(max[1], 60. * ODM_HEIGHT_SCALE);
+    }
+
+    #[test]
+    fn custom_size_works() {
+        let mut odm = test_odm_with_size((64, 32));
+        odm.set_tile(63, 31, 7).unwrap();
+        assert!(odm.set_tile(64, 0, 7).is_err());
+        assert!(odm.set_height(0, 32, 7).is_err());
+
+        let bytes = odm.to_bytes();
+        assert!(Odm::try_from_with_size(bytes.as_slice(), (1, 32)).is_err());
+        let parsed = Odm::try_from_with_size(bytes.as_slice(), (64, 32)).unwrap();
+        assert_eq!(parsed.size(), (64, 32));
+        assert_eq!(parsed.play_size(), (24, 1));
+        assert_eq!(parsed.height_map.len(), 64 * 32);
+        assert_eq!(parsed.tile_map[31 * 64 + 63], 7);
+        assert_eq!(parsed.height_map, odm.height_map);
+
+        let odm_data = OdmData::new(&parsed, &test_tile_table());
+        assert_eq!(odm_data.positions.len(), 64 * 32);
+        assert_eq!(odm_data.indices.len(), 63 * 31 * 6);
+    }
+
+    #[test]
+    fn texture_names_works() {
+        let mut bytes = test_odm().to_bytes();
+        bytes[3 * 32..4 * 32].copy_from_slice(&[b'a'; 32]);
+        bytes[4 * 32..5 * 32].fill(0);
+        bytes[4 * 32..4 * 32 + 7].copy_from_slice(b"dirttyl");
+        let odm = Odm::try_from(bytes.as_slice()).unwrap();
+        assert_eq!(odm.sky_texture, "a".repeat(32));
+        assert_eq!(odm.ground_texture, "dirttyl");
+    }
+
+    #[test]
+    fn environment_works() {
+        let mut odm = test_odm();
+        odm.sky_texture = "sky01".into();
+        let environment = odm.environment();
+        assert_eq!(environment.sky_texture, "sky01");
+
+        // the names are the 4th and 5th blocks of the header, followed by the tile data,
+        // then the 3 maps, 4 empty lists and the decoration grid of a 2x2 map
+        let size = (2, 2);
+        let mut data = vec![0; HEIGHT_MAP_OFFSET as usize + 3 * 4 + 4 * 4 + 4 * 4];
+        data[3 * 32..3 * 32 + 5].copy_from_slice(b"sky02");
+        data[4 * 32..4 * 32 + 7].copy_from_slice(b"grastyl");
+        let odm = Odm::try_from_with_size(&data, size).unwrap();
+        assert_eq!(
+            odm.environment(),
+            OdmEnvironment {
+                sky_texture: "sky02",
+                ground_texture: "grastyl",
+            }
+        );
+    }
+
+    #[test]
+    fn odm_editor_works() {
+        let odm = test_odm();
+        let old_height = odm.height(7, 2).unwrap();
+        let mut editor = OdmEditor::new(odm);
+        assert!(!editor.undo());
+
+        editor.set_tile(3, 5, 42).unwrap();
+        editor.set_height(7, 2, 200).unwrap();
+        assert!(editor.set_tile(ODM_SIZE, 0, 1).is_err());
+
+        assert!(editor.undo());
+        assert_eq!(editor.odm().height(7, 2), Some(old_height));
+        assert_eq!(editor.odm().tile(3, 5), Some(42));
+        assert!(editor.can_redo());
+
+        assert!(editor.redo());
+        assert!(!editor.redo());
+        let odm = editor.into_odm();
+        assert_eq!(odm.tile(3, 5), Some(42));
+        assert_eq!(odm.height(7, 2), Some(200));
+    }
+
+    #[test]
+    fn used_tile_ids_works() {
+        let mut odm = test_odm();
+        odm.tile_map.fill(0);
+        odm.set_tile(3, 5, 42).unwrap();
+        odm.set_tile(ODM_SIZE - 1, ODM_SIZE - 1, 200).unwrap();
+        odm.set_tile(4, 5, 42).unwrap();
+
+        let used = odm.used_tile_ids();
+        assert_eq!(used.len(), 3);
+        assert_eq!(used.into_iter().collect::<Vec<u8>>(), [0, 42, 200]);
+    }
+
+    #[test]
+    fn tile_works() {
+        let mut odm = test_odm();
+        odm.tile_map[9 * ODM_SIZE + 4] = 0xa5;
+        assert_eq!(odm.tile(4, 9), Some(0xa5));
+        assert_eq!(odm.tile(ODM_SIZE, 9), None);
+        assert_eq!(odm.tile(4, ODM_SIZE), None);
+        assert_eq!(odm.tiles()[9 * ODM_SIZE + 4], 0xa5);
+        assert_eq!(odm.tiles().len(), ODM_SIZE * ODM_SIZE);
+    }
+
+    #[test]
+    fn tile_attributes_works() {
+        let odm = test_odm();
+        let attributes = odm.tile_attributes(&test_tile_table());
+        assert_eq!(attributes.len(), odm.tiles().len());
+        assert!(attributes.iter().all(|a| *a == TileAttributes::default()));
+    }
+
+    #[test]
+    fn heightmap_works() {
+        let mut odm = test_odm();
+        odm.set_height(4, 9, 42).unwrap();
+        assert_eq!(odm.heightmap().len(), ODM_SIZE * ODM_SIZE);
+        assert_eq!(odm.heightmap()[9 * ODM_SIZE + 4], 42);
+        assert_eq!(odm.height(4, 9), Some(42));
+    }
+
+    #[test]
+    fn height_at_works() {
+        let mut odm = test_odm_with_size((4, 4));
+        odm.height_map.fill(0);
+        odm.set_height(1, 1, 10).unwrap();
+
+        let (x, z) = (-ODM_TILE_SCALE, -ODM_TILE_SCALE);
+        assert_eq!(odm.height_at(x, z), 10. * ODM_HEIGHT_SCALE);
+        assert_eq!(
+            odm.height_at(x + ODM_TILE_SCALE / 2., z),
+            5. * ODM_HEIGHT_SCALE
+        );
+        assert_eq!(odm.height_at(-1e6, -1e6), 0.);
+
+        odm.set_height(3, 3, 20).unwrap();
+        assert_eq!(odm.height_at(1e6, 1e6), 20. * ODM_HEIGHT_SCALE);
+        assert_eq!(odm.height_at(1e6, z), 0.);
+    }
+
+    #[test]
+    fn object_count_works() {
+        let mut odm = test_odm();
+        assert_eq!(odm.object_count(), 0);
+        odm.billboards.push(Billboard::default());
+        assert_eq!(odm.object_count(), 1);
+        assert_eq!(odm.objects().len(), 1);
+        assert!(odm.object_count() <= odm.object_capacity());
+    }
+
+    #[test]
+    fn party_view_direction_works() {
+        let mut odm = test_odm();
+        assert_eq!(odm.party_view_direction(), None);
+
+        let mut party_start = Billboard {
+            declist_name: "party start".into(),
+            ..Default::default()
+        };
+        party_start.data.direction = 90;
+        odm.billboards.push(party_start);
+
+        let direction = odm.party_view_direction().unwrap();
+        let forward = (direction.cos(), direction.sin());
+        assert!(forward.0.abs() < 1e-6);
+        assert!((forward.1 - 1.).abs() < 1e-6);
+    }
+
+    #[test]
+    fn tile_at_works() {
+        let odm = test_odm_with_size((4, 4));
+        assert_eq!(odm.tile_at(0., 0.), Some((2, 2)));
+        assert_eq!(
+            odm.tile_at(-2. * ODM_TILE_SCALE, -2. * ODM_TILE_SCALE),
+            Some((0, 0))
+        );
+        assert_eq!(odm.tile_at(-1., 0.), Some((1, 2)));
+        assert_eq!(odm.tile_at(ODM_TILE_SCALE, 0.), None);
+        assert_eq!(odm.tile_at(-3. * ODM_TILE_SCALE, 0.), None);
+    }
+
+    #[test]
+    fn raycast_works() {
+        let mut odm = test_odm_with_size((8, 8));
+        odm.height_map.fill(0);
+        let hit = odm
+            .raycast([10., 1000., -20.], [0., -1., 0.], 5000.)
+            .unwrap();
+        assert!((hit[0] - 10.).abs() < 1e-3 && (hit[2] + 20.).abs() < 1e-3);
+        assert!(hit[1].abs() < 0.1);
+
+        odm.set_height(4, 4, 20).unwrap();
+        let hit = odm.raycast([0., 1000., 0.], [0., -1., 0.], 5000.).unwrap();
+        assert!((hit[1] - 20. * ODM_HEIGHT_SCALE).abs() < 0.1);
+
+        assert_eq!(odm.raycast([0., 1000., 0.], [0., 1., 0.], 5000.), None);
+        assert_eq!(odm.raycast([0., 1000., 0.], [0., -1., 0.], 100.), None);
+        assert_eq!(odm.raycast([0., -10., 0.], [0., -1., 0.], 5000.), None);
+    }
+
+    #[test]
+    fn tile_slope_works() {
+        let mut odm = test_odm_with_size((4, 4));
+        odm.height_map.fill(0);
+        assert!(odm.tile_slope(1, 1).abs() < 1e-6);
+
+        odm.set_height(2, 2, 255).unwrap();
+        assert!(odm.tile_slope(1, 1) > 1.0);
+        assert_eq!(odm.tile_slope(0, 0), 0.);
+        assert_eq!(odm.tile_slope(10, 10), odm.tile_slope(2, 2));
+    }
+
+    #[test]
+    fn tile_slope_narrow_map_works() {
+        let mut odm = test_odm_with_size((1, 3));
+        odm.height_map.fill(0);
+        assert_eq!(odm.tile_slope(0, 0), 0.);
+        assert_eq!(odm.tile_slope(5, 5), 0.);
+
+        odm.set_height(0, 1, 16).unwrap();
+        let rise = 16. * ODM_HEIGHT_SCALE;
+        assert!((odm.tile_slope(0, 0) - rise.atan2(ODM_TILE_SCALE)).abs() < 1e-6);
+
+        let odm = test_odm_with_size((0, 0));
+        assert_eq!(odm.tile_slope(0, 0), 0.);
+    }
+
+    #[test]
+    fn to_bytes_round_trip_works() {
+        let mut odm = test_odm();
+        odm.sky_texture = "sky01".into();
+        odm.set_tile(3, 5, 42).unwrap();
+        odm.set_height(7, 2, 200).unwrap();
+        assert!(odm.set_tile(ODM_SIZE, 0, 1).is_err());
+
+        let bytes = odm.to_bytes();
+        let mut parsed = Odm::try_from(bytes.as_slice()).unwrap();
+        assert_eq!(parsed.name, "test");
+        assert_eq!(parsed.sky_texture, "sky01");
+        assert_eq!(parsed.tile_map[5 * ODM_SIZE + 3], 42);
+        assert_eq!(parsed.height_map[2 * ODM_SIZE + 7], 200);
+        assert_eq!(parsed.height_map, odm.height_map);
+
+        parsed.set_tile(3, 5, 43).unwrap();
+        parsed.name = "renamed".into();
+        let reparsed = Odm::try_from(parsed.to_bytes().as_slice()).unwrap();
+        assert_eq!(reparsed.tile_map[5 * ODM_SIZE + 3], 43);
+        assert_eq!(reparsed.name, "renamed");
+        assert_eq!(reparsed.to_bytes().len(), bytes.len());
+    }
+
+    #[test]
+    fn get_map_round_trip_works() {
+        let lod_manager = LodManager::new(get_lod_path()).unwrap();
+        let mut map = Odm::new(&lod_manager, "oute3.odm").unwrap();
+        assert_eq!(map.to_bytes(), map.data);
+
+        map.set_tile(10, 20, 99).unwrap();
+        let parsed = Odm::try_from(map.to_bytes().as_slice()).unwrap();
+        assert_eq!(parsed.tile_map[20 * ODM_SIZE + 10], 99);
+        assert_eq!(parsed.bsp_models.len(), map.bsp_models.len());
+        assert_eq!(parsed.billboards.len(), map.billboards.len());
+        assert_eq!(parsed.spawn_points, map.spawn_points);
+    }
+
+    #[test]
+    fn get_chests_works() {
+        let lod_manager = LodManager::new(get_lod_path()).unwrap();
+        let map = Odm::new(&lod_manager, "oute3.odm").unwrap();
+        let chests = map.chests();
+        assert!(chests.iter().all(|c| !c.declist_name.is_empty()));
+        let half = (ODM_SIZE as i32 / 2) * ODM_TILE_SCALE as i32;
+        assert!(chests
+            .iter()
+            .all(|c| c.position[0].abs() <= half && c.position[1].abs() <= half));
+    }
+
+    #[test]
+    fn chests_works() {
+        let billboard = |name: &str, attributes: u16, position: [i32; 3]| Billboard {
+            declist_name: name.into(),
+            data: BillboardData {
+                declist_id: 7,
+                attributes,
+                position,
+                event: 12,
+                ..Default::default()
+            },
+        };
+        let mut odm = test_odm();
+        odm.billboards = vec![
+            billboard("tree1", 0, [0, 0, 0]),
+            billboard("chest01", 0x0020, [-1024, 2048, 96]),
+            billboard("crate", 0x0020 | 0x0010, [512, -512, 0]),
+            billboard("well", 0x0010, [256, 256, 0]),
+        ];
+
+        let chests = odm.chests();
+        assert_eq!(chests.len(), 2);
+        assert_eq!(chests[0].declist_name, "chest01");
+        assert_eq!(chests[0].position, [-1024, 2048, 96]);
+        assert_eq!(chests[0].event, 12);
+        assert_eq!(chests[1].position, [512, -512, 0]);
     }
 }
@@ -51,7 +51,7 @@ impl TryFrom<&Lod> for Palettes {
 
         let mut palettes: HashMap<u16, Palette> = HashMap::new();
         for file_name in palette_files {
-            let palette = Palette::try_from(lod.try_get_bytes(&file_name).ok_or("expected file")?)?;
+            let palette = Palette::try_from(lod.try_get_bytes(&file_name)?)?;
             let id = extract_palette_id(&file_name)?;
             palettes.insert(id, palette);
         }
@@ -20,12 +20,17 @@ pub(super) fn try_read_name(name: &[u8]) -> Option<String> {
     try_read_string(&mut cursor).map(|s| s.to_lowercase()).ok()
 }
 
+/// Reads a fixed size string field, up to its first null or to the end of the field
+/// when it's fully used.
 pub(super) fn try_read_string_block(
     cursor: &mut Cursor<&[u8]>,
     size: usize,
 ) -> Result<String, Box<dyn Error>> {
-    let pos = cursor.position();
-    let s = try_read_string(cursor)?;
-    cursor.seek(std::io::SeekFrom::Start(pos + size as u64))?;
+    let pos = cursor.position() as usize;
+    let data = cursor.get_ref();
+    let block = &data[pos.min(data.len())..(pos + size).min(data.len())];
+    let end = block.iter().position(|&b| b == 0).unwrap_or(block.len());
+    let s = String::from_utf8(block[..end].to_vec())?;
+    cursor.seek(std::io::SeekFrom::Start((pos + size) as u64))?;
     Ok(s)
 }
@@ -1,7 +1,7 @@
-use flate2::bufread::ZlibDecoder;
+use flate2::{bufread::ZlibDecoder, write::ZlibEncoder, Compression};
 use std::{
     error::Error,
-    io::{BufReader, Cursor, Read},
+    io::{BufReader, Cursor, Read, Write},
 };
 
 pub fn decompress(
@@ -10,14 +10,24 @@ pub fn decompress(
     uncompressed_size: usize,
 ) -> Result<Vec<u8>, Box<dyn Error>> {
     check_size(data.len(), compressed_size)?;
-    let uncompressed_data = decompress_zlib(data, uncompressed_size)?;
+    let uncompressed_data = inflate(data, uncompressed_size)?;
     check_size(uncompressed_data.len(), uncompressed_size)?;
     Ok(uncompressed_data)
 }
 
-fn decompress_zlib(data: &[u8], reserve_size: usize) -> Result<Vec<u8>, Box<dyn Error>> {
-    let reader: BufReader<_> = BufReader::new(Cursor::new(data));
-    let mut z = ZlibDecoder::new(reader);
+/// `level` goes from 0 (stored) to 9 (best), higher values are clamped.
+pub fn compress(data: &[u8], level: u32) -> std::io::Result<Vec<u8>> {
+    let mut z = ZlibEncoder::new(
+        Vec::with_capacity(data.len()),
+        Compression::new(level.min(9)),
+    );
+    z.write_all(data)?;
+    z.finish()
+}
+
+/// Inflates a zlib stream without checking its size.
+pub fn inflate(data: &[u8], reserve_size: usize) -> std::io::Result<Vec<u8>> {
+    let mut z = ZlibDecoder::new(BufReader::new(Cursor::new(data)));
     let mut buf: Vec<u8> = Vec::with_capacity(reserve_size);
     z.read_to_end(&mut buf)?;
     Ok(buf)
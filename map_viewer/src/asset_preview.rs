@@ -0,0 +1,181 @@
+use bevy::{
+    input::InputSystem, prelude::*, render::render_asset::RenderAssetUsages,
+    window::ReceivedCharacter,
+};
+
+use crate::{despawn_all, dev::KeyBindings, world::WorldSettings, GameState};
+
+/// Biggest side of the preview on screen, in pixels
+const PREVIEW_SIZE: f32 = 512.0;
+
+/// Shows a sprite or a bitmap of the lod archives by name,
+/// the name is typed while the preview is open and decoded on enter.
+pub struct AssetPreviewPlugin;
+
+impl Plugin for AssetPreviewPlugin {
+    fn build(&self, app: &mut App) {
+        app.init_resource::<AssetPreview>()
+            .add_systems(OnEnter(GameState::Game), asset_preview_setup)
+            .add_systems(
+                PreUpdate,
+                type_asset_name
+                    .after(InputSystem)
+                    .run_if(in_state(GameState::Game)),
+            )
+            .add_systems(Update, show_asset_preview.run_if(in_state(GameState::Game)))
+            .add_systems(OnExit(GameState::Game), despawn_all::<AssetPreviewPanel>);
+    }
+}
+
+#[derive(Resource, Default)]
+struct AssetPreview {
+    open: bool,
+    name: String,
+    submitted: Option<String>,
+    status: String,
+}
+
+#[derive(Component)]
+struct AssetPreviewPanel;
+
+#[derive(Component)]
+struct AssetPreviewImage;
+
+#[derive(Component)]
+struct AssetPreviewText;
+
+fn asset_preview_setup(mut commands: Commands) {
+    commands
+        .spawn((
+            Name::new("asset preview"),
+            NodeBundle {
+                style: Style {
+                    display: Display::None,
+                    position_type: PositionType::Absolute,
+                    width: Val::Percent(100.0),
+                    height: Val::Percent(100.0),
+                    flex_direction: FlexDirection::Column,
+                    align_items: AlignItems::Center,
+                    justify_content: JustifyContent::Center,
+                    row_gap: Val::Px(10.0),
+                    ..default()
+                },
+                background_color: Color::rgba(0.0, 0.0, 0.0, 0.8).into(),
+                ..default()
+            },
+            AssetPreviewPanel,
+        ))
+        .with_children(|parent| {
+            parent.spawn((
+                TextBundle::from_section(
+                    "",
+                    TextStyle {
+                        font_size: 20.0,
+                        color: Color::WHITE,
+                        ..default()
+                    },
+                ),
+                AssetPreviewText,
+            ));
+            parent.spawn((
+                ImageBundle {
+                    style: Style {
+                        display: Display::None,
+                        ..default()
+                    },
+                    ..default()
+                },
+                AssetPreviewImage,
+            ));
+        });
+}
+
+/// Collects the typed name while the preview is open, the keys are then cleared
+/// so the other hotkeys don't fire while typing.
+fn type_asset_name(
+    mut keys: ResMut<ButtonInput<KeyCode>>,
+    key_bindings: Res<KeyBindings>,
+    mut characters: EventReader<ReceivedCharacter>,
+    mut preview: ResMut<AssetPreview>,
+) {
+    if keys.just_pressed(key_bindings.toggle_asset_preview) {
+        preview.open = !preview.open;
+        characters.clear();
+        return;
+    }
+    if !preview.open {
+        characters.clear();
+        return;
+    }
+
+    for event in characters.read() {
+        let typed = event
+            .char
+            .chars()
+            .filter(|c| c.is_ascii_graphic() || *c == ' ');
+        preview.name.extend(typed);
+    }
+    if keys.just_pressed(KeyCode::Backspace) {
+        preview.name.pop();
+    }
+    if keys.just_pressed(KeyCode::Enter) {
+        preview.submitted = Some(preview.name.trim().to_lowercase());
+    }
+    keys.reset_all();
+}
+
+/// Decodes the submitted name as a sprite, with its palette and transparency,
+/// or else as a bitmap.
+fn show_asset_preview(
+    mut preview: ResMut<AssetPreview>,
+    settings: Res<WorldSettings>,
+    mut images: ResMut<Assets<Image>>,
+    mut panel: Query<&mut Style, (With<AssetPreviewPanel>, Without<AssetPreviewImage>)>,
+    mut image: Query<(&mut UiImage, &mut Style), With<AssetPreviewImage>>,
+    mut text: Query<&mut Text, With<AssetPreviewText>>,
+) {
+    if !preview.is_changed() {
+        return;
+    }
+    for mut style in &mut panel {
+        style.display = if preview.open {
+            Display::Flex
+        } else {
+            Display::None
+        };
+    }
+
+    if let Some(name) = preview.submitted.take() {
+        let decoded = settings
+            .lod_manager
+            .sprite(&name)
+            .or_else(|| settings.lod_manager.bitmap(&name));
+        let Ok((mut ui_image, mut style)) = image.get_single_mut() else {
+            return;
+        };
+        match decoded {
+            Some(decoded) => {
+                let (width, height) = (decoded.width() as f32, decoded.height() as f32);
+                let scale = (PREVIEW_SIZE / width.max(height)).min(4.0);
+                style.display = Display::Flex;
+                style.width = Val::Px(width * scale);
+                style.height = Val::Px(height * scale);
+                ui_image.texture = images.add(Image::from_dynamic(
+                    decoded,
+                    true,
+                    RenderAssetUsages::default(),
+                ));
+                preview.status = format!("{} ({}x{})", name, width, height);
+            }
+            None => {
+                style.display = Display::None;
+                preview.status = format!("{} not found", name);
+            }
+        }
+    }
+
+    let value = format!("Asset: {}_\n{}", preview.name, preview.status);
+    for mut text in &mut text {
+        text.sections[0].value = value.clone();
+    }
+}
@@ -1,16 +1,23 @@
 use bevy::diagnostic::FrameTimeDiagnosticsPlugin;
 use bevy::prelude::*;
-use bevy::window::{PresentMode, Window};
+use bevy::window::{PresentMode, PrimaryWindow, Window, WindowResolution};
 
-use crate::APP_NAME;
+use crate::{odm::LoadedOdm, StartupView, APP_NAME};
 
 pub struct BevyConfigPlugin;
 
 impl Plugin for BevyConfigPlugin {
     fn build(&self, app: &mut App) {
+        let resolution = app
+            .world
+            .get_resource::<StartupView>()
+            .and_then(|view| view.window_size())
+            .map(|size| WindowResolution::new(size.x, size.y))
+            .unwrap_or_default();
         let default_plugins = DefaultPlugins.set(WindowPlugin {
             primary_window: Some(Window {
                 title: APP_NAME.into(),
+                resolution,
                 present_mode: PresentMode::AutoVsync,
                 prevent_default_event_handling: false,
                 ..default()
@@ -19,6 +26,20 @@ impl Plugin for BevyConfigPlugin {
         });
 
         app.insert_resource(Msaa::Sample4)
-            .add_plugins((default_plugins, FrameTimeDiagnosticsPlugin));
+            .add_plugins((default_plugins, FrameTimeDiagnosticsPlugin))
+            .add_systems(
+                Update,
+                update_window_title.run_if(resource_exists_and_changed::<LoadedOdm>),
+            );
+    }
+}
+
+/// Shows the name of the loaded map next to the application name.
+fn update_window_title(
+    odm: Res<LoadedOdm>,
+    mut primary_window: Query<&mut Window, With<PrimaryWindow>>,
+) {
+    for mut window in &mut primary_window {
+        window.title = format!("{} - {}", APP_NAME, odm.0.file_name);
     }
 }
@@ -0,0 +1,172 @@
+use std::path::PathBuf;
+
+use bevy::prelude::*;
+use clap::Parser;
+
+use crate::{
+    odm::OdmName,
+    world::{CustomAtlas, WorldSettings},
+};
+
+/// Command line of the viewer.
+#[derive(Parser, Debug)]
+#[command(version, about)]
+pub struct Args {
+    /// Map to open, e.g. `oute3` or `oute3.odm`
+    #[arg(long)]
+    pub map: Option<String>,
+    /// Initial camera position, as `x,y,z` in viewer coordinates
+    #[arg(long, value_parser = parse_vec3, allow_hyphen_values = true)]
+    pub pos: Option<Vec3>,
+    /// Point the initial camera looks at, as `x,y,z` in viewer coordinates
+    #[arg(long, value_parser = parse_vec3, allow_hyphen_values = true)]
+    pub look: Option<Vec3>,
+    /// Initial size of the window, as `width x height` in logical pixels, e.g. `1600x900`
+    #[arg(long, value_parser = parse_window_size)]
+    pub window_size: Option<Vec2>,
+    /// Spawn at most this many decorations, the nearest to the center of the map
+    #[arg(long, value_name = "N")]
+    pub max_decorations: Option<usize>,
+    /// Texture the terrain with this pre-baked atlas instead of generating it, needs `--atlas-layout`
+    #[arg(long, value_name = "PNG", requires = "atlas_layout")]
+    pub atlas: Option<PathBuf>,
+    /// Json layout of the tiles in the `--atlas` image
+    #[arg(long, value_name = "JSON", requires = "atlas")]
+    pub atlas_layout: Option<PathBuf>,
+    /// Save the terrain atlas of every loaded map to this png
+    #[arg(long, value_name = "PNG")]
+    pub dump_atlas: Option<PathBuf>,
+    /// Show this map side by side with the current one, e.g. `oute2`
+    #[arg(long, value_name = "MAP")]
+    pub compare: Option<String>,
+    /// Print the buffer sizes of the indexed and non-indexed terrain meshes of a map and exit
+    #[arg(long, value_name = "MAP")]
+    pub mesh_stats: Option<String>,
+    /// Print a summary of a map (size, terrain, environment and objects) and exit
+    #[arg(long, value_name = "MAP")]
+    pub report: Option<String>,
+}
+
+impl Args {
+    pub fn startup_view(&self) -> Result<StartupView, String> {
+        let map = self.map.as_deref().map(OdmName::try_from).transpose()?;
+        let compare_map = self.compare.as_deref().map(OdmName::try_from).transpose()?;
+        Ok(StartupView {
+            map,
+            position: self.pos,
+            look_at: self.look,
+            window_size: self.window_size,
+            max_decorations: self.max_decorations,
+            custom_atlas: self.atlas.clone().zip(self.atlas_layout.clone()),
+            dump_atlas: self.dump_atlas.clone(),
+            compare_map,
+        })
+    }
+}
+
+/// Map and viewpoint requested on the command line, applied once when the game starts.
+#[derive(Resource, Default)]
+pub struct StartupView {
+    map: Option<OdmName>,
+    position: Option<Vec3>,
+    look_at: Option<Vec3>,
+    window_size: Option<Vec2>,
+    max_decorations: Option<usize>,
+    /// Atlas image and layout
+    custom_atlas: Option<(PathBuf, PathBuf)>,
+    dump_atlas: Option<PathBuf>,
+    compare_map: Option<OdmName>,
+}
+
+impl StartupView {
+    pub(crate) fn take_map(&mut self) -> Option<OdmName> {
+        self.map.take()
+    }
+
+    pub(crate) fn window_size(&self) -> Option<Vec2> {
+        self.window_size
+    }
+
+    pub(crate) fn compare_map(&self) -> Option<OdmName> {
+        self.compare_map.clone()
+    }
+
+    /// Overrides the render settings given on the command line.
+    pub(crate) fn apply_settings(&self, settings: &mut WorldSettings) {
+        if self.max_decorations.is_some() {
+            settings.max_decorations = self.max_decorations;
+        }
+        if let Some((image, layout)) = &self.custom_atlas {
+            settings.custom_atlas = Some(CustomAtlas {
+                image: image.clone(),
+                layout: layout.clone(),
+            });
+        }
+        if self.dump_atlas.is_some() {
+            settings.dump_atlas = self.dump_atlas.clone();
+        }
+    }
+
+    /// Moves the camera to the requested viewpoint, only the first time it's called.
+    pub(crate) fn apply_camera(&mut self, transform: &mut Transform) {
+        if let Some(position) = self.position.take() {
+            transform.translation = position;
+        }
+        if let Some(look_at) = self.look_at.take() {
+            transform.look_at(look_at, Vec3::Y);
+        }
+    }
+}
+
+fn parse_vec3(value: &str) -> Result<Vec3, String> {
+    let values = value
+        .split(',')
+        .map(|v| v.trim().parse::<f32>().map_err(|e| e.to_string()))
+        .collect::<Result<Vec<f32>, String>>()?;
+    match values.as_slice() {
+        [x, y, z] => Ok(Vec3::new(*x, *y, *z)),
+        _ => Err(format!("expected x,y,z but got {:?}", value)),
+    }
+}
+
+fn parse_window_size(value: &str) -> Result<Vec2, String> {
+    let (width, height) = value
+        .split_once('x')
+        .ok_or(format!("expected WIDTHxHEIGHT but got {:?}", value))?;
+    let parse = |v: &str| match v.trim().parse::<f32>() {
+        Ok(v) if v > 0.0 => Ok(v),
+        Ok(_) => Err(format!("window size must be positive, got {:?}", value)),
+        Err(e) => Err(e.to_string()),
+    };
+    Ok(Vec2::new(parse(width)?, parse(height)?))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn atlas_args_work() {
+        assert!(Args::try_parse_from(["map_viewer", "--atlas", "atlas.png"]).is_err());
+        assert!(Args::try_parse_from(["map_viewer", "--atlas-layout", "atlas.json"]).is_err());
+
+        let args = Args::try_parse_from([
+            "map_viewer",
+            "--atlas",
+            "atlas.png",
+            "--atlas-layout",
+            "atlas.json",
+        ])
+        .unwrap();
+        let startup_view = args.startup_view().unwrap();
+        assert_eq!(
+            startup_view.custom_atlas,
+            Some((PathBuf::from("atlas.png"), PathBuf::from("atlas.json")))
+        );
+
+        let args = Args::try_parse_from(["map_viewer", "--dump-atlas", "dump.png"]).unwrap();
+        let startup_view = args.startup_view().unwrap();
+        assert_eq!(startup_view.dump_atlas, Some(PathBuf::from("dump.png")));
+        assert_eq!(startup_view.custom_atlas, None);
+    }
+}
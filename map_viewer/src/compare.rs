@@ -0,0 +1,206 @@
+use bevy::{
+    prelude::*,
+    render::{camera::Viewport, view::RenderLayers},
+    window::PrimaryWindow,
+};
+use lod::odm::{ODM_SIZE, ODM_TILE_SCALE};
+
+use crate::{
+    cli::StartupView,
+    despawn_all,
+    odm::{spawn_odm, CurrentMap, OdmBundle, OdmName, TerrainMode},
+    player::FlyCam,
+    world::WorldSettings,
+    GameState,
+};
+
+/// Where the compared map is placed, far enough to be hidden by the fog of the other view
+const COMPARE_OFFSET: Vec3 = Vec3::new(ODM_TILE_SCALE * ODM_SIZE as f32 * 2.0, 0.0, 0.0);
+
+/// Each map is only seen by its own camera, the fog doesn't hide the other map in the
+/// survey and isometric modes. The sky and the lights stay on the default layer of both.
+const CURRENT_MAP_LAYERS: RenderLayers = RenderLayers::layer(1);
+const COMPARE_MAP_LAYERS: RenderLayers = RenderLayers::layer(2);
+
+type MapsQuery<'w, 's> = Query<'w, 's, Entity, Or<(With<CompareMap>, With<CompareCam>)>>;
+
+type FlyCamQuery<'w, 's> =
+    Query<'w, 's, (&'static Transform, &'static mut Camera), (With<FlyCam>, Without<CompareCam>)>;
+
+type CompareCamQuery<'w, 's> = Query<
+    'w,
+    's,
+    (&'static mut Transform, &'static mut Camera),
+    (With<CompareCam>, Without<FlyCam>),
+>;
+
+pub struct ComparePlugin;
+
+impl Plugin for ComparePlugin {
+    fn build(&self, app: &mut App) {
+        let map = app
+            .world
+            .get_resource::<StartupView>()
+            .and_then(StartupView::compare_map);
+        app.insert_resource(CompareSettings { map, changed: true })
+            .add_systems(OnEnter(GameState::Game), reload_compare_map)
+            .add_systems(
+                Update,
+                (change_compare_map, assign_map_layers, sync_compare_camera)
+                    .chain()
+                    .run_if(in_state(GameState::Game)),
+            )
+            .add_systems(
+                OnExit(GameState::Game),
+                (despawn_all::<CompareMap>, despawn_all::<CompareCam>),
+            );
+    }
+}
+
+/// A second map shown side by side with the current one, both views
+/// follow the same camera movements.
+#[derive(Resource)]
+pub(crate) struct CompareSettings {
+    pub map: Option<OdmName>,
+    pub changed: bool,
+}
+
+#[derive(Component)]
+struct CompareMap;
+
+#[derive(Component)]
+struct CompareCam;
+
+fn reload_compare_map(mut compare: ResMut<CompareSettings>) {
+    compare.changed = true;
+}
+
+#[allow(clippy::too_many_arguments)]
+fn change_compare_map(
+    mut commands: Commands,
+    mut compare: ResMut<CompareSettings>,
+    world_settings: Res<WorldSettings>,
+    mut images: ResMut<Assets<Image>>,
+    mut meshes: ResMut<Assets<Mesh>>,
+    mut materials: ResMut<Assets<StandardMaterial>>,
+    terrain_mode: Res<TerrainMode>,
+    maps: MapsQuery,
+    fly_cam: Query<(&Projection, &FogSettings), With<FlyCam>>,
+) {
+    if !compare.changed {
+        return;
+    }
+    let Ok((projection, fog)) = fly_cam.get_single() else {
+        return;
+    };
+    compare.changed = false;
+
+    for e in &maps {
+        commands.entity(e).despawn_recursive();
+    }
+
+    let Some(name) = &compare.map else {
+        return;
+    };
+    let odm = match OdmBundle::new(
+        &world_settings.lod_manager,
+        name.to_string().as_str(),
+        &world_settings,
+    ) {
+        Ok(odm) => odm,
+        Err(e) => {
+            error!("Failed to load the map to compare {}: {}", name, e);
+            return;
+        }
+    };
+
+    let (entity, _) = spawn_odm(
+        &mut commands,
+        odm,
+        Transform::from_translation(COMPARE_OFFSET),
+        &mut images,
+        &mut meshes,
+        &mut materials,
+        *terrain_mode,
+    );
+    commands.entity(entity).insert(CompareMap);
+
+    commands.spawn((
+        Name::new("compare_camera"),
+        Camera3dBundle {
+            camera: Camera {
+                order: 1,
+                ..default()
+            },
+            projection: projection.clone(),
+            ..default()
+        },
+        fog.clone(),
+        COMPARE_MAP_LAYERS.with(0),
+        CompareCam,
+    ));
+}
+
+/// Puts the newly spawned maps and all their children on the layers of their camera.
+fn assign_map_layers(
+    mut commands: Commands,
+    current_maps: Query<Entity, Added<CurrentMap>>,
+    compare_maps: Query<Entity, Added<CompareMap>>,
+    children: Query<&Children>,
+    fly_cam: Query<Entity, (With<FlyCam>, Without<RenderLayers>)>,
+) {
+    for camera in &fly_cam {
+        commands.entity(camera).insert(CURRENT_MAP_LAYERS.with(0));
+    }
+    let maps = current_maps
+        .iter()
+        .map(|map| (map, CURRENT_MAP_LAYERS))
+        .chain(compare_maps.iter().map(|map| (map, COMPARE_MAP_LAYERS)));
+    for (map, layers) in maps {
+        for entity in std::iter::once(map).chain(children.iter_descendants(map)) {
+            commands.entity(entity).insert(layers);
+        }
+    }
+}
+
+/// Splits the window between the two cameras and moves the second one along with the first
+fn sync_compare_camera(
+    primary_window: Query<&Window, With<PrimaryWindow>>,
+    mut fly_cam: FlyCamQuery,
+    mut compare_cam: CompareCamQuery,
+) {
+    let (Ok(window), Ok((transform, mut camera))) =
+        (primary_window.get_single(), fly_cam.get_single_mut())
+    else {
+        return;
+    };
+
+    let Ok((mut compare_transform, mut compare_camera)) = compare_cam.get_single_mut() else {
+        if camera.viewport.is_some() {
+            camera.viewport = None;
+        }
+        return;
+    };
+
+    *compare_transform = transform.with_translation(transform.translation + COMPARE_OFFSET);
+
+    let (width, height) = (window.physical_width(), window.physical_height());
+    let half = UVec2::new(width / 2, height);
+    // a minimized window has no room for the viewports
+    if half.x == 0 || half.y == 0 {
+        return;
+    }
+    let viewport_size = |camera: &Camera| camera.viewport.as_ref().map(|v| v.physical_size);
+    if viewport_size(&camera) != Some(half) || viewport_size(&compare_camera) != Some(half) {
+        camera.viewport = Some(Viewport {
+            physical_position: UVec2::ZERO,
+            physical_size: half,
+            ..default()
+        });
+        compare_camera.viewport = Some(Viewport {
+            physical_position: UVec2::new(width / 2, 0),
+            physical_size: half,
+            ..default()
+        });
+    }
+}
@@ -1,17 +1,25 @@
 use bevy::{
     diagnostic::{DiagnosticsStore, FrameTimeDiagnosticsPlugin, LogDiagnosticsPlugin},
+    ecs::reflect::ReflectResource,
     input::{common_conditions::input_toggle_active, ButtonInput},
     pbr::wireframe::{WireframeConfig, WireframePlugin},
     prelude::{
-        default, in_state, App, Color, Commands, Component, IntoSystemConfigs, KeyCode, OnEnter,
-        Plugin, Query, Res, ResMut, Resource, TextBundle, Transform, Update, Vec3, With,
+        default, in_state, App, Assets, Color, Commands, Component, DetectChanges, Gizmos,
+        GlobalTransform, Handle, IntoSystemConfigs, KeyCode, Mesh, OnEnter, Plugin, Query, Ref,
+        Res, ResMut, Resource, StandardMaterial, TextBundle, Transform, Update, Vec3, With,
     },
+    reflect::Reflect,
     text::{Text, TextSection, TextStyle},
 };
 use bevy_inspector_egui::quick::WorldInspectorPlugin;
 use lod::odm::{ODM_PLAY_SIZE, ODM_TILE_SCALE};
 
-use crate::{player::FlyCam, GameState};
+use crate::{
+    odm::{DecorationBounds, TerrainLines, TerrainMesh},
+    player::FlyCam,
+    world::WorldSettings,
+    GameState,
+};
 
 /// Keeps track of mouse motion events, pitch, and yaw
 #[derive(Resource)]
@@ -27,11 +35,76 @@ impl Default for DevConfig {
     }
 }
 
+/// Appearance of the wireframe overlay, editable at runtime from the inspector.
+/// Line thickness is not configurable since wgpu only draws one pixel wide lines.
+#[derive(Resource, Reflect)]
+#[reflect(Resource)]
+pub struct WireframeSettings {
+    pub color: Color,
+}
+
+impl Default for WireframeSettings {
+    fn default() -> Self {
+        Self {
+            color: Color::WHITE,
+        }
+    }
+}
+
+/// Debug lines drawn along the terrain normals, one every `stride` vertices.
+#[derive(Resource, Reflect)]
+#[reflect(Resource)]
+pub struct NormalLinesSettings {
+    pub enabled: bool,
+    pub stride: usize,
+    pub length: f32,
+    pub color: Color,
+}
+
+impl Default for NormalLinesSettings {
+    fn default() -> Self {
+        Self {
+            enabled: false,
+            stride: 24,
+            length: 256.0,
+            color: Color::FUCHSIA,
+        }
+    }
+}
+
+/// Debug boxes around the decorations, sized from their declist radius and height.
+#[derive(Resource, Reflect)]
+#[reflect(Resource)]
+pub struct DecorationBoxesSettings {
+    pub enabled: bool,
+    pub color: Color,
+}
+
+impl Default for DecorationBoxesSettings {
+    fn default() -> Self {
+        Self {
+            enabled: false,
+            color: Color::LIME_GREEN,
+        }
+    }
+}
+
 /// Key configuration
 #[derive(Resource)]
 pub struct KeyBindings {
     pub toggle_wireframe: KeyCode,
     pub toggle_play_area: KeyCode,
+    pub export_scene: KeyCode,
+    pub toggle_tile_borders: KeyCode,
+    pub toggle_minimap_mode: KeyCode,
+    pub toggle_normal_lines: KeyCode,
+    pub toggle_measure: KeyCode,
+    pub toggle_asset_preview: KeyCode,
+    pub toggle_decoration_boxes: KeyCode,
+    pub cycle_terrain_mode: KeyCode,
+    pub cycle_height_color_map: KeyCode,
+    pub toggle_smooth_terrain: KeyCode,
+    pub cycle_tessellation: KeyCode,
 }
 
 impl Default for KeyBindings {
@@ -39,6 +112,17 @@ impl Default for KeyBindings {
         Self {
             toggle_wireframe: KeyCode::BracketRight,
             toggle_play_area: KeyCode::BracketLeft,
+            export_scene: KeyCode::F12,
+            toggle_tile_borders: KeyCode::Backslash,
+            toggle_minimap_mode: KeyCode::KeyN,
+            toggle_normal_lines: KeyCode::KeyB,
+            toggle_measure: KeyCode::KeyT,
+            toggle_asset_preview: KeyCode::F2,
+            toggle_decoration_boxes: KeyCode::KeyG,
+            cycle_terrain_mode: KeyCode::KeyM,
+            cycle_height_color_map: KeyCode::KeyC,
+            toggle_smooth_terrain: KeyCode::KeyX,
+            cycle_tessellation: KeyCode::KeyV,
         }
     }
 }
@@ -128,11 +212,105 @@ fn dev_input(
     key_bindings: Res<KeyBindings>,
     mut dev_config: ResMut<DevConfig>,
     mut wireframe_config: ResMut<WireframeConfig>,
+    mut world_settings: ResMut<WorldSettings>,
+    mut normal_lines: ResMut<NormalLinesSettings>,
+    mut decoration_boxes: ResMut<DecorationBoxesSettings>,
 ) {
     if keys.just_pressed(key_bindings.toggle_wireframe) {
         dev_config.show_play_area = !dev_config.show_play_area;
     } else if keys.just_pressed(key_bindings.toggle_play_area) {
         wireframe_config.global = !wireframe_config.global;
+    } else if keys.just_pressed(key_bindings.toggle_tile_borders) {
+        world_settings.show_tile_borders = !world_settings.show_tile_borders;
+        world_settings.odm_changed = true;
+    } else if keys.just_pressed(key_bindings.toggle_normal_lines) {
+        normal_lines.enabled = !normal_lines.enabled;
+    } else if keys.just_pressed(key_bindings.toggle_decoration_boxes) {
+        decoration_boxes.enabled = !decoration_boxes.enabled;
+    }
+}
+
+fn draw_normal_lines(
+    settings: Res<NormalLinesSettings>,
+    mut gizmos: Gizmos,
+    meshes: Res<Assets<Mesh>>,
+    terrain: Query<(&Handle<Mesh>, &GlobalTransform), With<TerrainMesh>>,
+) {
+    if !settings.enabled {
+        return;
+    }
+    for (handle, transform) in &terrain {
+        let Some(mesh) = meshes.get(handle) else {
+            continue;
+        };
+        for (position, normal) in sampled_normals(mesh, settings.stride) {
+            let start = transform.transform_point(position);
+            let direction = transform.affine().transform_vector3(normal);
+            gizmos.line(
+                start,
+                start + direction.normalize_or_zero() * settings.length,
+                settings.color,
+            );
+        }
+    }
+}
+
+/// Positions and normals of one vertex every `stride`, in mesh space.
+/// The terrain meshes are read back after their upload, they are created with
+/// [`MAP_ASSET_USAGES`](crate::odm::MAP_ASSET_USAGES) to stay in the main world.
+fn sampled_normals(mesh: &Mesh, stride: usize) -> impl Iterator<Item = (Vec3, Vec3)> + '_ {
+    let positions = mesh
+        .attribute(Mesh::ATTRIBUTE_POSITION)
+        .and_then(|p| p.as_float3())
+        .unwrap_or_default();
+    let normals = mesh
+        .attribute(Mesh::ATTRIBUTE_NORMAL)
+        .and_then(|n| n.as_float3())
+        .unwrap_or_default();
+    positions
+        .iter()
+        .zip(normals)
+        .step_by(stride.max(1))
+        .map(|(position, normal)| (Vec3::from(*position), Vec3::from(*normal)))
+}
+
+fn draw_decoration_boxes(
+    settings: Res<DecorationBoxesSettings>,
+    mut gizmos: Gizmos,
+    maps: Query<(&DecorationBounds, &GlobalTransform)>,
+) {
+    if !settings.enabled {
+        return;
+    }
+    for (bounds, transform) in &maps {
+        for &(position, radius, height) in &bounds.0 {
+            let center = position + Vec3::Y * height / 2.0;
+            let size = Vec3::new(radius * 2.0, height, radius * 2.0);
+            let transform =
+                Transform::from(*transform) * Transform::from_translation(center).with_scale(size);
+            gizmos.cuboid(transform, settings.color);
+        }
+    }
+}
+
+/// The color is applied to the wireframe overlay and to the terrain lines, including
+/// the lines of a map loaded after the color was changed.
+fn update_wireframe_color(
+    settings: Res<WireframeSettings>,
+    mut wireframe_config: ResMut<WireframeConfig>,
+    lines: Query<(&Handle<StandardMaterial>, Ref<TerrainLines>)>,
+    mut materials: ResMut<Assets<StandardMaterial>>,
+) {
+    if settings.is_changed() {
+        wireframe_config.default_color = settings.color;
+    }
+    for (handle, lines) in &lines {
+        if !settings.is_changed() && !lines.is_added() {
+            continue;
+        }
+        if let Some(material) = materials.get_mut(handle) {
+            material.base_color = settings.color;
+        }
     }
 }
 
@@ -167,6 +345,12 @@ impl Plugin for DevPlugin {
     fn build(&self, app: &mut App) {
         app.init_resource::<KeyBindings>()
             .insert_resource(DevConfig::default())
+            .init_resource::<WireframeSettings>()
+            .register_type::<WireframeSettings>()
+            .init_resource::<NormalLinesSettings>()
+            .register_type::<NormalLinesSettings>()
+            .init_resource::<DecorationBoxesSettings>()
+            .register_type::<DecorationBoxesSettings>()
             .add_plugins((
                 WireframePlugin,
                 LogDiagnosticsPlugin::default(),
@@ -174,10 +358,54 @@ impl Plugin for DevPlugin {
             ))
             .add_systems(
                 Update,
-                (dev_input, update_fps_text, update_position_text)
+                (
+                    dev_input,
+                    update_fps_text,
+                    update_position_text,
+                    draw_normal_lines,
+                    draw_decoration_boxes,
+                )
                     .run_if(in_state(GameState::Game)),
             )
+            .add_systems(Update, update_wireframe_color)
             .add_systems(OnEnter(GameState::Game), dev_setup);
         //.add_systems(OnExit(GameState::Game), despawn_all::<DevStuff>);
     }
 }
+
+#[cfg(test)]
+mod tests {
+    use bevy::render::render_asset::RenderAssetUsages;
+    use lod::{
+        dtile::TileTable,
+        odm::{Odm, OdmData},
+    };
+
+    use super::*;
+    use crate::odm::{OdmBundle, TERRAIN_CHUNK_SIZE};
+
+    #[test]
+    fn sampled_normals_works() {
+        // flat 4x4 odm without models, billboards, decorations or spawn points
+        let mut data = vec![0; 176 + 3 * 16];
+        data.extend(0u32.to_le_bytes().repeat(3));
+        data.extend(vec![0; 16 * 4]);
+        data.extend(0u32.to_le_bytes());
+        let map = Odm::try_from_with_size(&data, (4, 4)).unwrap();
+        let tile_table = TileTable::new(std::array::from_fn(|_| "grastyl".to_string()));
+        let odm_data = OdmData::new(&map, &tile_table);
+
+        for smooth in [true, false] {
+            let chunks =
+                OdmBundle::generate_terrain_chunks(&odm_data, None, smooth, TERRAIN_CHUNK_SIZE);
+            for (_, mesh) in &chunks {
+                assert!(mesh.asset_usage.contains(RenderAssetUsages::MAIN_WORLD));
+                let normals: Vec<(Vec3, Vec3)> = sampled_normals(mesh, 5).collect();
+                assert_eq!(normals.len(), mesh.count_vertices().div_ceil(5));
+                assert!(normals
+                    .iter()
+                    .all(|(_, normal)| normal.abs_diff_eq(Vec3::Y, 1e-6)));
+            }
+        }
+    }
+}
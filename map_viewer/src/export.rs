@@ -0,0 +1,513 @@
+use std::{collections::HashMap, error::Error, fs, path::Path};
+
+use bevy::{
+    prelude::*,
+    render::{
+        mesh::{Indices, VertexAttributeValues},
+        render_resource::PrimitiveTopology,
+    },
+};
+use serde_json::{json, Value};
+
+use crate::{dev::KeyBindings, odm::CurrentMap, GameState};
+
+const EXPORT_PATH: &str = "./target/export";
+const EXPORT_NAME: &str = "scene";
+
+const COMPONENT_FLOAT: u32 = 5126;
+const COMPONENT_UNSIGNED_INT: u32 = 5125;
+const TARGET_ARRAY_BUFFER: u32 = 34962;
+const TARGET_ELEMENT_ARRAY_BUFFER: u32 = 34963;
+const MODE_LINES: u32 = 1;
+
+/// Exports the loaded map (terrain and models) as a glTF scene.
+pub struct ExportPlugin;
+
+impl Plugin for ExportPlugin {
+    fn build(&self, app: &mut App) {
+        app.add_systems(Update, export_scene.run_if(in_state(GameState::Game)));
+    }
+}
+
+type NodeQuery<'w, 's> = Query<
+    'w,
+    's,
+    (
+        Option<&'static Name>,
+        &'static Transform,
+        Option<&'static Handle<Mesh>>,
+        Option<&'static Handle<StandardMaterial>>,
+        Option<&'static Children>,
+    ),
+>;
+
+fn export_scene(
+    keys: Res<ButtonInput<KeyCode>>,
+    key_bindings: Res<KeyBindings>,
+    roots: Query<Entity, With<CurrentMap>>,
+    nodes: NodeQuery,
+    meshes: Res<Assets<Mesh>>,
+    materials: Res<Assets<StandardMaterial>>,
+    images: Res<Assets<Image>>,
+) {
+    if !keys.just_pressed(key_bindings.export_scene) {
+        return;
+    }
+
+    let mut builder = GltfBuilder::new(&meshes, &materials, &images);
+    let scene_nodes: Vec<usize> = roots
+        .iter()
+        .map(|root| builder.push_node(root, &nodes))
+        .collect();
+
+    match builder.write(Path::new(EXPORT_PATH), &scene_nodes) {
+        Ok(()) => info!("Scene exported to {}/{}.gltf", EXPORT_PATH, EXPORT_NAME),
+        Err(e) => error!("Failed to export scene: {}", e),
+    }
+}
+
+struct GltfBuilder<'a> {
+    meshes: &'a Assets<Mesh>,
+    materials: &'a Assets<StandardMaterial>,
+    images: &'a Assets<Image>,
+    buffer: Vec<u8>,
+    buffer_views: Vec<Value>,
+    accessors: Vec<Value>,
+    gltf_meshes: Vec<Value>,
+    gltf_materials: Vec<Value>,
+    gltf_textures: Vec<Value>,
+    gltf_images: Vec<ImageEntry>,
+    gltf_nodes: Vec<Value>,
+    mesh_ids: HashMap<AssetId<Mesh>, Option<usize>>,
+    material_ids: HashMap<AssetId<StandardMaterial>, usize>,
+    texture_ids: HashMap<AssetId<Image>, Option<usize>>,
+}
+
+struct ImageEntry {
+    uri: String,
+    image: Image,
+}
+
+impl<'a> GltfBuilder<'a> {
+    fn new(
+        meshes: &'a Assets<Mesh>,
+        materials: &'a Assets<StandardMaterial>,
+        images: &'a Assets<Image>,
+    ) -> Self {
+        Self {
+            meshes,
+            materials,
+            images,
+            buffer: Vec::new(),
+            buffer_views: Vec::new(),
+            accessors: Vec::new(),
+            gltf_meshes: Vec::new(),
+            gltf_materials: Vec::new(),
+            gltf_textures: Vec::new(),
+            gltf_images: Vec::new(),
+            gltf_nodes: Vec::new(),
+            mesh_ids: HashMap::new(),
+            material_ids: HashMap::new(),
+            texture_ids: HashMap::new(),
+        }
+    }
+
+    fn push_node(&mut self, entity: Entity, nodes: &NodeQuery) -> usize {
+        let index = self.gltf_nodes.len();
+        self.gltf_nodes.push(Value::Null);
+
+        let Ok((name, transform, mesh, material, children)) = nodes.get(entity) else {
+            self.gltf_nodes[index] = json!({});
+            return index;
+        };
+
+        let mut node = json!({
+            "name": name.map(|n| n.as_str()).unwrap_or("node"),
+            "translation": transform.translation.to_array(),
+            "rotation": transform.rotation.to_array(),
+            "scale": transform.scale.to_array(),
+        });
+
+        if let Some(mesh) = mesh {
+            let material = material.map(|m| self.push_material(m));
+            if let Some(mesh) = self.push_mesh(mesh, material) {
+                node["mesh"] = json!(mesh);
+            }
+        }
+
+        if let Some(children) = children {
+            let children: Vec<usize> = children
+                .iter()
+                .map(|child| self.push_node(*child, nodes))
+                .collect();
+            node["children"] = json!(children);
+        }
+
+        self.gltf_nodes[index] = node;
+        index
+    }
+
+    /// Decorations are already flat quads textured with their sprite, they are exported
+    /// like the other meshes and their material keeps the alpha mask cutting out the sprite.
+    fn push_mesh(&mut self, handle: &Handle<Mesh>, material: Option<usize>) -> Option<usize> {
+        if let Some(id) = self.mesh_ids.get(&handle.id()) {
+            return *id;
+        }
+        let id = self.meshes.get(handle).and_then(|mesh| {
+            let positions = mesh.attribute(Mesh::ATTRIBUTE_POSITION)?.as_float3()?;
+            let mut attributes = json!({
+                "POSITION": self.push_vec3_accessor(positions, true),
+            });
+            if let Some(normals) = mesh
+                .attribute(Mesh::ATTRIBUTE_NORMAL)
+                .and_then(|n| n.as_float3())
+            {
+                attributes["NORMAL"] = json!(self.push_vec3_accessor(normals, false));
+            }
+            if let Some(VertexAttributeValues::Float32x2(uvs)) =
+                mesh.attribute(Mesh::ATTRIBUTE_UV_0)
+            {
+                attributes["TEXCOORD_0"] = json!(self.push_vec2_accessor(uvs));
+            }
+
+            let mut primitive = json!({ "attributes": attributes });
+            // triangles are the default mode, the terrain lines are the only other topology
+            if mesh.primitive_topology() == PrimitiveTopology::LineList {
+                primitive["mode"] = json!(MODE_LINES);
+            }
+            if let Some(indices) = mesh.indices() {
+                primitive["indices"] = json!(self.push_indices(indices));
+            }
+            if let Some(material) = material {
+                primitive["material"] = json!(material);
+            }
+
+            self.gltf_meshes.push(json!({ "primitives": [primitive] }));
+            Some(self.gltf_meshes.len() - 1)
+        });
+        self.mesh_ids.insert(handle.id(), id);
+        id
+    }
+
+    fn push_material(&mut self, handle: &Handle<StandardMaterial>) -> usize {
+        if let Some(id) = self.material_ids.get(&handle.id()) {
+            return *id;
+        }
+        let (color, texture, double_sided, alpha_mode) = match self.materials.get(handle) {
+            Some(material) => (
+                material.base_color.as_rgba_f32(),
+                material.base_color_texture.clone(),
+                material.cull_mode.is_none(),
+                material.alpha_mode,
+            ),
+            None => ([1.0, 1.0, 1.0, 1.0], None, false, AlphaMode::Opaque),
+        };
+
+        let mut pbr = json!({
+            "baseColorFactor": color,
+            "metallicFactor": 0,
+            "roughnessFactor": 1,
+        });
+        if let Some(texture) = texture.and_then(|t| self.push_texture(&t)) {
+            pbr["baseColorTexture"] = json!({ "index": texture });
+        }
+
+        let mut material = json!({
+            "pbrMetallicRoughness": pbr,
+            "doubleSided": double_sided,
+        });
+        match alpha_mode {
+            AlphaMode::Opaque => {}
+            AlphaMode::Mask(cutoff) => {
+                material["alphaMode"] = json!("MASK");
+                material["alphaCutoff"] = json!(cutoff);
+            }
+            _ => material["alphaMode"] = json!("BLEND"),
+        }
+
+        self.gltf_materials.push(material);
+        let id = self.gltf_materials.len() - 1;
+        self.material_ids.insert(handle.id(), id);
+        id
+    }
+
+    fn push_texture(&mut self, handle: &Handle<Image>) -> Option<usize> {
+        if let Some(id) = self.texture_ids.get(&handle.id()) {
+            return *id;
+        }
+        let id = self.images.get(handle).map(|image| {
+            let uri = format!("{}_texture_{}.png", EXPORT_NAME, self.gltf_images.len());
+            self.gltf_images.push(ImageEntry {
+                uri,
+                image: image.clone(),
+            });
+            self.gltf_textures
+                .push(json!({ "source": self.gltf_images.len() - 1 }));
+            self.gltf_textures.len() - 1
+        });
+        self.texture_ids.insert(handle.id(), id);
+        id
+    }
+
+    fn push_vec3_accessor(&mut self, values: &[[f32; 3]], with_bounds: bool) -> usize {
+        let bytes: Vec<u8> = values
+            .iter()
+            .flat_map(|v| v.iter().flat_map(|c| c.to_le_bytes()))
+            .collect();
+        let view = self.push_buffer_view(&bytes, TARGET_ARRAY_BUFFER);
+        let mut accessor = json!({
+            "bufferView": view,
+            "componentType": COMPONENT_FLOAT,
+            "count": values.len(),
+            "type": "VEC3",
+        });
+        if with_bounds {
+            let mut min = [f32::MAX; 3];
+            let mut max = [f32::MIN; 3];
+            for v in values {
+                for i in 0..3 {
+                    min[i] = min[i].min(v[i]);
+                    max[i] = max[i].max(v[i]);
+                }
+            }
+            accessor["min"] = json!(min);
+            accessor["max"] = json!(max);
+        }
+        self.push_accessor(accessor)
+    }
+
+    fn push_vec2_accessor(&mut self, values: &[[f32; 2]]) -> usize {
+        let bytes: Vec<u8> = values
+            .iter()
+            .flat_map(|v| v.iter().flat_map(|c| c.to_le_bytes()))
+            .collect();
+        let view = self.push_buffer_view(&bytes, TARGET_ARRAY_BUFFER);
+        self.push_accessor(json!({
+            "bufferView": view,
+            "componentType": COMPONENT_FLOAT,
+            "count": values.len(),
+            "type": "VEC2",
+        }))
+    }
+
+    fn push_indices(&mut self, indices: &Indices) -> usize {
+        let bytes: Vec<u8> = indices
+            .iter()
+            .flat_map(|i| (i as u32).to_le_bytes())
+            .collect();
+        let view = self.push_buffer_view(&bytes, TARGET_ELEMENT_ARRAY_BUFFER);
+        self.push_accessor(json!({
+            "bufferView": view,
+            "componentType": COMPONENT_UNSIGNED_INT,
+            "count": indices.len(),
+            "type": "SCALAR",
+        }))
+    }
+
+    fn push_buffer_view(&mut self, bytes: &[u8], target: u32) -> usize {
+        while !self.buffer.len().is_multiple_of(4) {
+            self.buffer.push(0);
+        }
+        self.buffer_views.push(json!({
+            "buffer": 0,
+            "byteOffset": self.buffer.len(),
+            "byteLength": bytes.len(),
+            "target": target,
+        }));
+        self.buffer.extend_from_slice(bytes);
+        self.buffer_views.len() - 1
+    }
+
+    fn push_accessor(&mut self, accessor: Value) -> usize {
+        self.accessors.push(accessor);
+        self.accessors.len() - 1
+    }
+
+    /// The glTF document, referencing the buffer and the images saved by `write`.
+    fn to_json(&self, scene_nodes: &[usize]) -> Value {
+        let images: Vec<Value> = self
+            .gltf_images
+            .iter()
+            .map(|entry| json!({ "uri": entry.uri }))
+            .collect();
+        json!({
+            "asset": { "version": "2.0", "generator": crate::APP_NAME },
+            "scene": 0,
+            "scenes": [{ "nodes": scene_nodes }],
+            "nodes": self.gltf_nodes,
+            "meshes": self.gltf_meshes,
+            "materials": self.gltf_materials,
+            "textures": self.gltf_textures,
+            "images": images,
+            "accessors": self.accessors,
+            "bufferViews": self.buffer_views,
+            "buffers": [{
+                "uri": format!("{}.bin", EXPORT_NAME),
+                "byteLength": self.buffer.len(),
+            }],
+        })
+    }
+
+    fn write(&self, path: &Path, scene_nodes: &[usize]) -> Result<(), Box<dyn Error>> {
+        fs::create_dir_all(path)?;
+        fs::write(path.join(format!("{}.bin", EXPORT_NAME)), &self.buffer)?;
+
+        for entry in &self.gltf_images {
+            entry
+                .image
+                .clone()
+                .try_into_dynamic()?
+                .to_rgba8()
+                .save(path.join(&entry.uri))?;
+        }
+
+        let json = serde_json::to_string(&self.to_json(scene_nodes))?;
+        fs::write(path.join(format!("{}.gltf", EXPORT_NAME)), json)?;
+        Ok(())
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use bevy::ecs::system::RunSystemOnce;
+
+    use super::*;
+    use crate::odm::{decoration_material, spawn_odm, DecorationMeshData, OdmBundle, TerrainMode};
+    use bevy::render::{
+        render_asset::RenderAssetUsages,
+        render_resource::{Extent3d, TextureDimension, TextureFormat},
+    };
+    use lod::{dtile::TileTable, odm::Odm};
+
+    fn export_json(world: &mut World) -> Value {
+        let json = world.run_system_once(
+            |roots: Query<Entity, With<CurrentMap>>,
+             nodes: NodeQuery,
+             meshes: Res<Assets<Mesh>>,
+             materials: Res<Assets<StandardMaterial>>,
+             images: Res<Assets<Image>>| {
+                let mut builder = GltfBuilder::new(&meshes, &materials, &images);
+                let scene_nodes: Vec<usize> = roots
+                    .iter()
+                    .map(|root| builder.push_node(root, &nodes))
+                    .collect();
+                serde_json::to_string(&builder.to_json(&scene_nodes)).unwrap()
+            },
+        );
+        serde_json::from_str(&json).unwrap()
+    }
+
+    #[test]
+    fn gltf_json_works() {
+        let mut world = World::new();
+        let mut meshes = Assets::<Mesh>::default();
+        let mut materials = Assets::<StandardMaterial>::default();
+        let mut decoration = DecorationMeshData::default();
+        decoration.push(Vec3::ZERO, (64.0, 128.0), [0.0, 0.0, 1.0, 1.0]);
+        let mesh = meshes.add(Mesh::from(decoration));
+        let material = materials.add(decoration_material(Handle::default()));
+        world.insert_resource(meshes);
+        world.insert_resource(materials);
+        world.insert_resource(Assets::<Image>::default());
+
+        world
+            .spawn((
+                Name::new("map \"e3\" \\ odm"),
+                Transform::from_xyz(1.0, 2.0, 3.0),
+                CurrentMap,
+            ))
+            .with_children(|parent| {
+                parent.spawn((Name::new("decoration"), Transform::IDENTITY, mesh, material));
+            });
+
+        let gltf = export_json(&mut world);
+        assert_eq!(gltf["scenes"][0]["nodes"], json!([0]));
+        assert_eq!(gltf["nodes"][0]["name"], "map \"e3\" \\ odm");
+        assert_eq!(gltf["nodes"][0]["translation"], json!([1.0, 2.0, 3.0]));
+        assert_eq!(gltf["nodes"][0]["children"], json!([1]));
+
+        // the decoration is exported as two crossed quads with an alpha cutout
+        let index = |value: &Value| value.as_u64().unwrap() as usize;
+        let decoration = &gltf["nodes"][1];
+        assert_eq!(decoration["name"], "decoration");
+        let primitive = &gltf["meshes"][index(&decoration["mesh"])]["primitives"][0];
+        let positions = &gltf["accessors"][index(&primitive["attributes"]["POSITION"])];
+        assert_eq!(positions["count"], 8);
+        assert_eq!(gltf["accessors"][index(&primitive["indices"])]["count"], 12);
+        let material = &gltf["materials"][index(&primitive["material"])];
+        assert_eq!(material["alphaMode"], "MASK");
+        assert_eq!(material["doubleSided"], true);
+    }
+
+    #[test]
+    fn spawned_map_export_works() {
+        // empty 4x4 odm: header, height, tile and attribute maps, then no models,
+        // no billboards, no decoration faces and grid, and no spawn points
+        let size = (4, 4);
+        let mut data = vec![0; 176 + 3 * 16];
+        data.extend(0u32.to_le_bytes().repeat(3));
+        data.extend(vec![0; 16 * 4]);
+        data.extend(0u32.to_le_bytes());
+        let map = Odm::try_from_with_size(&data, size).unwrap();
+        let tile_table = TileTable::new(std::array::from_fn(|_| "grastyl".to_string()));
+        let texture = Image::new_fill(
+            Extent3d {
+                width: 4,
+                height: 4,
+                depth_or_array_layers: 1,
+            },
+            TextureDimension::D2,
+            &[255; 4],
+            TextureFormat::Rgba8UnormSrgb,
+            crate::odm::MAP_ASSET_USAGES,
+        );
+        let bundle = OdmBundle::with_terrain(map, &tile_table, texture, 1, None, false);
+
+        let mut world = World::new();
+        world.insert_resource(Assets::<Mesh>::default());
+        world.insert_resource(Assets::<StandardMaterial>::default());
+        world.insert_resource(Assets::<Image>::default());
+        let mut bundle = Some(bundle);
+        let entity = world.run_system_once(
+            move |mut commands: Commands,
+                  mut images: ResMut<Assets<Image>>,
+                  mut meshes: ResMut<Assets<Mesh>>,
+                  mut materials: ResMut<Assets<StandardMaterial>>| {
+                let (entity, _) = spawn_odm(
+                    &mut commands,
+                    bundle.take().unwrap(),
+                    Transform::IDENTITY,
+                    &mut images,
+                    &mut meshes,
+                    &mut materials,
+                    TerrainMode::default(),
+                );
+                commands.entity(entity).insert(CurrentMap);
+                entity
+            },
+        );
+        assert!(world.get::<Children>(entity).is_some());
+
+        // the render world only gets a copy, the export reads the assets back after the upload
+        let meshes = world.resource::<Assets<Mesh>>();
+        assert!(meshes
+            .iter()
+            .all(|(_, mesh)| mesh.asset_usage.contains(RenderAssetUsages::MAIN_WORLD)));
+        let images = world.resource::<Assets<Image>>();
+        assert!(images
+            .iter()
+            .all(|(_, image)| image.asset_usage.contains(RenderAssetUsages::MAIN_WORLD)));
+
+        let gltf = export_json(&mut world);
+        let index = |value: &Value| value.as_u64().unwrap() as usize;
+        let nodes = gltf["nodes"].as_array().unwrap();
+        let node = |name: &str| nodes.iter().find(|node| node["name"] == name).unwrap();
+        let terrain = &gltf["meshes"][index(&node("terrain")["mesh"])]["primitives"][0];
+        assert!(terrain.get("mode").is_none());
+        let material = &gltf["materials"][index(&terrain["material"])];
+        assert!(material["pbrMetallicRoughness"]["baseColorTexture"].is_object());
+        assert_eq!(gltf["images"].as_array().unwrap().len(), 1);
+        let lines = &gltf["meshes"][index(&node("terrain_lines")["mesh"])]["primitives"][0];
+        assert_eq!(lines["mode"], MODE_LINES);
+    }
+}
@@ -1,17 +1,33 @@
+use asset_preview::AssetPreviewPlugin;
 use bevy::prelude::{
     App, Commands, Component, DespawnRecursiveExt, Entity, Plugin, Query, States, With,
 };
 use bevy_config::BevyConfigPlugin;
+pub use cli::{Args, StartupView};
+use compare::ComparePlugin;
 use dev::DevPlugin;
+use export::ExportPlugin;
+use measure::MeasurePlugin;
 use menu::MenuPlugin;
+pub use mesh_stats::print_mesh_stats;
+use minimap::MinimapPlugin;
+pub use report::print_report;
 use splash::SplashPlugin;
 use world::WorldPlugin;
 
+pub(crate) mod asset_preview;
 pub(crate) mod bevy_config;
+pub(crate) mod cli;
+pub(crate) mod compare;
 pub(crate) mod dev;
+pub(crate) mod export;
+pub(crate) mod measure;
 pub(crate) mod menu;
+pub(crate) mod mesh_stats;
+pub(crate) mod minimap;
 pub(crate) mod odm;
 pub(crate) mod player;
+pub(crate) mod report;
 pub(crate) mod splash;
 pub(crate) mod utils;
 pub(crate) mod world;
@@ -36,6 +52,11 @@ impl Plugin for GamePlugin {
             SplashPlugin,
             WorldPlugin,
             DevPlugin,
+            ExportPlugin,
+            MinimapPlugin,
+            ComparePlugin,
+            MeasurePlugin,
+            AssetPreviewPlugin,
         ));
     }
 }
@@ -1,7 +1,45 @@
 use bevy::prelude::*;
+use clap::Parser;
 
-use map_viewer::GamePlugin;
+use map_viewer::{print_mesh_stats, print_report, Args, GamePlugin};
+
+fn odm_file_name(map: &str) -> String {
+    if map.ends_with(".odm") {
+        map.to_string()
+    } else {
+        format!("{}.odm", map)
+    }
+}
 
 fn main() {
-    App::new().add_plugins(GamePlugin).run();
+    let args = Args::parse();
+    if let Some(map) = &args.mesh_stats {
+        let map = odm_file_name(map);
+        if let Err(e) = print_mesh_stats(&map) {
+            eprintln!("Failed to build the mesh stats for {}: {}", map, e);
+            std::process::exit(1);
+        }
+        return;
+    }
+    if let Some(map) = &args.report {
+        let map = odm_file_name(map);
+        if let Err(e) = print_report(&map) {
+            eprintln!("Failed to build the report for {}: {}", map, e);
+            std::process::exit(1);
+        }
+        return;
+    }
+
+    let startup_view = match args.startup_view() {
+        Ok(startup_view) => startup_view,
+        Err(e) => {
+            eprintln!("Invalid arguments: {}", e);
+            std::process::exit(2);
+        }
+    };
+
+    App::new()
+        .insert_resource(startup_view)
+        .add_plugins(GamePlugin)
+        .run();
 }
@@ -0,0 +1,158 @@
+use bevy::{
+    prelude::*,
+    window::{CursorGrabMode, PrimaryWindow},
+};
+
+use crate::{despawn_all, dev::KeyBindings, odm::LoadedOdm, player::FlyCam, GameState};
+
+/// How far from the camera a click can hit the terrain
+const MAX_PICK_DISTANCE: f32 = 100_000.0;
+
+/// Measures the distance between two points clicked on the terrain,
+/// left click picks a point and right click clears them.
+pub struct MeasurePlugin;
+
+impl Plugin for MeasurePlugin {
+    fn build(&self, app: &mut App) {
+        app.init_resource::<MeasureTool>()
+            .add_systems(OnEnter(GameState::Game), measure_setup)
+            .add_systems(
+                Update,
+                (
+                    toggle_measure,
+                    pick_points,
+                    draw_measure,
+                    update_measure_text,
+                )
+                    .chain()
+                    .run_if(in_state(GameState::Game)),
+            )
+            .add_systems(OnExit(GameState::Game), despawn_all::<MeasureText>);
+    }
+}
+
+#[derive(Resource, Default)]
+struct MeasureTool {
+    enabled: bool,
+    points: Vec<Vec3>,
+}
+
+#[derive(Component)]
+struct MeasureText;
+
+fn measure_setup(mut commands: Commands) {
+    commands.spawn((
+        Name::new("measure"),
+        TextBundle::from_section(
+            "",
+            TextStyle {
+                font_size: 15.0,
+                color: Color::GOLD,
+                ..default()
+            },
+        )
+        .with_style(Style {
+            position_type: PositionType::Absolute,
+            left: Val::Px(10.0),
+            bottom: Val::Px(10.0),
+            ..default()
+        }),
+        MeasureText,
+    ));
+}
+
+fn toggle_measure(
+    keys: Res<ButtonInput<KeyCode>>,
+    key_bindings: Res<KeyBindings>,
+    mut tool: ResMut<MeasureTool>,
+) {
+    if keys.just_pressed(key_bindings.toggle_measure) {
+        tool.enabled = !tool.enabled;
+        tool.points.clear();
+    }
+}
+
+/// Casts a ray from the cursor when the mouse is free, a third point starts a new measure.
+fn pick_points(
+    buttons: Res<ButtonInput<MouseButton>>,
+    mut tool: ResMut<MeasureTool>,
+    odm: Option<Res<LoadedOdm>>,
+    window: Query<&Window, With<PrimaryWindow>>,
+    camera: Query<(&Camera, &GlobalTransform), With<FlyCam>>,
+) {
+    if !tool.enabled {
+        return;
+    }
+    if buttons.just_pressed(MouseButton::Right) {
+        tool.points.clear();
+        return;
+    }
+    if !buttons.just_pressed(MouseButton::Left) {
+        return;
+    }
+    let (Some(odm), Ok(window), Ok((camera, camera_transform))) =
+        (odm, window.get_single(), camera.get_single())
+    else {
+        return;
+    };
+    if window.cursor.grab_mode != CursorGrabMode::None {
+        return;
+    }
+    let Some(ray) = window
+        .cursor_position()
+        .and_then(|cursor| camera.viewport_to_world(camera_transform, cursor))
+    else {
+        return;
+    };
+    let Some(hit) = odm.0.raycast(
+        ray.origin.to_array(),
+        ray.direction.to_array(),
+        MAX_PICK_DISTANCE,
+    ) else {
+        return;
+    };
+
+    if tool.points.len() == 2 {
+        tool.points.clear();
+    }
+    tool.points.push(Vec3::from(hit));
+}
+
+fn draw_measure(tool: Res<MeasureTool>, mut gizmos: Gizmos) {
+    for point in &tool.points {
+        gizmos.sphere(*point, Quat::IDENTITY, 64.0, Color::GOLD);
+    }
+    if let [a, b] = tool.points.as_slice() {
+        gizmos.line(*a, *b, Color::GOLD);
+    }
+}
+
+fn update_measure_text(
+    tool: Res<MeasureTool>,
+    odm: Option<Res<LoadedOdm>>,
+    mut text: Query<&mut Text, With<MeasureText>>,
+) {
+    if !tool.is_changed() {
+        return;
+    }
+    let value = match (tool.enabled, tool.points.as_slice(), odm) {
+        (false, _, _) => String::new(),
+        (true, [a, b], Some(odm)) => {
+            let tiles = odm.0.tile_at(a.x, a.z).zip(odm.0.tile_at(b.x, b.z));
+            let tiles = tiles.map_or(String::new(), |((c0, r0), (c1, r1))| {
+                format!(" | tiles: {} x {}", c0.abs_diff(c1), r0.abs_diff(r1))
+            });
+            format!(
+                "Distance: {:.0} (horizontal {:.0}, vertical {:.0}){}",
+                a.distance(*b),
+                a.xz().distance(b.xz()),
+                (b.y - a.y).abs(),
+                tiles
+            )
+        }
+        (true, _, _) => "Measure: click two points on the terrain".to_string(),
+    };
+    for mut text in &mut text {
+        text.sections[0].value = value.clone();
+    }
+}
@@ -0,0 +1,81 @@
+use std::error::Error;
+
+use bevy::render::mesh::Mesh;
+use lod::{
+    colormap::ColorMap,
+    odm::{Odm, OdmData},
+    LodManager,
+};
+
+use crate::odm::OdmBundle;
+
+/// Size of the buffers of one terrain mesh variant.
+struct MeshStats {
+    vertices: usize,
+    indices: usize,
+    vertex_bytes: usize,
+    index_bytes: usize,
+}
+
+impl From<&Mesh> for MeshStats {
+    fn from(mesh: &Mesh) -> Self {
+        Self {
+            vertices: mesh.count_vertices(),
+            indices: mesh.indices().map_or(0, |indices| indices.len()),
+            vertex_bytes: mesh.get_vertex_buffer_data().len(),
+            index_bytes: mesh.get_index_buffer_bytes().map_or(0, |bytes| bytes.len()),
+        }
+    }
+}
+
+impl MeshStats {
+    fn bytes(&self) -> usize {
+        self.vertex_bytes + self.index_bytes
+    }
+}
+
+/// Prints the vertex count, index count and buffer sizes of the terrain of `map_name`
+/// built as the meshes the viewer can upload: indexed per tile quad with smooth normals,
+/// without indices with flat normals, and height colored on the shared vertex grid.
+pub fn print_mesh_stats(map_name: &str) -> Result<(), Box<dyn Error>> {
+    let lod_manager = LodManager::new(lod::get_lod_path())?;
+    let map = Odm::new(&lod_manager, map_name)?;
+    let tile_table = map.tile_table(&lod_manager)?;
+
+    let indexed = OdmBundle::generate_terrain_mesh(OdmData::new(&map, &tile_table), None, true);
+    let non_indexed =
+        OdmBundle::generate_terrain_mesh(OdmData::new(&map, &tile_table), None, false);
+    let odm_data = OdmData::new(&map, &tile_table);
+    let colors = odm_data.height_colors(ColorMap::default());
+    let grid = OdmBundle::generate_terrain_mesh(odm_data, Some(colors), true);
+    let indexed = MeshStats::from(&indexed);
+    let non_indexed = MeshStats::from(&non_indexed);
+    let grid = MeshStats::from(&grid);
+
+    println!("{}: terrain meshes", map_name);
+    println!(
+        "{:<16}{:>12}{:>12}{:>16}{:>14}{:>14}",
+        "mesh", "vertices", "indices", "vertex bytes", "index bytes", "total bytes"
+    );
+    for (name, stats) in [
+        ("indexed", &indexed),
+        ("non-indexed", &non_indexed),
+        ("height colored", &grid),
+    ] {
+        println!(
+            "{:<16}{:>12}{:>12}{:>16}{:>14}{:>14}",
+            name,
+            stats.vertices,
+            stats.indices,
+            stats.vertex_bytes,
+            stats.index_bytes,
+            stats.bytes()
+        );
+    }
+    println!(
+        "indexed saves {} bytes ({:.1}%)",
+        non_indexed.bytes() as i64 - indexed.bytes() as i64,
+        100.0 * (1.0 - indexed.bytes() as f64 / non_indexed.bytes() as f64)
+    );
+    Ok(())
+}
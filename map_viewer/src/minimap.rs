@@ -0,0 +1,211 @@
+use bevy::{
+    prelude::*,
+    render::{
+        render_asset::RenderAssetUsages,
+        render_resource::{Extent3d, TextureDimension, TextureFormat},
+    },
+};
+use lod::odm::{Odm, ODM_TILE_SCALE};
+
+use crate::{despawn_all, dev::KeyBindings, odm::LoadedOdm, player::FlyCam, GameState};
+
+/// Side of the minimap on screen, in pixels
+const MINIMAP_SIZE: f32 = 192.0;
+
+pub struct MinimapPlugin;
+
+impl Plugin for MinimapPlugin {
+    fn build(&self, app: &mut App) {
+        app.init_resource::<MinimapMode>()
+            .add_systems(OnEnter(GameState::Game), minimap_setup)
+            .add_systems(
+                Update,
+                (update_minimap_image, toggle_minimap_mode, update_minimap)
+                    .chain()
+                    .run_if(in_state(GameState::Game)),
+            )
+            .add_systems(OnExit(GameState::Game), despawn_all::<Minimap>);
+    }
+}
+
+/// Whether north or the camera facing points up on the minimap
+#[derive(Resource, Default, Clone, Copy, PartialEq, Debug)]
+pub enum MinimapMode {
+    #[default]
+    NorthUp,
+    CameraUp,
+}
+
+#[derive(Component)]
+struct Minimap;
+
+#[derive(Component)]
+struct MinimapImage;
+
+#[derive(Component)]
+struct MinimapMarker;
+
+fn minimap_setup(mut commands: Commands) {
+    let marker_color = BackgroundColor(Color::RED);
+    commands
+        .spawn((
+            Name::new("minimap"),
+            NodeBundle {
+                style: Style {
+                    position_type: PositionType::Absolute,
+                    right: Val::Px(10.0),
+                    bottom: Val::Px(10.0),
+                    width: Val::Px(MINIMAP_SIZE),
+                    height: Val::Px(MINIMAP_SIZE),
+                    overflow: Overflow::clip(),
+                    ..default()
+                },
+                ..default()
+            },
+            Minimap,
+        ))
+        .with_children(|parent| {
+            parent
+                .spawn((
+                    ImageBundle {
+                        style: Style {
+                            width: Val::Percent(100.0),
+                            height: Val::Percent(100.0),
+                            ..default()
+                        },
+                        ..default()
+                    },
+                    MinimapImage,
+                ))
+                .with_children(|parent| {
+                    parent
+                        .spawn((
+                            NodeBundle {
+                                style: Style {
+                                    position_type: PositionType::Absolute,
+                                    width: Val::Px(8.0),
+                                    height: Val::Px(12.0),
+                                    flex_direction: FlexDirection::Column,
+                                    align_items: AlignItems::Center,
+                                    ..default()
+                                },
+                                ..default()
+                            },
+                            MinimapMarker,
+                        ))
+                        .with_children(|parent| {
+                            // the head shows where the camera is looking
+                            parent.spawn(NodeBundle {
+                                style: Style {
+                                    width: Val::Px(8.0),
+                                    height: Val::Px(4.0),
+                                    ..default()
+                                },
+                                background_color: marker_color,
+                                ..default()
+                            });
+                            parent.spawn(NodeBundle {
+                                style: Style {
+                                    width: Val::Px(2.0),
+                                    height: Val::Px(8.0),
+                                    ..default()
+                                },
+                                background_color: marker_color,
+                                ..default()
+                            });
+                        });
+                });
+        });
+}
+
+/// Top down view of the terrain, the water in blue and the land shaded by height.
+fn minimap_image(odm: &Odm) -> Image {
+    let (width, depth) = odm.size();
+    let data = odm
+        .height_map
+        .iter()
+        .flat_map(|&h| {
+            if h == 0 {
+                [32, 64, 128, 255]
+            } else {
+                [h / 2, 64 + h / 2, h / 3, 255]
+            }
+        })
+        .collect();
+    Image::new(
+        Extent3d {
+            width: width as u32,
+            height: depth as u32,
+            depth_or_array_layers: 1,
+        },
+        TextureDimension::D2,
+        data,
+        TextureFormat::Rgba8UnormSrgb,
+        RenderAssetUsages::RENDER_WORLD,
+    )
+}
+
+fn update_minimap_image(
+    odm: Option<Res<LoadedOdm>>,
+    mut images: ResMut<Assets<Image>>,
+    mut query: Query<&mut UiImage, With<MinimapImage>>,
+) {
+    let Some(odm) = odm.filter(|odm| odm.is_changed()) else {
+        return;
+    };
+    let handle = images.add(minimap_image(&odm.0));
+    for mut image in &mut query {
+        image.texture = handle.clone();
+    }
+}
+
+fn toggle_minimap_mode(
+    keys: Res<ButtonInput<KeyCode>>,
+    key_bindings: Res<KeyBindings>,
+    mut mode: ResMut<MinimapMode>,
+) {
+    if keys.just_pressed(key_bindings.toggle_minimap_mode) {
+        *mode = match *mode {
+            MinimapMode::NorthUp => MinimapMode::CameraUp,
+            MinimapMode::CameraUp => MinimapMode::NorthUp,
+        };
+    }
+}
+
+type MarkerQuery<'w, 's> = Query<
+    'w,
+    's,
+    (&'static mut Style, &'static mut Transform),
+    (With<MinimapMarker>, Without<MinimapImage>, Without<FlyCam>),
+>;
+
+fn update_minimap(
+    mode: Res<MinimapMode>,
+    odm: Option<Res<LoadedOdm>>,
+    camera: Query<&Transform, With<FlyCam>>,
+    mut map: Query<&mut Transform, (With<MinimapImage>, Without<FlyCam>)>,
+    mut marker: MarkerQuery,
+) {
+    let (Some(odm), Ok(camera)) = (odm, camera.get_single()) else {
+        return;
+    };
+    let (width, depth) = odm.0.size();
+    let (yaw, _, _) = camera.rotation.to_euler(EulerRot::YXZ);
+    // ui y points down, so a positive rotation is clockwise on screen
+    let map_rotation = match *mode {
+        MinimapMode::NorthUp => Quat::IDENTITY,
+        MinimapMode::CameraUp => Quat::from_rotation_z(yaw),
+    };
+
+    for mut transform in &mut map {
+        transform.rotation = map_rotation;
+    }
+
+    let x = (camera.translation.x / ODM_TILE_SCALE / width as f32 + 0.5) * MINIMAP_SIZE;
+    let y = (camera.translation.z / ODM_TILE_SCALE / depth as f32 + 0.5) * MINIMAP_SIZE;
+    for (mut style, mut transform) in &mut marker {
+        style.left = Val::Px(x - 4.0);
+        style.top = Val::Px(y - 6.0);
+        transform.rotation = Quat::from_rotation_z(-yaw);
+    }
+}
@@ -11,21 +11,63 @@ use bevy::{
 //     BillboardLockAxis, BillboardLockAxisBundle, BillboardTextureHandle,
 // };
 
-use std::error::Error;
+use std::{collections::HashMap, error::Error};
 
-use crate::{despawn_all, utils::random_color, world::WorldSettings, GameState};
+use crate::{
+    cli::StartupView,
+    despawn_all,
+    dev::KeyBindings,
+    player::{FlyCam, MovementSettings},
+    utils::random_color,
+    world::{water::water_bundle, WorldSettings},
+    GameState,
+};
 use lod::{
-    dtile::TileTable,
-    odm::{Odm, OdmData},
+    billboard::BillboardManager,
+    colormap::ColorMap,
+    dtile::{load_atlas, TileTable},
+    image::{pack_atlas, TileBorder},
+    odm::{Odm, OdmData, ODM_TILE_SCALE},
     LodManager,
 };
 
+const DECORATION_ATLAS_WIDTH: u32 = 4096;
+
+/// The map meshes and textures stay in the main world once uploaded,
+/// the glTF export and the debug normals read them back.
+pub(super) const MAP_ASSET_USAGES: RenderAssetUsages = RenderAssetUsages::all();
+
+/// Side of the terrain chunks in tiles, a 128 tiles map is split in 8x8 chunks.
+pub(super) const TERRAIN_CHUNK_SIZE: usize = 16;
+
 // TODO make it a real bundle
 pub(super) struct OdmBundle {
     pub map: Odm,
-    pub mesh: Mesh,
+    /// Terrain split in chunks of [`TERRAIN_CHUNK_SIZE`] tiles
+    pub chunks: Vec<(ChunkCoord, Mesh)>,
+    /// Edges of the terrain triangles
+    pub lines: Mesh,
     pub texture: Image,
+    /// Number of tiles in the terrain atlas along u and v
+    pub atlas_size: UVec2,
+    /// Minimum and maximum corners of the terrain, in world space
+    pub aabb: (Vec3, Vec3),
+    /// The terrain mesh carries height colors and is drawn without the atlas
+    pub height_colored: bool,
     pub models: Vec<ModelBundle>,
+    pub decorations: Vec<DecorationBundle>,
+    pub decoration_bounds: DecorationBounds,
+}
+
+/// Stacks the levels of a mip chain in a single texture, starting from the full size one.
+fn mipmapped_image(levels: Vec<Image>) -> Option<Image> {
+    let mut levels = levels.into_iter();
+    let mut image = levels.next()?;
+    for level in levels {
+        image.data.extend(level.data);
+        image.texture_descriptor.mip_level_count += 1;
+    }
+    Some(image)
 }
 
 // TODO make it a real bundle
@@ -35,29 +77,98 @@ pub(super) struct ModelBundle {
     pub material: StandardMaterial,
 }
 
+/// World position, declist radius and height of every spawned decoration, kept on the map
+/// entity to draw their bounding boxes when debugging placement.
+#[derive(Component, Default, Clone)]
+pub(super) struct DecorationBounds(pub Vec<(Vec3, f32, f32)>);
+
+/// Decorations sharing the same texture, when batching is enabled there is only
+/// one of these holding all the decorations of the map in a single mesh.
+pub(super) struct DecorationBundle {
+    pub texture: Image,
+    pub meshes: Vec<Mesh>,
+}
+
 impl OdmBundle {
-    pub(super) fn new(lod_manager: &LodManager, map_name: &str) -> Result<Self, Box<dyn Error>> {
+    pub(super) fn new(
+        lod_manager: &LodManager,
+        map_name: &str,
+        settings: &WorldSettings,
+    ) -> Result<Self, Box<dyn Error>> {
         let map = Odm::new(lod_manager, map_name)?;
         let tile_table = map.tile_table(lod_manager)?;
-        let mesh = Self::generate_terrain_mesh(&map, &tile_table);
-        let image = bevy::render::texture::Image::from_dynamic(
-            tile_table.atlas_image(lod_manager)?,
-            true,
-            RenderAssetUsages::RENDER_WORLD,
-        );
-        let models = process_models(&map);
+        let (atlas_levels, tile_table) = if let Some(custom_atlas) = &settings.custom_atlas {
+            let (atlas, layout) = load_atlas(&custom_atlas.image, &custom_atlas.layout)?;
+            (vec![atlas], tile_table.with_layout(&layout)?)
+        } else if settings.show_tile_borders {
+            let atlas = tile_table.atlas_image_with_border(lod_manager, &TileBorder::default())?;
+            (vec![atlas], tile_table)
+        } else {
+            (tile_table.atlas_mips(lod_manager)?, tile_table)
+        };
+        if let (Some(path), Some(atlas)) = (&settings.dump_atlas, atlas_levels.first()) {
+            if let Err(e) = atlas.save(path) {
+                warn!("Failed to save the terrain atlas to {:?}: {}", path, e);
+            }
+        }
+        let texture = mipmapped_image(
+            atlas_levels
+                .into_iter()
+                .map(|level| Image::from_dynamic(level, true, MAP_ASSET_USAGES))
+                .collect(),
+        )
+        .ok_or("terrain atlas has no image")?;
+        let (decorations, decoration_bounds) = process_decorations(lod_manager, &map, settings)?;
 
         Ok(OdmBundle {
+            decorations,
+            decoration_bounds,
+            ..Self::with_terrain(
+                map,
+                &tile_table,
+                texture,
+                settings.tessellation,
+                settings.height_color_map,
+                settings.smooth_terrain,
+            )
+        })
+    }
+
+    /// Terrain and models of `map` textured with the atlas of `tile_table`, without decorations.
+    pub(super) fn with_terrain(
+        map: Odm,
+        tile_table: &TileTable,
+        texture: Image,
+        tessellation: usize,
+        height_color_map: Option<ColorMap>,
+        smooth: bool,
+    ) -> Self {
+        let atlas_size = UVec2::new(tile_table.size().0 as u32, tile_table.size().1 as u32);
+        let odm_data = OdmData::with_tessellation(&map, tile_table, tessellation);
+        let lines = Self::generate_terrain_lines(&odm_data);
+        let aabb = (Vec3::from(odm_data.aabb.0), Vec3::from(odm_data.aabb.1));
+        let colors = height_color_map.map(|color_map| odm_data.height_colors(color_map));
+        let height_colored = colors.is_some();
+        let chunks = Self::generate_terrain_chunks(&odm_data, colors, smooth, TERRAIN_CHUNK_SIZE);
+        let models = process_models(&map);
+
+        OdmBundle {
             map,
-            mesh,
-            texture: image,
+            chunks,
+            lines,
+            texture,
+            atlas_size,
+            aabb,
+            height_colored,
             models,
-        })
+            decorations: Vec::new(),
+            decoration_bounds: DecorationBounds::default(),
+        }
     }
 
     pub fn terrain_material(&self, image_handle: Handle<Image>) -> StandardMaterial {
         StandardMaterial {
-            base_color_texture: Some(image_handle),
+            base_color_texture: (!self.height_colored).then_some(image_handle),
             unlit: false,
             alpha_mode: AlphaMode::Opaque,
             fog_enabled: true,
@@ -69,30 +180,72 @@ impl OdmBundle {
         }
     }
 
-    fn generate_terrain_mesh(odm: &Odm, tile_table: &TileTable) -> Mesh {
-        let odm_data = OdmData::new(odm, tile_table);
-        let mut mesh = Mesh::new(
-            PrimitiveTopology::TriangleList,
-            RenderAssetUsages::RENDER_WORLD,
-        );
-        mesh.insert_indices(bevy::render::mesh::Indices::U32(odm_data.indices.clone()));
-        mesh.insert_attribute(Mesh::ATTRIBUTE_POSITION, odm_data.positions);
-        mesh.duplicate_vertices();
-        mesh.insert_attribute(Mesh::ATTRIBUTE_UV_0, odm_data.uvs);
-
-        // let normals = generate_terrain_normals(
-        //     mesh.attribute(Mesh::ATTRIBUTE_POSITION)
-        //         .unwrap()
-        //         .as_float3()
-        //         .unwrap(),
-        //     &odm_data.indices,
-        // );
-        // mesh.insert_attribute(Mesh::ATTRIBUTE_NORMAL, normals);
+    /// `colors` are sRGB and per vertex of `odm_data.positions`, `smooth` shades with normals
+    /// averaged over the triangles sharing a grid vertex instead of flat triangles.
+    pub(super) fn generate_terrain_mesh(
+        odm_data: OdmData,
+        colors: Option<Vec<[f32; 4]>>,
+        smooth: bool,
+    ) -> Mesh {
+        let (colors, normals) = Self::terrain_attributes(&odm_data, colors, smooth);
+        let quads: Vec<usize> = (0..odm_data.indices.len() / 6).collect();
+        terrain_quads_mesh(&odm_data, &quads, colors.as_deref(), normals.as_deref())
+    }
 
-        mesh.compute_flat_normals();
-        mesh.compute_aabb();
-        _ = mesh.generate_tangents();
+    /// Same as [`OdmBundle::generate_terrain_mesh`] with the terrain split in square chunks
+    /// of `chunk_size` tiles, each one is spawned apart so the chunks out of view are culled.
+    /// The smooth normals are computed on the whole grid, there are no seams between chunks.
+    pub(super) fn generate_terrain_chunks(
+        odm_data: &OdmData,
+        colors: Option<Vec<[f32; 4]>>,
+        smooth: bool,
+        chunk_size: usize,
+    ) -> Vec<(ChunkCoord, Mesh)> {
+        let (colors, normals) = Self::terrain_attributes(odm_data, colors, smooth);
+        let (quads_x, quads_z) = (odm_data.grid_size.0 - 1, odm_data.grid_size.1 - 1);
+        let chunk_quads = (chunk_size * odm_data.tessellation).max(1);
+
+        let mut chunks = Vec::new();
+        for z in 0..quads_z.div_ceil(chunk_quads) {
+            for x in 0..quads_x.div_ceil(chunk_quads) {
+                let rows = z * chunk_quads..((z + 1) * chunk_quads).min(quads_z);
+                let cols = x * chunk_quads..((x + 1) * chunk_quads).min(quads_x);
+                let quads: Vec<usize> = rows
+                    .flat_map(|row| cols.clone().map(move |col| row * quads_x + col))
+                    .collect();
+                let mesh =
+                    terrain_quads_mesh(odm_data, &quads, colors.as_deref(), normals.as_deref());
+                chunks.push((ChunkCoord { x, z }, mesh));
+            }
+        }
+        chunks
+    }
+
+    /// Linear colors and, for smooth terrain, the normals of the grid vertices.
+    fn terrain_attributes(
+        odm_data: &OdmData,
+        colors: Option<Vec<[f32; 4]>>,
+        smooth: bool,
+    ) -> TerrainAttributes {
+        let colors = colors.map(|colors| {
+            colors
+                .into_iter()
+                .map(|[r, g, b, a]| Color::rgba(r, g, b, a).as_linear_rgba_f32())
+                .collect()
+        });
+        let normals = smooth.then(|| generate_normals(&odm_data.positions, &odm_data.indices));
+        (colors, normals)
+    }
 
+    fn generate_terrain_lines(odm_data: &OdmData) -> Mesh {
+        let indices = odm_data
+            .indices
+            .chunks_exact(3)
+            .flat_map(|t| [t[0], t[1], t[1], t[2], t[2], t[0]])
+            .collect();
+        let mut mesh = Mesh::new(PrimitiveTopology::LineList, MAP_ASSET_USAGES);
+        mesh.insert_indices(bevy::render::mesh::Indices::U32(indices));
+        mesh.insert_attribute(Mesh::ATTRIBUTE_POSITION, odm_data.positions.clone());
         mesh
     }
 }
@@ -117,6 +270,245 @@ fn process_models(map: &Odm) -> Vec<ModelBundle> {
     models
 }
 
+/// Linear vertex colors and smooth normals of the terrain grid, when they are used.
+type TerrainAttributes = (Option<Vec<[f32; 4]>>, Option<Vec<[f32; 3]>>);
+
+/// Sprite index, world position and size of a decoration.
+type DecorationPlacement = (usize, Vec3, (f32, f32));
+
+fn process_decorations(
+    lod_manager: &LodManager,
+    map: &Odm,
+    settings: &WorldSettings,
+) -> Result<(Vec<DecorationBundle>, DecorationBounds), Box<dyn Error>> {
+    let billboard_manager = BillboardManager::new(lod_manager)?;
+
+    // Sprites are shared by many decorations, load each of them only once.
+    let mut sprite_ids: HashMap<u16, usize> = HashMap::new();
+    let mut sprites = Vec::new();
+    let mut placements: Vec<DecorationPlacement> = Vec::new();
+    for b in &map.billboards {
+        if b.data.is_invisible() {
+            continue;
+        }
+        let sprite_id = match sprite_ids.get(&b.data.declist_id) {
+            Some(id) => *id,
+            None => {
+                let Some(sprite) =
+                    billboard_manager.get(lod_manager, &b.declist_name, b.data.declist_id)
+                else {
+                    continue;
+                };
+                if !b.is_drawn(&sprite.d_declist_item) {
+                    continue;
+                }
+                sprites.push(sprite);
+                sprite_ids.insert(b.data.declist_id, sprites.len() - 1);
+                sprites.len() - 1
+            }
+        };
+        let position = Vec3::new(
+            b.data.position[0] as f32,
+            b.data.position[2] as f32,
+            -b.data.position[1] as f32,
+        );
+        placements.push((sprite_id, position, sprites[sprite_id].dimensions()));
+    }
+
+    if let Some(max_decorations) = settings.max_decorations {
+        cap_decorations(&mut placements, max_decorations, Vec3::ZERO);
+    }
+    let bounds = DecorationBounds(
+        placements
+            .iter()
+            .map(|(sprite_id, position, _)| {
+                let item = &sprites[*sprite_id].d_declist_item;
+                (*position, item.radius as f32, item.height as f32)
+            })
+            .collect(),
+    );
+
+    if settings.batch_decorations {
+        let images: Vec<_> = sprites.iter().map(|s| s.image.clone()).collect();
+        let (atlas, uv_rects) = pack_atlas(&images, DECORATION_ATLAS_WIDTH);
+        let mut mesh_data = DecorationMeshData::default();
+        for (sprite_id, position, size) in &placements {
+            mesh_data.push(*position, *size, uv_rects[*sprite_id]);
+        }
+        let decorations = vec![DecorationBundle {
+            texture: Image::from_dynamic(atlas, true, MAP_ASSET_USAGES),
+            meshes: vec![mesh_data.into()],
+        }];
+        return Ok((decorations, bounds));
+    }
+
+    let mut decorations: Vec<DecorationBundle> = sprites
+        .into_iter()
+        .map(|s| DecorationBundle {
+            texture: Image::from_dynamic(s.image, true, MAP_ASSET_USAGES),
+            meshes: Vec::new(),
+        })
+        .collect();
+    for (sprite_id, position, size) in placements {
+        let mut mesh_data = DecorationMeshData::default();
+        mesh_data.push(position, size, [0.0, 0.0, 1.0, 1.0]);
+        decorations[sprite_id].meshes.push(mesh_data.into());
+    }
+    Ok((decorations, bounds))
+}
+
+/// Keeps only the `max` decorations nearest to `center` on the ground plane.
+fn cap_decorations(placements: &mut Vec<DecorationPlacement>, max: usize, center: Vec3) {
+    if placements.len() <= max {
+        return;
+    }
+    let distance = |p: &Vec3| p.xz().distance_squared(center.xz());
+    placements.sort_by(|a, b| distance(&a.1).total_cmp(&distance(&b.1)));
+    placements.truncate(max);
+}
+
+/// Decorations are drawn as two crossed quads so they can be merged in a static mesh.
+#[derive(Default)]
+pub(super) struct DecorationMeshData {
+    positions: Vec<[f32; 3]>,
+    normals: Vec<[f32; 3]>,
+    uvs: Vec<[f32; 2]>,
+    indices: Vec<u32>,
+}
+
+impl DecorationMeshData {
+    pub(super) fn push(&mut self, position: Vec3, size: (f32, f32), uv_rect: [f32; 4]) {
+        let (half_width, height) = (size.0 / 2.0, size.1);
+        for (axis, normal) in [(Vec3::X, Vec3::Z), (Vec3::Z, Vec3::X)] {
+            let start = self.positions.len() as u32;
+            let left = position - axis * half_width;
+            let right = position + axis * half_width;
+            for (corner, uv) in [
+                (left, [uv_rect[0], uv_rect[3]]),
+                (right, [uv_rect[2], uv_rect[3]]),
+                (right + Vec3::Y * height, [uv_rect[2], uv_rect[1]]),
+                (left + Vec3::Y * height, [uv_rect[0], uv_rect[1]]),
+            ] {
+                self.positions.push(corner.to_array());
+                self.normals.push(normal.to_array());
+                self.uvs.push(uv);
+            }
+            self.indices
+                .extend([start, start + 1, start + 2, start, start + 2, start + 3]);
+        }
+    }
+}
+
+impl From<DecorationMeshData> for Mesh {
+    fn from(data: DecorationMeshData) -> Self {
+        let mut mesh = Mesh::new(PrimitiveTopology::TriangleList, MAP_ASSET_USAGES);
+        mesh.insert_indices(bevy::render::mesh::Indices::U32(data.indices));
+        mesh.insert_attribute(Mesh::ATTRIBUTE_POSITION, data.positions);
+        mesh.insert_attribute(Mesh::ATTRIBUTE_NORMAL, data.normals);
+        mesh.insert_attribute(Mesh::ATTRIBUTE_UV_0, data.uvs);
+        mesh
+    }
+}
+
+pub(super) fn decoration_material(image_handle: Handle<Image>) -> StandardMaterial {
+    StandardMaterial {
+        base_color_texture: Some(image_handle),
+        alpha_mode: AlphaMode::Mask(0.5),
+        unlit: true,
+        cull_mode: None,
+        ..default()
+    }
+}
+
+/// Mesh of the grid `quads` of the terrain, see [`OdmData::grid_size`].
+///
+/// The tiles have their own uvs in the atlas so the grid vertices can't be shared between
+/// tiles: with `normals` the mesh is indexed with the 4 corners of every quad, without them
+/// every triangle has its own vertices for the flat normals. Height colored terrain has no
+/// uvs, with `normals` too it shares the grid vertices, see [`terrain_grid_mesh`].
+fn terrain_quads_mesh(
+    odm_data: &OdmData,
+    quads: &[usize],
+    colors: Option<&[[f32; 4]]>,
+    normals: Option<&[[f32; 3]]>,
+) -> Mesh {
+    if let (Some(colors), Some(normals)) = (colors, normals) {
+        return terrain_grid_mesh(odm_data, quads, colors, normals);
+    }
+    // offsets in the 6 indices of a quad, its 2 triangles share the 2nd and 3rd vertices
+    let corners: &[usize] = if normals.is_some() {
+        &[0, 1, 2, 5]
+    } else {
+        &[0, 1, 2, 3, 4, 5]
+    };
+    let vertices: Vec<usize> = quads
+        .iter()
+        .flat_map(|quad| corners.iter().map(move |corner| quad * 6 + corner))
+        .collect();
+    let grid_vertices: Vec<u32> = vertices.iter().map(|&v| odm_data.indices[v]).collect();
+
+    let mut mesh = Mesh::new(PrimitiveTopology::TriangleList, MAP_ASSET_USAGES);
+    mesh.insert_attribute(
+        Mesh::ATTRIBUTE_POSITION,
+        gather(&odm_data.positions, &grid_vertices),
+    );
+    if let Some(colors) = colors {
+        mesh.insert_attribute(Mesh::ATTRIBUTE_COLOR, gather(colors, &grid_vertices));
+    }
+    let uvs: Vec<[f32; 2]> = vertices.iter().map(|&v| odm_data.uvs[v]).collect();
+    mesh.insert_attribute(Mesh::ATTRIBUTE_UV_0, uvs);
+    if let Some(normals) = normals {
+        let indices = (0..grid_vertices.len() as u32)
+            .step_by(4)
+            .flat_map(|i| [i, i + 1, i + 2, i + 2, i + 1, i + 3])
+            .collect();
+        mesh.insert_indices(bevy::render::mesh::Indices::U32(indices));
+        mesh.insert_attribute(Mesh::ATTRIBUTE_NORMAL, gather(normals, &grid_vertices));
+    } else {
+        mesh.compute_flat_normals();
+    }
+    mesh.compute_aabb();
+    _ = mesh.generate_tangents();
+
+    mesh
+}
+
+/// Mesh of the grid `quads` of the terrain with one vertex per grid vertex, indexed with the
+/// triangles of `odm_data`. Only for meshes without uvs: a vertex is shared by up to 4 tiles.
+fn terrain_grid_mesh(
+    odm_data: &OdmData,
+    quads: &[usize],
+    colors: &[[f32; 4]],
+    normals: &[[f32; 3]],
+) -> Mesh {
+    let mut local_ids: HashMap<u32, u32> = HashMap::new();
+    let mut grid_vertices = Vec::new();
+    let indices = quads
+        .iter()
+        .flat_map(|quad| &odm_data.indices[quad * 6..quad * 6 + 6])
+        .map(|&v| {
+            *local_ids.entry(v).or_insert_with(|| {
+                grid_vertices.push(v);
+                grid_vertices.len() as u32 - 1
+            })
+        })
+        .collect();
+
+    let mut mesh = Mesh::new(PrimitiveTopology::TriangleList, MAP_ASSET_USAGES);
+    mesh.insert_attribute(
+        Mesh::ATTRIBUTE_POSITION,
+        gather(&odm_data.positions, &grid_vertices),
+    );
+    mesh.insert_attribute(Mesh::ATTRIBUTE_COLOR, gather(colors, &grid_vertices));
+    mesh.insert_attribute(Mesh::ATTRIBUTE_NORMAL, gather(normals, &grid_vertices));
+    mesh.insert_indices(bevy::render::mesh::Indices::U32(indices));
+    mesh
+}
+
+fn gather<T: Copy>(values: &[T], indices: &[u32]) -> Vec<T> {
+    indices.iter().map(|&i| values[i as usize]).collect()
+}
+
 fn generate_normals(vertices: &[[f32; 3]], indices: &[u32]) -> Vec<[f32; 3]> {
     let mut normals = vec![[0.0, 0.0, 0.0]; vertices.len()];
 
@@ -142,19 +534,13 @@ fn generate_normals(vertices: &[[f32; 3]], indices: &[u32]) -> Vec<[f32; 3]> {
     }
 
     for normal in normals.iter_mut() {
-        let length = (normal[0] * normal[0] + normal[1] * normal[1] + normal[2] * normal[2]).sqrt();
-        normal[0] /= length;
-        normal[1] /= length;
-        normal[2] /= length;
+        *normal = Vec3::from(*normal).normalize_or_zero().into();
     }
     normals
 }
 
 fn generate_bsp_model_mesh(model: &lod::bsp_model::BSPModel) -> Mesh {
-    let mut mesh = Mesh::new(
-        PrimitiveTopology::TriangleList,
-        RenderAssetUsages::RENDER_WORLD,
-    );
+    let mut mesh = Mesh::new(PrimitiveTopology::TriangleList, MAP_ASSET_USAGES);
     mesh.insert_indices(bevy::render::mesh::Indices::U32(model.indices.clone()));
     mesh.insert_attribute(Mesh::ATTRIBUTE_POSITION, model.vertices.clone());
     mesh.duplicate_vertices();
@@ -181,6 +567,7 @@ fn generate_bsp_model_bounding_box(model: &lod::bsp_model::BSPModel) -> Cuboid {
     )
 }
 
+#[derive(Clone)]
 pub(super) struct OdmName {
     pub x: char,
     pub y: char,
@@ -203,12 +590,19 @@ impl Display for OdmName {
 impl TryFrom<&str> for OdmName {
     type Error = String;
 
+    /// Accepts `outXY` with an optional `.odm` extension, e.g. `oute3` or `oute3.odm`.
     fn try_from(value: &str) -> Result<Self, Self::Error> {
-        let x = value.as_bytes().get(3).copied().ok_or("invalid map name")? as char;
-        let y = value.as_bytes().get(4).copied().ok_or("invalid map name")? as char;
+        let name = value.to_lowercase();
+        let name = name.strip_suffix(".odm").unwrap_or(&name);
+        let coords = name
+            .strip_prefix("out")
+            .ok_or(format!("invalid map name {:?}", value))?;
+        let [x, y] = coords.as_bytes() else {
+            return Err(format!("invalid map name {:?}", value));
+        };
 
-        let x = Self::validate_x(x).ok_or("invalid map x coordinate")?;
-        let y = Self::validate_y(y).ok_or("invalid map y coordinate")?;
+        let x = Self::validate_x(*x as char).ok_or("invalid map x coordinate")?;
+        let y = Self::validate_y(*y as char).ok_or("invalid map y coordinate")?;
 
         Ok(Self { x, y })
     }
@@ -253,52 +647,69 @@ impl OdmName {
     }
 }
 
-#[derive(Component)]
-struct CurrentMap;
-
-fn odm_setup(mut commands: Commands) {}
-
-fn change_odm(
-    mut commands: Commands,
-    mut settings: ResMut<WorldSettings>,
-    mut images: ResMut<Assets<Image>>,
-    mut meshes: ResMut<Assets<Mesh>>,
-    mut materials: ResMut<Assets<StandardMaterial>>,
-    //mut billboard_textures: ResMut<Assets<BillboardTextureBundle>>,
-    query: Query<Entity, With<CurrentMap>>,
-) {
-    if !settings.odm_changed {
-        return;
-    }
+/// Height of the camera above the party start marker
+const PARTY_EYE_HEIGHT: f32 = 192.0;
 
-    for e in &query {
-        commands.entity(e).despawn_recursive();
-    }
+#[derive(Component)]
+pub(super) struct CurrentMap;
 
-    let odm = OdmBundle::new(
-        &settings.lod_manager,
-        settings.current_odm.to_string().as_str(),
-    );
+/// The map currently shown, kept around for queries like the terrain height.
+#[derive(Resource)]
+pub(super) struct LoadedOdm(pub Odm);
 
-    if odm.is_err() {
-        return;
-    }
-    let odm = odm.unwrap();
+fn odm_setup(mut commands: Commands) {}
 
+/// Spawns the terrain, models, water and decorations of a map under a single entity.
+pub(super) fn spawn_odm(
+    commands: &mut Commands,
+    odm: OdmBundle,
+    transform: Transform,
+    images: &mut Assets<Image>,
+    meshes: &mut Assets<Mesh>,
+    materials: &mut Assets<StandardMaterial>,
+    terrain_mode: TerrainMode,
+) -> (Entity, Odm) {
     let image_handle = images.add(odm.texture.clone());
     let material = odm.terrain_material(image_handle);
 
-    commands
+    let entity = commands
         .spawn((
             Name::new("odm"),
-            PbrBundle {
-                mesh: meshes.add(odm.mesh.clone()),
-                material: materials.add(material),
-                ..default()
-            },
-            CurrentMap,
+            SpatialBundle::from_transform(transform),
+            odm.decoration_bounds,
         ))
         .with_children(|parent| {
+            let material = materials.add(material);
+            for (coord, mesh) in odm.chunks {
+                parent.spawn((
+                    Name::new("terrain"),
+                    PbrBundle {
+                        mesh: meshes.add(mesh),
+                        material: material.clone(),
+                        visibility: terrain_mode.textured_visibility(),
+                        ..default()
+                    },
+                    TerrainMesh,
+                    TerrainAtlas(odm.atlas_size),
+                    coord,
+                ));
+            }
+            parent.spawn((
+                Name::new("terrain_lines"),
+                PbrBundle {
+                    mesh: meshes.add(odm.lines.clone()),
+                    // colored from `WireframeSettings` once spawned
+                    material: materials.add(StandardMaterial {
+                        base_color: Color::WHITE,
+                        unlit: true,
+                        ..default()
+                    }),
+                    visibility: terrain_mode.lines_visibility(),
+                    ..default()
+                },
+                TerrainLines,
+            ));
+
             for m in odm.models {
                 parent.spawn((
                     Name::new("model"),
@@ -311,6 +722,27 @@ fn change_odm(
                 ));
             }
 
+            let (width, depth) = odm.map.size();
+            parent.spawn(water_bundle(
+                Vec2::new(width as f32, depth as f32) * ODM_TILE_SCALE,
+                meshes,
+                materials,
+            ));
+
+            for d in odm.decorations {
+                let material = materials.add(decoration_material(images.add(d.texture)));
+                for mesh in d.meshes {
+                    parent.spawn((
+                        Name::new("decoration"),
+                        PbrBundle {
+                            mesh: meshes.add(mesh),
+                            material: material.clone(),
+                            ..default()
+                        },
+                    ));
+                }
+            }
+
             // let sprite_manager =
             //     lod::billboard::BillboardManager::new(&settings.lod_manager).unwrap();
 
@@ -349,11 +781,238 @@ fn change_odm(
             //         },
             //     ));
             // }
-        });
+        })
+        .id();
+
+    (entity, odm.map)
+}
+
+#[allow(clippy::too_many_arguments)]
+fn change_odm(
+    mut commands: Commands,
+    mut settings: ResMut<WorldSettings>,
+    mut images: ResMut<Assets<Image>>,
+    mut meshes: ResMut<Assets<Mesh>>,
+    mut materials: ResMut<Assets<StandardMaterial>>,
+    mut movement_settings: ResMut<MovementSettings>,
+    mut camera: Query<&mut Transform, With<FlyCam>>,
+    terrain_mode: Res<TerrainMode>,
+    //mut billboard_textures: ResMut<Assets<BillboardTextureBundle>>,
+    query: Query<Entity, With<CurrentMap>>,
+    mut startup_view: Option<ResMut<StartupView>>,
+) {
+    if !settings.odm_changed {
+        return;
+    }
+
+    if let Some(map) = startup_view.as_mut().and_then(|view| view.take_map()) {
+        settings.current_odm = map;
+    }
+
+    for e in &query {
+        commands.entity(e).despawn_recursive();
+    }
+
+    let odm = OdmBundle::new(
+        &settings.lod_manager,
+        settings.current_odm.to_string().as_str(),
+        &settings,
+    );
+
+    if odm.is_err() {
+        return;
+    }
+    let odm = odm.unwrap();
+    let triangles: usize = odm
+        .chunks
+        .iter()
+        .map(|(_, mesh)| match mesh.indices() {
+            Some(indices) => indices.len() / 3,
+            None => mesh.count_vertices() / 3,
+        })
+        .sum();
+    info!(
+        "Terrain: {} triangles in {} chunks",
+        triangles,
+        odm.chunks.len()
+    );
+
+    let (play_width, play_depth) = odm.map.play_size();
+    movement_settings.max_xz = ODM_TILE_SCALE * play_width.max(play_depth) as f32 / 2.0;
+
+    // Start from the game's point of view, the camera looks along -z
+    if let (Some(start), Some(direction)) = (odm.map.party_start(), odm.map.party_view_direction())
+    {
+        let position = start.data.position;
+        for mut transform in &mut camera {
+            transform.translation = Vec3::new(
+                position[0] as f32,
+                position[2] as f32 + PARTY_EYE_HEIGHT,
+                -position[1] as f32,
+            );
+            transform.rotation = Quat::from_rotation_y(direction - std::f32::consts::FRAC_PI_2);
+        }
+    } else {
+        // Frame the whole terrain from above its southern edge
+        let (min, max) = odm.aabb;
+        let center = (min + max) / 2.0;
+        let eye = Vec3::new(center.x, max.y + (max.x - min.x) / 2.0, max.z);
+        for mut transform in &mut camera {
+            *transform = Transform::from_translation(eye).looking_at(center, Vec3::Y);
+        }
+    }
+    if let Some(startup_view) = startup_view.as_mut() {
+        for mut transform in &mut camera {
+            startup_view.apply_camera(&mut transform);
+        }
+    }
+
+    let (entity, map) = spawn_odm(
+        &mut commands,
+        odm,
+        Transform::IDENTITY,
+        &mut images,
+        &mut meshes,
+        &mut materials,
+        *terrain_mode,
+    );
+    commands.entity(entity).insert(CurrentMap);
 
+    commands.insert_resource(LoadedOdm(map));
     settings.odm_changed = false;
 }
 
+/// Which of the terrain meshes are shown, textured triangles, lines or both.
+#[derive(Resource, Default, Clone, Copy, PartialEq, Debug)]
+pub(super) enum TerrainMode {
+    #[default]
+    Textured,
+    Lines,
+    Both,
+}
+
+impl TerrainMode {
+    fn next(self) -> Self {
+        match self {
+            TerrainMode::Textured => TerrainMode::Lines,
+            TerrainMode::Lines => TerrainMode::Both,
+            TerrainMode::Both => TerrainMode::Textured,
+        }
+    }
+
+    fn textured_visibility(self) -> Visibility {
+        if self == TerrainMode::Lines {
+            Visibility::Hidden
+        } else {
+            Visibility::Inherited
+        }
+    }
+
+    fn lines_visibility(self) -> Visibility {
+        if self == TerrainMode::Textured {
+            Visibility::Hidden
+        } else {
+            Visibility::Inherited
+        }
+    }
+}
+
+#[derive(Component)]
+pub(super) struct TerrainMesh;
+
+/// Position of a terrain chunk, in chunks from the north-west corner of the map.
+#[derive(Component, Clone, Copy, Debug, PartialEq, Eq)]
+pub(super) struct ChunkCoord {
+    pub x: usize,
+    pub z: usize,
+}
+
+#[derive(Component)]
+pub(super) struct TerrainAtlas(pub UVec2);
+
+#[derive(Component)]
+pub(super) struct TerrainLines;
+
+fn cycle_terrain_mode(
+    keys: Res<ButtonInput<KeyCode>>,
+    key_bindings: Res<KeyBindings>,
+    mut terrain_mode: ResMut<TerrainMode>,
+    mut textured: Query<&mut Visibility, (With<TerrainMesh>, Without<TerrainLines>)>,
+    mut lines: Query<&mut Visibility, (With<TerrainLines>, Without<TerrainMesh>)>,
+) {
+    if !keys.just_pressed(key_bindings.cycle_terrain_mode) {
+        return;
+    }
+    *terrain_mode = terrain_mode.next();
+    info!("Terrain mode: {:?}", *terrain_mode);
+
+    for mut visibility in &mut textured {
+        *visibility = terrain_mode.textured_visibility();
+    }
+    for mut visibility in &mut lines {
+        *visibility = terrain_mode.lines_visibility();
+    }
+}
+
+/// Cycles through the height color maps and back to the textured terrain, reloading the map.
+fn cycle_height_color_map(
+    keys: Res<ButtonInput<KeyCode>>,
+    key_bindings: Res<KeyBindings>,
+    mut settings: ResMut<WorldSettings>,
+) {
+    if !keys.just_pressed(key_bindings.cycle_height_color_map) {
+        return;
+    }
+    let next = match settings.height_color_map {
+        None => ColorMap::ALL.first().copied(),
+        Some(current) => ColorMap::ALL
+            .iter()
+            .skip_while(|&&c| c != current)
+            .nth(1)
+            .copied(),
+    };
+    settings.height_color_map = next;
+    settings.odm_changed = true;
+    info!("Height color map: {:?}", next);
+}
+
+/// Tessellation factors cycled through at runtime.
+const TESSELLATIONS: [usize; 3] = [1, 2, 4];
+
+/// Switches the terrain between smooth and flat shading, reloading the map.
+fn toggle_smooth_terrain(
+    keys: Res<ButtonInput<KeyCode>>,
+    key_bindings: Res<KeyBindings>,
+    mut settings: ResMut<WorldSettings>,
+) {
+    if !keys.just_pressed(key_bindings.toggle_smooth_terrain) {
+        return;
+    }
+    settings.smooth_terrain = !settings.smooth_terrain;
+    settings.odm_changed = true;
+    info!("Smooth terrain: {}", settings.smooth_terrain);
+}
+
+/// Cycles the terrain tessellation factor, reloading the map.
+fn cycle_tessellation(
+    keys: Res<ButtonInput<KeyCode>>,
+    key_bindings: Res<KeyBindings>,
+    mut settings: ResMut<WorldSettings>,
+) {
+    if !keys.just_pressed(key_bindings.cycle_tessellation) {
+        return;
+    }
+    let next = TESSELLATIONS
+        .iter()
+        .position(|&t| t == settings.tessellation)
+        .map_or(TESSELLATIONS[0], |i| {
+            TESSELLATIONS[(i + 1) % TESSELLATIONS.len()]
+        });
+    settings.tessellation = next;
+    settings.odm_changed = true;
+    info!("Terrain tessellation: {}", next);
+}
+
 fn change_map_input(keys: Res<ButtonInput<KeyCode>>, mut settings: ResMut<WorldSettings>) {
     let new_map = if keys.just_pressed(KeyCode::KeyJ) {
         settings.current_odm.go_north()
@@ -378,13 +1037,133 @@ pub struct OdmPlugin;
 
 impl Plugin for OdmPlugin {
     fn build(&self, app: &mut App) {
-        app
+        app.init_resource::<TerrainMode>()
             //.add_plugins(BillboardPlugin)
             .add_systems(
                 Update,
-                (change_map_input, change_odm).run_if(in_state(GameState::Game)),
+                (
+                    change_map_input,
+                    change_odm,
+                    cycle_terrain_mode,
+                    cycle_height_color_map,
+                    cycle_tessellation,
+                    toggle_smooth_terrain,
+                )
+                    .run_if(in_state(GameState::Game)),
             )
             .add_systems(OnEnter(GameState::Game), odm_setup)
             .add_systems(OnExit(GameState::Game), despawn_all::<CurrentMap>);
     }
 }
+
+#[cfg(test)]
+mod tests {
+    use bevy::render::render_resource::{Extent3d, TextureDimension, TextureFormat};
+
+    use super::*;
+
+    #[test]
+    fn cap_decorations_works() {
+        // the height is ignored, only the distance on the ground plane counts
+        let placements: Vec<DecorationPlacement> = [900.0, -100.0, 300.0, 50.0, -700.0]
+            .into_iter()
+            .enumerate()
+            .map(|(i, x)| (i, Vec3::new(x, 1000.0 * i as f32, 20.0), (64.0, 64.0)))
+            .collect();
+
+        let mut capped = placements.clone();
+        cap_decorations(&mut capped, 3, Vec3::ZERO);
+        let kept: Vec<usize> = capped.iter().map(|(sprite_id, _, _)| *sprite_id).collect();
+        assert_eq!(kept, [3, 1, 2]);
+
+        let mut capped = placements.clone();
+        cap_decorations(&mut capped, 2, Vec3::new(800.0, 0.0, 0.0));
+        let kept: Vec<usize> = capped.iter().map(|(sprite_id, _, _)| *sprite_id).collect();
+        assert_eq!(kept, [0, 2]);
+
+        let mut uncapped = placements.clone();
+        cap_decorations(&mut uncapped, placements.len(), Vec3::ZERO);
+        assert_eq!(uncapped, placements);
+    }
+
+    #[test]
+    fn terrain_grid_mesh_works() {
+        // flat 4x4 odm without models, billboards, decorations or spawn points
+        let mut data = vec![0; 176 + 3 * 16];
+        data.extend(0u32.to_le_bytes().repeat(3));
+        data.extend(vec![0; 16 * 4]);
+        data.extend(0u32.to_le_bytes());
+        let map = Odm::try_from_with_size(&data, (4, 4)).unwrap();
+        let tile_table = TileTable::new(std::array::from_fn(|_| "grastyl".to_string()));
+        let odm_data = OdmData::new(&map, &tile_table);
+        let quads = odm_data.indices.len() / 6;
+        let colors = || Some(odm_data.height_colors(ColorMap::default()));
+
+        // height colored smooth terrain shares the grid vertices
+        let mesh =
+            OdmBundle::generate_terrain_mesh(OdmData::new(&map, &tile_table), colors(), true);
+        assert_eq!(mesh.count_vertices(), odm_data.positions.len());
+        assert_eq!(mesh.indices().unwrap().len(), odm_data.indices.len());
+        assert!(mesh.attribute(Mesh::ATTRIBUTE_UV_0).is_none());
+
+        // the chunks share the vertices on their borders
+        let chunks = OdmBundle::generate_terrain_chunks(&odm_data, colors(), true, 1);
+        assert_eq!(chunks.len(), quads);
+        assert!(chunks.iter().all(|(_, mesh)| mesh.count_vertices() == 4));
+
+        // the textured terrain keeps the 4 corners of every quad for the tile uvs
+        let mesh = OdmBundle::generate_terrain_mesh(OdmData::new(&map, &tile_table), None, true);
+        assert_eq!(mesh.count_vertices(), quads * 4);
+        assert!(mesh.attribute(Mesh::ATTRIBUTE_UV_0).is_some());
+    }
+
+    #[test]
+    fn mipmapped_image_works() {
+        let level = |size: u32, value: u8| {
+            Image::new_fill(
+                Extent3d {
+                    width: size,
+                    height: size,
+                    depth_or_array_layers: 1,
+                },
+                TextureDimension::D2,
+                &[value; 4],
+                TextureFormat::Rgba8UnormSrgb,
+                RenderAssetUsages::RENDER_WORLD,
+            )
+        };
+        let image = mipmapped_image(vec![level(4, 1), level(2, 2), level(1, 3)]).unwrap();
+        assert_eq!(image.texture_descriptor.mip_level_count, 3);
+        assert_eq!(image.size(), UVec2::new(4, 4));
+        assert_eq!(image.data.len(), (16 + 4 + 1) * 4);
+        assert!(image.data[..64].iter().all(|&v| v == 1));
+        assert!(image.data[64..80].iter().all(|&v| v == 2));
+        assert!(image.data[80..].iter().all(|&v| v == 3));
+
+        assert!(mipmapped_image(Vec::new()).is_none());
+    }
+
+    #[test]
+    fn odm_name_works() {
+        for name in ["oute3", "oute3.odm", "OUTE3.ODM"] {
+            let odm_name = OdmName::try_from(name).unwrap();
+            assert_eq!((odm_name.x, odm_name.y), ('e', '3'));
+        }
+        assert_eq!(OdmName::try_from("outa1").unwrap().to_string(), "outa1.odm");
+
+        for name in [
+            "",
+            "out",
+            "oute",
+            "xyze3",
+            "abce3.blv",
+            "oute3.blv",
+            "oute3x",
+            "oute33.odm",
+            "outf3",
+            "oute4",
+        ] {
+            assert!(OdmName::try_from(name).is_err(), "{:?} was accepted", name);
+        }
+    }
+}
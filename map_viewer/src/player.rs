@@ -1,9 +1,12 @@
+use bevy::core_pipeline::tonemapping::Tonemapping;
 use bevy::ecs::event::{Events, ManualEventReader};
 use bevy::input::mouse::MouseMotion;
 use bevy::prelude::*;
+use bevy::render::camera::ScalingMode;
 use bevy::window::{CursorGrabMode, PrimaryWindow};
+use lod::odm::Odm;
 
-use crate::GameState;
+use crate::{odm::LoadedOdm, GameState};
 
 /// Keeps track of mouse motion events, pitch, and yaw
 #[derive(Resource, Default)]
@@ -19,6 +22,13 @@ pub struct MovementSettings {
     pub rotation_speed: f32,
     pub max_xz: f32,
     pub max_y: f32,
+    /// Keep the camera at least `ground_clip_margin` above the terrain
+    pub prevent_ground_clip: bool,
+    pub ground_clip_margin: f32,
+    /// Longest frame time in seconds used to move the camera, so a stall after loading a map
+    /// or unfocusing the window doesn't make it jump. Rotation is applied per frame and is
+    /// not affected by stalls.
+    pub max_delta: f32,
 }
 
 impl Default for MovementSettings {
@@ -29,10 +39,20 @@ impl Default for MovementSettings {
             rotation_speed: 3.5,
             max_xz: 512.0 * 64.0,
             max_y: 512.0 * 64.0,
+            prevent_ground_clip: false,
+            ground_clip_margin: 128.0,
+            max_delta: 0.1,
         }
     }
 }
 
+impl MovementSettings {
+    /// Frame time in seconds used to move the camera, at most `max_delta`.
+    fn clamp_delta(&self, delta: f32) -> f32 {
+        delta.min(self.max_delta)
+    }
+}
+
 /// Key configuration
 #[derive(Resource)]
 pub struct KeyBindings {
@@ -43,6 +63,12 @@ pub struct KeyBindings {
     pub move_ascend: KeyCode,
     pub move_descend: KeyCode,
     pub toggle_grab_cursor: KeyCode,
+    pub dump_transform: KeyCode,
+    pub enter_survey: KeyCode,
+    pub exit_survey: KeyCode,
+    pub toggle_orbit: KeyCode,
+    pub cycle_tonemapping: KeyCode,
+    pub toggle_isometric: KeyCode,
 }
 
 impl Default for KeyBindings {
@@ -55,8 +81,159 @@ impl Default for KeyBindings {
             move_ascend: KeyCode::PageUp,
             move_descend: KeyCode::Insert,
             toggle_grab_cursor: KeyCode::Escape,
+            dump_transform: KeyCode::KeyP,
+            enter_survey: KeyCode::F5,
+            exit_survey: KeyCode::F6,
+            toggle_orbit: KeyCode::KeyO,
+            cycle_tonemapping: KeyCode::KeyY,
+            toggle_isometric: KeyCode::KeyI,
+        }
+    }
+}
+
+/// Circles the camera around the map center looking inward, `speed` is in radians per second
+#[derive(Resource, Reflect)]
+#[reflect(Resource)]
+pub struct OrbitSettings {
+    pub active: bool,
+    pub radius: f32,
+    pub height: f32,
+    pub speed: f32,
+}
+
+impl Default for OrbitSettings {
+    fn default() -> Self {
+        Self {
+            active: false,
+            radius: 512.0 * 48.0,
+            height: 512.0 * 16.0,
+            speed: 0.1,
+        }
+    }
+}
+
+/// Tonemapping curve and depth range of the camera.
+/// Bevy already renders perspective cameras with an infinite reverse-Z projection, depth 1
+/// at the near plane going to 0 at infinity, so the float depth stays precise over the whole
+/// map and there is no far plane. The near plane is what's left to tune: pushing it away
+/// from the default 0.1 keeps distant terrain from z-fighting.
+#[derive(Resource, Reflect)]
+#[reflect(Resource)]
+pub struct RenderSettings {
+    /// The default can wash out the terrain colors
+    pub tonemapping: Tonemapping,
+    /// Near plane of the perspective projection, well below `MovementSettings::ground_clip_margin`
+    pub near: f32,
+}
+
+impl Default for RenderSettings {
+    fn default() -> Self {
+        Self {
+            tonemapping: Tonemapping::default(),
+            near: 16.0,
+        }
+    }
+}
+
+/// Tonemappers cycled through with `KeyBindings::cycle_tonemapping`
+const TONEMAPPINGS: [Tonemapping; 8] = [
+    Tonemapping::None,
+    Tonemapping::Reinhard,
+    Tonemapping::ReinhardLuminance,
+    Tonemapping::AcesFitted,
+    Tonemapping::AgX,
+    Tonemapping::SomewhatBoringDisplayTransform,
+    Tonemapping::TonyMcMapface,
+    Tonemapping::BlenderFilmic,
+];
+
+/// Flies the camera to a decoration of the map, `object` is its index in `Odm::objects`
+/// and can be picked from the inspector.
+#[derive(Resource, Reflect)]
+#[reflect(Resource)]
+pub struct FocusSettings {
+    pub object: Option<usize>,
+    /// Distance kept from the object
+    pub distance: f32,
+    /// Length of the flight, in seconds
+    pub duration: f32,
+}
+
+impl Default for FocusSettings {
+    fn default() -> Self {
+        Self {
+            object: None,
+            distance: 1536.0,
+            duration: 1.5,
+        }
+    }
+}
+
+/// Camera flight in progress toward the focused object
+#[derive(Resource, Default)]
+struct FocusFlight {
+    flight: Option<(Transform, Transform, f32)>,
+}
+
+/// Camera height used for the survey overview
+const SURVEY_HEIGHT: f32 = 512.0 * 48.0;
+
+/// Render and movement state saved when entering survey mode, restored when leaving it
+struct SurveySnapshot {
+    transform: Transform,
+    projection: Projection,
+    fog: FogSettings,
+    max_xz: f32,
+    max_y: f32,
+    prevent_ground_clip: bool,
+}
+
+impl SurveySnapshot {
+    fn new(
+        transform: &Transform,
+        projection: &Projection,
+        fog: &FogSettings,
+        settings: &MovementSettings,
+    ) -> Self {
+        Self {
+            transform: *transform,
+            projection: projection.clone(),
+            fog: fog.clone(),
+            max_xz: settings.max_xz,
+            max_y: settings.max_y,
+            prevent_ground_clip: settings.prevent_ground_clip,
         }
     }
+
+    fn restore(
+        self,
+        transform: &mut Transform,
+        projection: &mut Projection,
+        fog: &mut FogSettings,
+        settings: &mut MovementSettings,
+    ) {
+        *transform = self.transform;
+        *projection = self.projection;
+        *fog = self.fog;
+        settings.max_xz = self.max_xz;
+        settings.max_y = self.max_y;
+        settings.prevent_ground_clip = self.prevent_ground_clip;
+    }
+}
+
+/// Survey mode shows the whole map from above without fog or movement bounds
+#[derive(Resource, Default)]
+struct SurveyMode {
+    saved: Option<SurveySnapshot>,
+}
+
+/// Elevation of the classic isometric view, atan(1/√2) or 35.264°
+const ISOMETRIC_ELEVATION: f32 = 0.615_479_7;
+
+/// Orthographic view of the whole map along its diagonal, saved like survey mode
+#[derive(Resource, Default)]
+struct IsometricMode {
+    saved: Option<SurveySnapshot>,
 }
 
 /// Used in queries when you want flycams and not other cameras
@@ -109,6 +286,7 @@ fn player_controls(
     primary_window: Query<&Window, With<PrimaryWindow>>,
     settings: Res<MovementSettings>,
     key_bindings: Res<KeyBindings>,
+    odm: Option<Res<LoadedOdm>>,
     mut query: Query<(&FlyCam, &mut Transform)>,
 ) {
     if let Ok(window) = primary_window.get_single() {
@@ -133,6 +311,9 @@ fn player_controls(
                             transform.rotate(rotation);
                         } else {
                             handle_movement(&settings, &key_bindings, key, &mut transform, &time);
+                            if let Some(odm) = &odm {
+                                limit_movement_to_ground(&settings, &odm.0, &mut transform);
+                            }
                         }
                     }
                 }
@@ -159,7 +340,8 @@ fn handle_movement(
         _ => return, // Ignore keys that are not for movement
     };
 
-    transform.translation += movement * time.delta_seconds() * settings.speed;
+    let delta = settings.clamp_delta(time.delta_seconds());
+    transform.translation += movement * delta * settings.speed;
 
     limit_movement_to_game_area(settings, transform);
 }
@@ -180,6 +362,18 @@ fn limit_movement_to_game_area(settings: &Res<'_, MovementSettings>, transform:
     }
 }
 
+// Keep the camera above the terrain
+fn limit_movement_to_ground(settings: &MovementSettings, odm: &Odm, transform: &mut Transform) {
+    if !settings.prevent_ground_clip {
+        return;
+    }
+    let ground = odm.height_at(transform.translation.x, transform.translation.z)
+        + settings.ground_clip_margin;
+    if transform.translation.y < ground {
+        transform.translation.y = ground;
+    }
+}
+
 /// Handles looking around if cursor is locked
 fn player_look(
     settings: Res<MovementSettings>,
@@ -227,6 +421,237 @@ fn cursor_grab(
     }
 }
 
+/// Logs the camera transform so a viewpoint can be copied into the code
+fn dump_camera_transform(
+    keys: Res<ButtonInput<KeyCode>>,
+    key_bindings: Res<KeyBindings>,
+    query: Query<&Transform, With<FlyCam>>,
+) {
+    if !keys.just_pressed(key_bindings.dump_transform) {
+        return;
+    }
+    for transform in &query {
+        let t = transform.translation;
+        let r = transform.rotation;
+        let (yaw, pitch, roll) = r.to_euler(EulerRot::YXZ);
+        info!(
+            "Camera transform: Transform::from_xyz({:.1}, {:.1}, {:.1}).with_rotation(Quat::from_xyzw({}, {}, {}, {}))",
+            t.x, t.y, t.z, r.x, r.y, r.z, r.w
+        );
+        info!(
+            "Camera euler (YXZ): yaw {:.2}°, pitch {:.2}°, roll {:.2}°",
+            yaw.to_degrees(),
+            pitch.to_degrees(),
+            roll.to_degrees()
+        );
+    }
+}
+
+/// Switches between the survey overview and the previous view
+fn survey_mode(
+    keys: Res<ButtonInput<KeyCode>>,
+    key_bindings: Res<KeyBindings>,
+    mut survey: ResMut<SurveyMode>,
+    isometric: Res<IsometricMode>,
+    mut settings: ResMut<MovementSettings>,
+    mut query: Query<(&mut Transform, &mut Projection, &mut FogSettings), With<FlyCam>>,
+) {
+    let Ok((mut transform, mut projection, mut fog)) = query.get_single_mut() else {
+        return;
+    };
+
+    if keys.just_pressed(key_bindings.enter_survey)
+        && survey.saved.is_none()
+        && isometric.saved.is_none()
+    {
+        survey.saved = Some(SurveySnapshot::new(
+            &transform,
+            &projection,
+            &fog,
+            &settings,
+        ));
+
+        settings.max_xz = f32::MAX;
+        settings.max_y = f32::MAX;
+        settings.prevent_ground_clip = false;
+        fog.color.set_a(0.0);
+        if let Projection::Perspective(perspective) = projection.as_mut() {
+            perspective.far *= 4.0;
+        }
+        let position = Vec3::new(
+            transform.translation.x,
+            SURVEY_HEIGHT,
+            transform.translation.z,
+        );
+        *transform = Transform::from_translation(position).looking_at(Vec3::ZERO, Vec3::Y);
+    } else if keys.just_pressed(key_bindings.exit_survey) {
+        if let Some(saved) = survey.saved.take() {
+            saved.restore(&mut transform, &mut projection, &mut fog, &mut settings);
+        }
+    }
+}
+
+/// Toggles an orthographic camera looking at the map center from 45° around and
+/// `ISOMETRIC_ELEVATION` above, zoomed to fit the whole map.
+fn isometric_mode(
+    keys: Res<ButtonInput<KeyCode>>,
+    key_bindings: Res<KeyBindings>,
+    survey: Res<SurveyMode>,
+    mut isometric: ResMut<IsometricMode>,
+    mut settings: ResMut<MovementSettings>,
+    mut query: Query<(&mut Transform, &mut Projection, &mut FogSettings), With<FlyCam>>,
+) {
+    if !keys.just_pressed(key_bindings.toggle_isometric) {
+        return;
+    }
+    let Ok((mut transform, mut projection, mut fog)) = query.get_single_mut() else {
+        return;
+    };
+
+    if let Some(saved) = isometric.saved.take() {
+        saved.restore(&mut transform, &mut projection, &mut fog, &mut settings);
+        return;
+    }
+    if survey.saved.is_some() {
+        return;
+    }
+    isometric.saved = Some(SurveySnapshot::new(
+        &transform,
+        &projection,
+        &fog,
+        &settings,
+    ));
+
+    let map_size = 2.0 * settings.max_xz;
+    settings.max_xz = f32::MAX;
+    settings.max_y = f32::MAX;
+    settings.prevent_ground_clip = false;
+    fog.color.set_a(0.0);
+
+    // the map diagonal spans the screen width, the ground is foreshortened by the elevation
+    let width = map_size * std::f32::consts::SQRT_2 * 1.05;
+    *projection = Projection::Orthographic(OrthographicProjection {
+        near: 0.0,
+        far: 4.0 * map_size,
+        scaling_mode: ScalingMode::AutoMin {
+            min_width: width,
+            min_height: width * ISOMETRIC_ELEVATION.sin(),
+        },
+        ..default()
+    });
+    // (1, 1, 1) is 45° around both ground axes and atan(1/√2) above the ground
+    let position = Vec3::ONE.normalize() * 2.0 * map_size;
+    *transform = Transform::from_translation(position).looking_at(Vec3::ZERO, Vec3::Y);
+}
+
+fn orbit_camera(
+    keys: Res<ButtonInput<KeyCode>>,
+    key_bindings: Res<KeyBindings>,
+    time: Res<Time>,
+    mut settings: ResMut<OrbitSettings>,
+    mut angle: Local<f32>,
+    mut query: Query<&mut Transform, With<FlyCam>>,
+) {
+    if keys.just_pressed(key_bindings.toggle_orbit) {
+        settings.active = !settings.active;
+    }
+    if !settings.active {
+        return;
+    }
+
+    *angle = (*angle + settings.speed * time.delta_seconds()) % std::f32::consts::TAU;
+    let position = Vec3::new(
+        settings.radius * angle.cos(),
+        settings.height,
+        settings.radius * angle.sin(),
+    );
+    for mut transform in &mut query {
+        *transform = Transform::from_translation(position).looking_at(Vec3::ZERO, Vec3::Y);
+    }
+}
+
+/// Starts a flight when a new object is focused, the camera keeps its heading and
+/// looks down at the object from `FocusSettings::distance`.
+fn start_focus(
+    settings: Res<FocusSettings>,
+    odm: Option<Res<LoadedOdm>>,
+    mut focus: ResMut<FocusFlight>,
+    query: Query<&Transform, With<FlyCam>>,
+) {
+    if !settings.is_changed() {
+        return;
+    }
+    let (Some(index), Some(odm), Ok(transform)) = (settings.object, odm, query.get_single()) else {
+        return;
+    };
+    let Some(object) = odm.0.objects().get(index) else {
+        warn!("No object {} on the map", index);
+        return;
+    };
+    let [x, y, z] = object.data.position;
+    let target = Vec3::new(x as f32, z as f32, -y as f32);
+
+    let heading = (transform.translation - target)
+        .xz()
+        .try_normalize()
+        .unwrap_or(Vec2::X);
+    let offset = Vec3::new(heading.x, 0.5, heading.y).normalize() * settings.distance;
+    let end = Transform::from_translation(target + offset).looking_at(target, Vec3::Y);
+    focus.flight = Some((*transform, end, 0.0));
+}
+
+fn fly_to_focus(
+    time: Res<Time>,
+    settings: Res<FocusSettings>,
+    mut focus: ResMut<FocusFlight>,
+    mut query: Query<&mut Transform, With<FlyCam>>,
+) {
+    let Some((start, end, elapsed)) = focus.flight.as_mut() else {
+        return;
+    };
+    *elapsed += time.delta_seconds();
+    let t = (*elapsed / settings.duration.max(f32::EPSILON)).min(1.0);
+    let t = t * t * (3.0 - 2.0 * t);
+    for mut transform in &mut query {
+        transform.translation = start.translation.lerp(end.translation, t);
+        transform.rotation = start.rotation.slerp(end.rotation, t);
+    }
+    if t >= 1.0 {
+        focus.flight = None;
+    }
+}
+
+fn cycle_tonemapping(
+    keys: Res<ButtonInput<KeyCode>>,
+    key_bindings: Res<KeyBindings>,
+    mut settings: ResMut<RenderSettings>,
+) {
+    if keys.just_pressed(key_bindings.cycle_tonemapping) {
+        let index = TONEMAPPINGS
+            .iter()
+            .position(|t| *t == settings.tonemapping)
+            .map_or(0, |i| (i + 1) % TONEMAPPINGS.len());
+        settings.tonemapping = TONEMAPPINGS[index];
+        info!("Tonemapping: {:?}", settings.tonemapping);
+    }
+}
+
+fn apply_render_settings(
+    settings: Res<RenderSettings>,
+    mut query: Query<(&mut Tonemapping, &mut Projection), With<FlyCam>>,
+    added: Query<(), Added<FlyCam>>,
+) {
+    if !settings.is_changed() && added.is_empty() {
+        return;
+    }
+    for (mut tonemapping, mut projection) in &mut query {
+        *tonemapping = settings.tonemapping;
+        if let Projection::Perspective(perspective) = projection.as_mut() {
+            perspective.near = settings.near;
+        }
+    }
+}
+
 /// Contains everything needed to add first-person fly camera behaviour to your game
 pub struct PlayerPlugin;
 impl Plugin for PlayerPlugin {
@@ -234,10 +659,43 @@ impl Plugin for PlayerPlugin {
         app.init_resource::<InputState>()
             .init_resource::<MovementSettings>()
             .init_resource::<KeyBindings>()
+            .init_resource::<SurveyMode>()
+            .init_resource::<IsometricMode>()
+            .init_resource::<OrbitSettings>()
+            .register_type::<OrbitSettings>()
+            .init_resource::<RenderSettings>()
+            .register_type::<RenderSettings>()
+            .init_resource::<FocusSettings>()
+            .register_type::<FocusSettings>()
+            .init_resource::<FocusFlight>()
             .add_systems(OnEnter(GameState::Game), setup_camera)
             .add_systems(
                 Update,
-                (player_controls, player_look, cursor_grab).run_if(in_state(GameState::Game)),
+                (
+                    player_controls,
+                    player_look,
+                    cursor_grab,
+                    dump_camera_transform,
+                    survey_mode,
+                    isometric_mode.after(survey_mode),
+                    orbit_camera.after(player_controls).after(player_look),
+                    (cycle_tonemapping, apply_render_settings).chain(),
+                    (start_focus, fly_to_focus).chain().after(orbit_camera),
+                )
+                    .run_if(in_state(GameState::Game)),
             );
     }
 }
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn clamp_delta_works() {
+        let settings = MovementSettings::default();
+        assert_eq!(settings.clamp_delta(0.016), 0.016);
+        assert_eq!(settings.clamp_delta(settings.max_delta), settings.max_delta);
+        assert_eq!(settings.clamp_delta(2.5), settings.max_delta);
+    }
+}
@@ -0,0 +1,11 @@
+use std::error::Error;
+
+use lod::{odm::Odm, LodManager};
+
+/// Prints the summary of `map_name` from [`Odm::report`].
+pub fn print_report(map_name: &str) -> Result<(), Box<dyn Error>> {
+    let lod_manager = LodManager::new(lod::get_lod_path())?;
+    let map = Odm::new(&lod_manager, map_name)?;
+    print!("{}", map.report());
+    Ok(())
+}
@@ -1,21 +1,30 @@
 use bevy::prelude::*;
+use std::path::PathBuf;
 
 use lod::{
+    colormap::ColorMap,
     odm::{ODM_HEIGHT_SCALE, ODM_PLAY_SIZE, ODM_TILE_SCALE},
     LodManager,
 };
 
 use crate::{
+    cli::StartupView,
     despawn_all,
     odm::{OdmName, OdmPlugin},
     player::{MovementSettings, PlayerPlugin},
     GameState,
 };
 
-use self::{sky::SkyPlugin, sun::SunPlugin};
+use self::{
+    clutter::ClutterPlugin, sky::SkyPlugin, sun::SunPlugin, triplanar::TriplanarPlugin,
+    water::WaterPlugin,
+};
 
+pub(crate) mod clutter;
 pub(crate) mod sky;
 pub(crate) mod sun;
+pub(crate) mod triplanar;
+pub(crate) mod water;
 
 #[derive(Component)]
 pub(super) struct InWorld;
@@ -25,6 +34,30 @@ pub(super) struct WorldSettings {
     pub lod_manager: LodManager,
     pub current_odm: OdmName,
     pub odm_changed: bool,
+    /// Draw all the decorations of the map from a single texture atlas and mesh
+    pub batch_decorations: bool,
+    /// Spawn at most this many decorations, keeping the ones nearest to the map center
+    pub max_decorations: Option<usize>,
+    /// Bake a thin border around every tile of the terrain atlas
+    pub show_tile_borders: bool,
+    /// Use a pre-baked terrain atlas instead of generating it from the lod files
+    pub custom_atlas: Option<CustomAtlas>,
+    /// Debug only, also save the generated terrain atlas to this file.
+    /// The texture is uploaded from memory either way.
+    pub dump_atlas: Option<PathBuf>,
+    /// Color the terrain by height with this gradient instead of texturing it
+    pub height_color_map: Option<ColorMap>,
+    /// Split every terrain tile in a `tessellation` x `tessellation` grid
+    pub tessellation: usize,
+    /// Shade the terrain with per-vertex normals averaged from the adjacent triangles
+    /// instead of one normal per triangle
+    pub smooth_terrain: bool,
+}
+
+/// Paths of a terrain atlas image and of its json layout
+pub(super) struct CustomAtlas {
+    pub image: PathBuf,
+    pub layout: PathBuf,
 }
 
 impl Default for WorldSettings {
@@ -33,6 +66,14 @@ impl Default for WorldSettings {
             lod_manager: LodManager::new(lod::get_lod_path()).expect("unable to load lod files"),
             current_odm: OdmName::default(),
             odm_changed: true,
+            batch_decorations: true,
+            max_decorations: None,
+            show_tile_borders: false,
+            custom_atlas: None,
+            dump_atlas: None,
+            height_color_map: None,
+            tessellation: 1,
+            smooth_terrain: true,
         }
     }
 }
@@ -41,13 +82,25 @@ pub struct WorldPlugin;
 
 impl Plugin for WorldPlugin {
     fn build(&self, app: &mut App) {
-        app.init_resource::<WorldSettings>()
+        let mut settings = WorldSettings::default();
+        if let Some(startup_view) = app.world.get_resource::<StartupView>() {
+            startup_view.apply_settings(&mut settings);
+        }
+        app.insert_resource(settings)
             .insert_resource(MovementSettings {
                 max_xz: ODM_TILE_SCALE * ODM_PLAY_SIZE as f32 / 2.0,
                 max_y: ODM_TILE_SCALE * ODM_HEIGHT_SCALE / 2.0,
                 ..Default::default()
             })
-            .add_plugins((PlayerPlugin, SunPlugin, SkyPlugin, OdmPlugin))
+            .add_plugins((
+                PlayerPlugin,
+                SunPlugin,
+                SkyPlugin,
+                WaterPlugin,
+                ClutterPlugin,
+                TriplanarPlugin,
+                OdmPlugin,
+            ))
             .add_systems(OnExit(GameState::Game), despawn_all::<InWorld>);
     }
 }
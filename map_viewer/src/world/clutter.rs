@@ -0,0 +1,215 @@
+use bevy::{
+    prelude::*,
+    render::{
+        render_asset::RenderAssetUsages,
+        render_resource::{Extent3d, TextureDimension, TextureFormat},
+    },
+};
+use lod::odm::ODM_TILE_SCALE;
+
+use crate::{
+    despawn_all,
+    odm::{decoration_material, DecorationMeshData, LoadedOdm},
+    player::FlyCam,
+    GameState,
+};
+
+use super::WorldSettings;
+
+pub struct ClutterPlugin;
+
+impl Plugin for ClutterPlugin {
+    fn build(&self, app: &mut App) {
+        app.init_resource::<ClutterSettings>()
+            .register_type::<ClutterSettings>()
+            .add_systems(
+                Update,
+                (load_clutter, update_clutter)
+                    .chain()
+                    .run_if(in_state(GameState::Game)),
+            )
+            .add_systems(OnExit(GameState::Game), despawn_all::<Clutter>);
+    }
+}
+
+/// Grass scattered on the grass tiles around the camera, `density` is the number
+/// of tufts per tile next to the camera, thinning out to none at `radius` tiles.
+/// When `sprite` is not found a plain tuft of grass is drawn instead.
+#[derive(Resource, Reflect)]
+#[reflect(Resource)]
+pub struct ClutterSettings {
+    pub enabled: bool,
+    pub density: u32,
+    pub radius: u32,
+    pub size: f32,
+    pub sprite: String,
+}
+
+impl Default for ClutterSettings {
+    fn default() -> Self {
+        Self {
+            enabled: true,
+            density: 4,
+            radius: 10,
+            size: 96.0,
+            sprite: "grass".into(),
+        }
+    }
+}
+
+/// Grass tiles of the loaded map and the tile the clutter was generated around
+#[derive(Resource)]
+struct ClutterState {
+    grass: Vec<bool>,
+    size: (usize, usize),
+    material: Handle<StandardMaterial>,
+    cell: Option<(i32, i32)>,
+}
+
+#[derive(Component)]
+struct Clutter;
+
+fn load_clutter(
+    mut commands: Commands,
+    odm: Option<Res<LoadedOdm>>,
+    world_settings: Res<WorldSettings>,
+    settings: Res<ClutterSettings>,
+    mut images: ResMut<Assets<Image>>,
+    mut materials: ResMut<Assets<StandardMaterial>>,
+) {
+    let Some(odm) = odm else {
+        return;
+    };
+    if !odm.is_changed() && !settings.is_changed() {
+        return;
+    }
+
+    let tile_table = match odm.0.tile_table(&world_settings.lod_manager) {
+        Ok(tile_table) => tile_table,
+        Err(e) => {
+            warn!("No clutter for this map: {}", e);
+            return;
+        }
+    };
+    let grass = odm
+        .0
+        .tile_map
+        .iter()
+        .map(|&tile| tile_table.name(tile).starts_with("gras"))
+        .collect();
+
+    let image = match world_settings.lod_manager.sprite(&settings.sprite) {
+        Some(sprite) => Image::from_dynamic(sprite, true, RenderAssetUsages::RENDER_WORLD),
+        None => grass_tuft(),
+    };
+
+    commands.insert_resource(ClutterState {
+        grass,
+        size: odm.0.size(),
+        material: materials.add(decoration_material(images.add(image))),
+        cell: None,
+    });
+}
+
+fn update_clutter(
+    mut commands: Commands,
+    settings: Res<ClutterSettings>,
+    state: Option<ResMut<ClutterState>>,
+    odm: Option<Res<LoadedOdm>>,
+    camera: Query<&Transform, With<FlyCam>>,
+    mut meshes: ResMut<Assets<Mesh>>,
+    clutter: Query<Entity, With<Clutter>>,
+) {
+    let (Some(mut state), Some(odm), Ok(camera)) = (state, odm, camera.get_single()) else {
+        return;
+    };
+    let cell = (
+        (camera.translation.x / ODM_TILE_SCALE).floor() as i32,
+        (camera.translation.z / ODM_TILE_SCALE).floor() as i32,
+    );
+    if state.cell == Some(cell) && !state.is_changed() && !settings.is_changed() {
+        return;
+    }
+    state.cell = Some(cell);
+
+    for e in &clutter {
+        commands.entity(e).despawn_recursive();
+    }
+    if !settings.enabled || settings.radius == 0 {
+        return;
+    }
+
+    let (width, depth) = state.size;
+    let radius = settings.radius as i32;
+    let mut mesh_data = DecorationMeshData::default();
+    for row in cell.1 - radius..=cell.1 + radius {
+        for col in cell.0 - radius..=cell.0 + radius {
+            let (w, d) = (col + width as i32 / 2, row + depth as i32 / 2);
+            if w < 0 || d < 0 || w >= width as i32 || d >= depth as i32 {
+                continue;
+            }
+            if !state.grass[d as usize * width + w as usize] {
+                continue;
+            }
+            let distance = ((col - cell.0) as f32).hypot((row - cell.1) as f32);
+            let falloff = 1.0 - distance / settings.radius as f32;
+            if falloff <= 0.0 {
+                continue;
+            }
+
+            let count = (settings.density as f32 * falloff).round() as u32;
+            for i in 0..count {
+                let seed = ((w as u32) << 20) ^ ((d as u32) << 8) ^ i;
+                let x = (col as f32 + random(seed)) * ODM_TILE_SCALE;
+                let z = (row as f32 + random(seed.wrapping_add(0x9e37))) * ODM_TILE_SCALE;
+                let position = Vec3::new(x, odm.0.height_at(x, z), z);
+                let size = settings.size * (0.75 + 0.5 * random(seed.wrapping_add(0x79b9)));
+                mesh_data.push(position, (size, size), [0.0, 0.0, 1.0, 1.0]);
+            }
+        }
+    }
+
+    commands.spawn((
+        Name::new("clutter"),
+        PbrBundle {
+            mesh: meshes.add(Mesh::from(mesh_data)),
+            material: state.material.clone(),
+            ..default()
+        },
+        Clutter,
+    ));
+}
+
+/// Stable pseudo random value in [0, 1) so the grass doesn't move when regenerated
+fn random(seed: u32) -> f32 {
+    let mut x = seed.wrapping_mul(0x9e3779b9);
+    x ^= x >> 16;
+    x = x.wrapping_mul(0x85ebca6b);
+    x ^= x >> 13;
+    (x & 0xffff) as f32 / 65536.0
+}
+
+/// A few green blades on a transparent background
+fn grass_tuft() -> Image {
+    const WIDTH: usize = 16;
+    const HEIGHT: usize = 32;
+    let mut data = vec![0; WIDTH * HEIGHT * 4];
+    for (i, x) in [1, 4, 7, 10, 13].into_iter().enumerate() {
+        let blade_height = HEIGHT - 4 * (i % 3);
+        for y in HEIGHT - blade_height..HEIGHT {
+            let offset = (y * WIDTH + x) * 4;
+            data[offset..offset + 4].copy_from_slice(&[40, 110 + (y * 3) as u8, 30, 255]);
+        }
+    }
+    Image::new(
+        Extent3d {
+            width: WIDTH as u32,
+            height: HEIGHT as u32,
+            depth_or_array_layers: 1,
+        },
+        TextureDimension::D2,
+        data,
+        TextureFormat::Rgba8UnormSrgb,
+        RenderAssetUsages::RENDER_WORLD,
+    )
+}
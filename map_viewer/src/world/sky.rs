@@ -1,18 +1,37 @@
-use bevy::{prelude::*, render::render_asset::RenderAssetUsages};
+use bevy::{
+    prelude::*,
+    render::{
+        render_asset::RenderAssetUsages,
+        render_resource::{Extent3d, TextureDimension, TextureFormat},
+    },
+};
 
-use crate::{despawn_all, GameState};
+use crate::{despawn_all, odm::LoadedOdm, GameState};
 
 use super::{InWorld, WorldSettings};
 
+const DEFAULT_SKY: &str = "sky01";
+const DEFAULT_HORIZON: [u8; 3] = [200, 215, 235];
+const DEFAULT_ZENITH: [u8; 3] = [60, 110, 190];
+
 pub struct SkyPlugin;
 
 impl Plugin for SkyPlugin {
     fn build(&self, app: &mut App) {
         app.add_systems(OnEnter(GameState::Game), sky_setup)
+            .add_systems(
+                Update,
+                update_sky
+                    .run_if(resource_exists_and_changed::<LoadedOdm>)
+                    .run_if(in_state(GameState::Game)),
+            )
             .add_systems(OnExit(GameState::Game), despawn_all::<InWorld>);
     }
 }
 
+#[derive(Component)]
+struct Sky;
+
 fn sky_setup(
     mut commands: Commands,
     mut images: ResMut<Assets<Image>>,
@@ -20,25 +39,78 @@ fn sky_setup(
     mut materials: ResMut<Assets<StandardMaterial>>,
     settings: Res<WorldSettings>,
 ) {
-    let image = bevy::render::texture::Image::from_dynamic(
-        settings.lod_manager.bitmap("sky01").unwrap(),
-        true,
-        RenderAssetUsages::RENDER_WORLD,
-    );
+    let image = sky_image(&settings, DEFAULT_SKY);
     let image_handle = images.add(image);
 
-    commands.spawn(PbrBundle {
-        mesh: meshes.add(Mesh::from(Cylinder::default())),
-        material: materials.add(StandardMaterial {
-            base_color_texture: Some(image_handle),
-            alpha_mode: AlphaMode::Opaque,
-            unlit: true,
-            flip_normal_map_y: true,
-            fog_enabled: true,
-            cull_mode: None,
+    commands.spawn((
+        Sky,
+        PbrBundle {
+            mesh: meshes.add(Mesh::from(Cylinder::default())),
+            material: materials.add(StandardMaterial {
+                base_color_texture: Some(image_handle),
+                alpha_mode: AlphaMode::Opaque,
+                unlit: true,
+                flip_normal_map_y: true,
+                fog_enabled: true,
+                cull_mode: None,
+                ..default()
+            }),
+            transform: Transform::from_scale(Vec3::splat(100_000_000.0)),
             ..default()
-        }),
-        transform: Transform::from_scale(Vec3::splat(100_000_000.0)),
-        ..default()
-    });
+        },
+    ));
+}
+
+/// Switches the sky to the one of the newly loaded map.
+fn update_sky(
+    odm: Res<LoadedOdm>,
+    settings: Res<WorldSettings>,
+    mut images: ResMut<Assets<Image>>,
+    mut materials: ResMut<Assets<StandardMaterial>>,
+    sky: Query<&Handle<StandardMaterial>, With<Sky>>,
+) {
+    let image = sky_image(&settings, odm.0.environment().sky_texture);
+    let image_handle = images.add(image);
+    for handle in &sky {
+        if let Some(material) = materials.get_mut(handle) {
+            material.base_color_texture = Some(image_handle.clone());
+        }
+    }
+}
+
+/// The sky bitmap when it exists, otherwise the default sky and lastly a default gradient.
+fn sky_image(settings: &WorldSettings, sky_texture: &str) -> Image {
+    let bitmap = settings
+        .lod_manager
+        .bitmap(sky_texture)
+        .or_else(|| settings.lod_manager.bitmap(DEFAULT_SKY));
+    match bitmap {
+        Some(bitmap) => Image::from_dynamic(bitmap, true, RenderAssetUsages::RENDER_WORLD),
+        None => sky_gradient(DEFAULT_HORIZON, DEFAULT_ZENITH),
+    }
+}
+
+/// Vertical gradient going from the zenith at the top to the horizon at the bottom.
+fn sky_gradient(horizon: [u8; 3], zenith: [u8; 3]) -> Image {
+    const HEIGHT: u32 = 64;
+    let mut data = Vec::with_capacity(HEIGHT as usize * 4);
+    for y in 0..HEIGHT {
+        let t = y as f32 / (HEIGHT - 1) as f32;
+        for c in 0..3 {
+            let (a, b) = (zenith[c] as f32, horizon[c] as f32);
+            data.push((a + (b - a) * t).round() as u8);
+        }
+        data.push(255);
+    }
+    Image::new(
+        Extent3d {
+            width: 1,
+            height: HEIGHT,
+            depth_or_array_layers: 1,
+        },
+        TextureDimension::D2,
+        data,
+        TextureFormat::Rgba8UnormSrgb,
+        RenderAssetUsages::RENDER_WORLD,
+    )
 }
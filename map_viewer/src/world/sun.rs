@@ -1,8 +1,8 @@
 use std::f32::consts::PI;
 
-use bevy::prelude::*;
+use bevy::{pbr::NotShadowReceiver, prelude::*};
 
-use crate::{despawn_all, GameState};
+use crate::{despawn_all, odm::TerrainMesh, GameState};
 
 use super::InWorld;
 
@@ -10,7 +10,14 @@ pub struct SunPlugin;
 
 impl Plugin for SunPlugin {
     fn build(&self, app: &mut App) {
-        app.add_systems(Update, (update_sun).run_if(in_state(GameState::Game)))
+        app.init_resource::<SunControl>()
+            .register_type::<SunControl>()
+            .init_resource::<ShadowSettings>()
+            .register_type::<ShadowSettings>()
+            .add_systems(
+                Update,
+                (update_sun, apply_shadow_settings).run_if(in_state(GameState::Game)),
+            )
             .add_systems(OnEnter(GameState::Game), sun_setup)
             .add_systems(OnExit(GameState::Game), despawn_all::<InWorld>);
     }
@@ -20,6 +27,7 @@ fn sun_setup(
     mut commands: Commands,
     mut meshes: ResMut<Assets<Mesh>>,
     mut materials: ResMut<Assets<StandardMaterial>>,
+    shadow_settings: Res<ShadowSettings>,
 ) {
     commands.insert_resource(AmbientLight {
         color: Color::WHITE,
@@ -49,7 +57,7 @@ fn sun_setup(
         Name::new("sun"),
         DirectionalLightBundle {
             directional_light: DirectionalLight {
-                shadows_enabled: true,
+                shadows_enabled: shadow_settings.enabled,
                 illuminance: 2000.,
                 ..default()
             },
@@ -61,7 +69,97 @@ fn sun_setup(
     ));
 }
 
-fn update_sun(time: Res<Time>, mut sun: Query<(&mut Transform, &mut Movable)>) {
+/// Manual sun placement, when `manual` is set the sun stops moving and
+/// its direction is taken from the azimuth and elevation (in degrees).
+#[derive(Resource, Reflect)]
+#[reflect(Resource)]
+pub struct SunControl {
+    pub manual: bool,
+    pub azimuth: f32,
+    pub elevation: f32,
+}
+
+impl Default for SunControl {
+    fn default() -> Self {
+        Self {
+            manual: false,
+            azimuth: 45.0,
+            elevation: 60.0,
+        }
+    }
+}
+
+impl SunControl {
+    /// Rotation of a light shining from the given azimuth and elevation.
+    pub fn rotation(&self) -> Quat {
+        Quat::from_euler(
+            EulerRot::YXZ,
+            self.azimuth.to_radians(),
+            -self.elevation.to_radians(),
+            0.0,
+        )
+    }
+
+    /// Direction the light travels, from the sun towards the ground.
+    pub fn direction(&self) -> Vec3 {
+        self.rotation() * Vec3::NEG_Z
+    }
+}
+
+/// Shadow casting from the sun and shadow receiving on the terrain,
+/// turning them off helps on low-end machines.
+#[derive(Resource, Reflect)]
+#[reflect(Resource)]
+pub struct ShadowSettings {
+    pub enabled: bool,
+}
+
+impl Default for ShadowSettings {
+    fn default() -> Self {
+        Self { enabled: true }
+    }
+}
+
+fn apply_shadow_settings(
+    mut commands: Commands,
+    shadow_settings: Res<ShadowSettings>,
+    mut lights: Query<&mut DirectionalLight>,
+    all_terrain: Query<Entity, With<TerrainMesh>>,
+    new_terrain: Query<Entity, Added<TerrainMesh>>,
+) {
+    let terrain: Vec<Entity> = if shadow_settings.is_changed() {
+        for mut light in &mut lights {
+            light.shadows_enabled = shadow_settings.enabled;
+        }
+        all_terrain.iter().collect()
+    } else {
+        new_terrain.iter().collect()
+    };
+
+    for entity in terrain {
+        if shadow_settings.enabled {
+            commands.entity(entity).remove::<NotShadowReceiver>();
+        } else {
+            commands.entity(entity).insert(NotShadowReceiver);
+        }
+    }
+}
+
+fn update_sun(
+    time: Res<Time>,
+    sun_control: Res<SunControl>,
+    mut sun: Query<(&mut Transform, &mut Movable)>,
+) {
+    if sun_control.manual {
+        let rotation = sun_control.rotation();
+        let direction = sun_control.direction();
+        for (mut transform, movable) in &mut sun {
+            transform.rotation = rotation;
+            transform.translation = -direction * movable.spawn.length();
+        }
+        return;
+    }
+
     for (mut transform, mut movable) in &mut sun {
         if (movable.spawn - transform.translation).length() > movable.max_distance {
             movable.speed *= -1.0;
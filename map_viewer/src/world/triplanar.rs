@@ -0,0 +1,200 @@
+use std::collections::HashMap;
+
+use bevy::{
+    asset::load_internal_asset,
+    pbr::{ExtendedMaterial, MaterialExtension},
+    prelude::*,
+    render::render_resource::{AsBindGroup, ShaderRef},
+};
+use lod::odm::ODM_TILE_SCALE;
+
+use crate::odm::{TerrainAtlas, TerrainMesh};
+
+const TRIPLANAR_SHADER_HANDLE: Handle<Shader> =
+    Handle::weak_from_u128(0x5d3c_86a1_2f4e_4b7a_9c11_e0f3_7a62_d8b4);
+
+pub type TriplanarMaterial = ExtendedMaterial<StandardMaterial, TriplanarExtension>;
+
+pub struct TriplanarPlugin;
+
+impl Plugin for TriplanarPlugin {
+    fn build(&self, app: &mut App) {
+        load_internal_asset!(
+            app,
+            TRIPLANAR_SHADER_HANDLE,
+            "triplanar.wgsl",
+            Shader::from_wgsl
+        );
+        app.add_plugins(MaterialPlugin::<TriplanarMaterial>::default())
+            .init_resource::<TriplanarSettings>()
+            .register_type::<TriplanarSettings>()
+            .add_systems(Update, apply_triplanar);
+    }
+}
+
+/// Projects the terrain textures along the world axes on steep slopes, where the
+/// top down uvs would stretch them. `slope_threshold` is the y of the surface normal
+/// below which the projection is used, blended over `blend`.
+#[derive(Resource, Reflect)]
+#[reflect(Resource)]
+pub struct TriplanarSettings {
+    pub enabled: bool,
+    pub slope_threshold: f32,
+    pub blend: f32,
+    pub sharpness: f32,
+}
+
+impl TriplanarSettings {
+    /// Range of the y of the surface normal over which the projection fades out:
+    /// fully projected below `x`, top down above `y`. The shader gets it as is.
+    fn slope_range(&self) -> Vec2 {
+        let lower = (self.slope_threshold - self.blend).clamp(0.0, 1.0);
+        let upper = self.slope_threshold.clamp(0.0, 1.0).max(lower + 1e-3);
+        Vec2::new(lower, upper)
+    }
+}
+
+impl Default for TriplanarSettings {
+    fn default() -> Self {
+        Self {
+            enabled: false,
+            slope_threshold: 0.7,
+            blend: 0.1,
+            sharpness: 4.0,
+        }
+    }
+}
+
+/// The uniforms are packed in vectors, see `Triplanar` in the shader
+#[derive(Asset, AsBindGroup, Reflect, Debug, Clone)]
+pub struct TriplanarExtension {
+    /// Tiles of the atlas along u and v, then the world size of a tile
+    #[uniform(100)]
+    atlas: Vec4,
+    /// Slope range, see [`TriplanarSettings::slope_range`], and sharpness of the projection
+    #[uniform(101)]
+    projection: Vec4,
+}
+
+impl MaterialExtension for TriplanarExtension {
+    fn fragment_shader() -> ShaderRef {
+        TRIPLANAR_SHADER_HANDLE.into()
+    }
+}
+
+impl TriplanarExtension {
+    fn new(settings: &TriplanarSettings, atlas_size: UVec2) -> Self {
+        Self {
+            atlas: atlas_size.as_vec2().extend(ODM_TILE_SCALE).extend(0.0),
+            projection: settings
+                .slope_range()
+                .extend(settings.sharpness)
+                .extend(0.0),
+        }
+    }
+}
+
+/// The standard material of the terrain, kept to switch back when triplanar is disabled
+#[derive(Component)]
+struct BaseTerrainMaterial(Handle<StandardMaterial>);
+
+type TerrainMaterials<'a> = (
+    Entity,
+    &'a TerrainAtlas,
+    Option<&'a Handle<StandardMaterial>>,
+    Option<&'a Handle<TriplanarMaterial>>,
+    Option<&'a BaseTerrainMaterial>,
+);
+
+/// Swaps the terrain material between the standard and triplanar ones, the chunks
+/// sharing a standard material also share the triplanar one.
+fn apply_triplanar(
+    mut commands: Commands,
+    settings: Res<TriplanarSettings>,
+    standard_materials: Res<Assets<StandardMaterial>>,
+    mut triplanar_materials: ResMut<Assets<TriplanarMaterial>>,
+    terrains: Query<TerrainMaterials, With<TerrainMesh>>,
+) {
+    let mut shared = HashMap::new();
+    for (entity, atlas, standard, triplanar, base) in &terrains {
+        match (settings.enabled, standard, triplanar) {
+            (true, Some(standard), _) => {
+                let Some(material) = standard_materials.get(standard) else {
+                    continue;
+                };
+                let handle = shared
+                    .entry(standard.id())
+                    .or_insert_with(|| {
+                        triplanar_materials.add(TriplanarMaterial {
+                            base: material.clone(),
+                            extension: TriplanarExtension::new(&settings, atlas.0),
+                        })
+                    })
+                    .clone();
+                commands
+                    .entity(entity)
+                    .remove::<Handle<StandardMaterial>>()
+                    .insert((handle, BaseTerrainMaterial(standard.clone())));
+            }
+            (true, None, Some(triplanar)) if settings.is_changed() => {
+                if let Some(material) = triplanar_materials.get_mut(triplanar) {
+                    material.extension = TriplanarExtension::new(&settings, atlas.0);
+                }
+            }
+            (false, None, Some(_)) => {
+                if let Some(base) = base {
+                    commands
+                        .entity(entity)
+                        .remove::<(Handle<TriplanarMaterial>, BaseTerrainMaterial)>()
+                        .insert(base.0.clone());
+                }
+            }
+            _ => {}
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    /// How much of a surface with this normal uses the projection, from 0 (flat)
+    /// to 1 (steep), as the shader computes it from the uniform.
+    fn steepness(settings: &TriplanarSettings, normal: Vec3) -> f32 {
+        let range = TriplanarExtension::new(settings, UVec2::ONE).projection;
+        let t = ((normal.normalize().y.abs() - range.x) / (range.y - range.x)).clamp(0.0, 1.0);
+        1.0 - t * t * (3.0 - 2.0 * t)
+    }
+
+    #[test]
+    fn steepness_works() {
+        let settings = TriplanarSettings::default();
+
+        assert_eq!(steepness(&settings, Vec3::Y), 0.0);
+        assert_eq!(steepness(&settings, Vec3::NEG_Y), 0.0);
+        assert_eq!(steepness(&settings, Vec3::X), 1.0);
+        assert_eq!(steepness(&settings, Vec3::new(0.0, 1.0, 1.0)), 0.0);
+        assert_eq!(steepness(&settings, Vec3::new(0.0, 1.0, 2.0)), 1.0);
+
+        let threshold = Vec3::new(0.0, 0.65, (1.0f32 - 0.65 * 0.65).sqrt());
+        assert!((steepness(&settings, threshold) - 0.5).abs() < 1e-3);
+    }
+
+    #[test]
+    fn slope_range_works() {
+        let settings = TriplanarSettings {
+            slope_threshold: 0.05,
+            blend: 0.1,
+            ..default()
+        };
+        assert_eq!(settings.slope_range(), Vec2::new(0.0, 0.05));
+
+        let settings = TriplanarSettings {
+            slope_threshold: 0.5,
+            blend: 0.0,
+            ..default()
+        };
+        let range = settings.slope_range();
+        assert!(range.x < range.y);
+    }
+}
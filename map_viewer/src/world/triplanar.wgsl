@@ -0,0 +1,71 @@
+#import bevy_pbr::{
+    pbr_bindings,
+    pbr_functions::alpha_discard,
+    pbr_fragment::pbr_input_from_standard_material,
+}
+
+#ifdef PREPASS_PIPELINE
+#import bevy_pbr::{
+    prepass_io::{VertexOutput, FragmentOutput},
+    pbr_deferred_functions::deferred_output,
+}
+#else
+#import bevy_pbr::{
+    forward_io::{VertexOutput, FragmentOutput},
+    pbr_functions::{apply_pbr_lighting, main_pass_post_lighting_processing},
+    pbr_types::STANDARD_MATERIAL_FLAGS_UNLIT_BIT,
+}
+#endif
+
+// xy: number of tiles in the atlas along u and v, z: world size of a tile
+@group(2) @binding(100) var<uniform> atlas: vec4<f32>;
+// xy: range of the y of the normal over which the triplanar projection fades out,
+// z: sharpness of the blend between the axes
+@group(2) @binding(101) var<uniform> projection: vec4<f32>;
+
+// samples the atlas tile `cell` at the repeating coordinates `local`
+fn sample_tile(cell: vec2<f32>, local: vec2<f32>) -> vec4<f32> {
+    let uv = (cell + fract(local)) / atlas.xy;
+    return textureSampleLevel(pbr_bindings::base_color_texture, pbr_bindings::base_color_sampler, uv, 0.0);
+}
+
+@fragment
+fn fragment(
+    in: VertexOutput,
+    @builtin(front_facing) is_front: bool,
+) -> FragmentOutput {
+    var pbr_input = pbr_input_from_standard_material(in, is_front);
+
+#ifdef VERTEX_UVS
+    let normal = normalize(in.world_normal);
+    let steepness = 1.0 - smoothstep(projection.x, projection.y, abs(normal.y));
+    let cell = floor(in.uv * atlas.xy);
+    let p = in.world_position.xyz / atlas.z;
+    var weights = pow(abs(normal), vec3<f32>(projection.z));
+    weights = weights / (weights.x + weights.y + weights.z);
+    let projected = sample_tile(cell, vec2<f32>(p.z, -p.y)) * weights.x
+        + sample_tile(cell, p.xz) * weights.y
+        + sample_tile(cell, vec2<f32>(p.x, -p.y)) * weights.z;
+    pbr_input.material.base_color = mix(
+        pbr_input.material.base_color,
+        pbr_bindings::material.base_color * projected,
+        steepness,
+    );
+#endif
+
+    pbr_input.material.base_color = alpha_discard(pbr_input.material, pbr_input.material.base_color);
+
+#ifdef PREPASS_PIPELINE
+    let out = deferred_output(in, pbr_input);
+#else
+    var out: FragmentOutput;
+    if (pbr_input.material.flags & STANDARD_MATERIAL_FLAGS_UNLIT_BIT) == 0u {
+        out.color = apply_pbr_lighting(pbr_input);
+    } else {
+        out.color = pbr_input.material.base_color;
+    }
+    out.color = main_pass_post_lighting_processing(pbr_input, out.color);
+#endif
+
+    return out;
+}
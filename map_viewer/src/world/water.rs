@@ -0,0 +1,107 @@
+use std::f32::consts::TAU;
+
+use bevy::prelude::*;
+
+use crate::GameState;
+
+/// Level of the water surface at low tide, just above the terrain water tiles
+const WATER_BASE_LEVEL: f32 = 4.0;
+
+pub struct WaterPlugin;
+
+impl Plugin for WaterPlugin {
+    fn build(&self, app: &mut App) {
+        app.init_resource::<Tide>()
+            .register_type::<Tide>()
+            .add_systems(Update, animate_tide.run_if(in_state(GameState::Game)));
+    }
+}
+
+/// A gentle rise and fall of the sea, `period` is in seconds.
+#[derive(Resource, Reflect)]
+#[reflect(Resource)]
+pub struct Tide {
+    pub amplitude: f32,
+    pub period: f32,
+}
+
+impl Default for Tide {
+    fn default() -> Self {
+        Self {
+            amplitude: 24.0,
+            period: 20.0,
+        }
+    }
+}
+
+impl Tide {
+    /// Height of the water surface after `seconds`, between the base level and `amplitude` above it.
+    pub fn level(&self, seconds: f32) -> f32 {
+        if self.period <= 0.0 {
+            return WATER_BASE_LEVEL;
+        }
+        let phase = (TAU * seconds / self.period).sin();
+        WATER_BASE_LEVEL + self.amplitude * (1.0 + phase) / 2.0
+    }
+}
+
+#[derive(Component)]
+pub struct Water;
+
+/// A translucent plane covering the whole map at sea level
+pub(crate) fn water_bundle(
+    size: Vec2,
+    meshes: &mut Assets<Mesh>,
+    materials: &mut Assets<StandardMaterial>,
+) -> (Name, PbrBundle, Water) {
+    (
+        Name::new("water"),
+        PbrBundle {
+            mesh: meshes.add(Plane3d::default().mesh().size(size.x, size.y)),
+            material: materials.add(StandardMaterial {
+                base_color: Color::rgba(0.1, 0.25, 0.45, 0.6),
+                alpha_mode: AlphaMode::Blend,
+                perceptual_roughness: 0.3,
+                ..default()
+            }),
+            transform: Transform::from_xyz(0.0, WATER_BASE_LEVEL, 0.0),
+            ..default()
+        },
+        Water,
+    )
+}
+
+fn animate_tide(time: Res<Time>, tide: Res<Tide>, mut query: Query<&mut Transform, With<Water>>) {
+    let level = tide.level(time.elapsed_seconds());
+    for mut transform in &mut query {
+        transform.translation.y = level;
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn tide_level_works() {
+        let tide = Tide::default();
+        let high = WATER_BASE_LEVEL + tide.amplitude;
+        let eps = 1e-3;
+
+        assert!((tide.level(0.0) - (WATER_BASE_LEVEL + high) / 2.0).abs() < eps);
+        assert!((tide.level(tide.period / 4.0) - high).abs() < eps);
+        assert!((tide.level(tide.period * 3.0 / 4.0) - WATER_BASE_LEVEL).abs() < eps);
+        assert!((tide.level(tide.period) - tide.level(0.0)).abs() < eps);
+
+        for step in 0..=100 {
+            let level = tide.level(tide.period * step as f32 / 100.0);
+            assert!((WATER_BASE_LEVEL - eps..=high + eps).contains(&level));
+        }
+
+        let still = Tide {
+            period: 0.0,
+            ..default()
+        };
+        assert_eq!(still.level(5.0), WATER_BASE_LEVEL);
+    }
+}